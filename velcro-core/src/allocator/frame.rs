@@ -0,0 +1,141 @@
+use crate::allocator::{raw::Buffer, AllocError, RawAllocator};
+use std::{
+    alloc::Layout,
+    cell::{Cell, RefCell},
+    fmt,
+    ptr::NonNull,
+};
+
+/// A bump allocator for temporary data of a frame, which is released all at once with
+/// [`reset`](Self::reset) at the beginning of every tick.
+///
+/// Unlike [`LinearAllocator`](super::LinearAllocator) it never runs out of memory: when the
+/// current chunk is full, a new one (twice as large) is taken from the heap. On reset all chunks
+/// are merged into one that is big enough for the whole frame, so after a few frames the
+/// allocator stops touching the heap.
+pub struct FrameAllocator {
+    chunks: RefCell<Vec<Buffer>>,
+    /// Offset in the last chunk.
+    offset: Cell<usize>,
+    used: Cell<usize>,
+    peak: usize,
+}
+
+impl fmt::Debug for FrameAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameAllocator")
+            .field("used", &self.used())
+            .field("peak", &self.peak())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl Default for FrameAllocator {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl FrameAllocator {
+    pub const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            chunks: RefCell::new(vec![Buffer::new(capacity)]),
+            offset: Cell::new(0),
+            used: Cell::new(0),
+            peak: 0,
+        }
+    }
+
+    /// Returns the amount of bytes used in the current frame, including alignment padding.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    /// Returns the biggest amount of bytes used in a frame so far.
+    #[inline]
+    pub fn peak(&self) -> usize {
+        self.peak.max(self.used())
+    }
+
+    /// Returns the total size of all chunks.
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().iter().map(Buffer::len).sum()
+    }
+
+    /// Releases all memory of the frame, should be called once per tick.
+    pub fn reset(&mut self) {
+        self.peak = self.peak();
+        self.used.set(0);
+        self.offset.set(0);
+
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            let capacity = chunks.iter().map(Buffer::len).sum();
+            *chunks = vec![Buffer::new(capacity)];
+        }
+    }
+}
+
+// SAFETY: Blocks never overlap, chunks are never freed while they can be borrowed, and the offset
+// moves back only in `reset`, which requires `&mut self`.
+unsafe impl RawAllocator for FrameAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let mut chunks = self.chunks.borrow_mut();
+        let last = chunks.last().expect("there is always at least one chunk");
+
+        let (ptr, end) = match last.bump(self.offset.get(), layout) {
+            Some(block) => block,
+            None => {
+                let len = (last.len() * 2).max(layout.size() + layout.align());
+                // The rest of the full chunk is wasted.
+                self.used
+                    .set(self.used.get() + last.len() - self.offset.get());
+                self.offset.set(0);
+                chunks.push(Buffer::new(len));
+                chunks
+                    .last()
+                    .unwrap()
+                    .bump(0, layout)
+                    .expect("new chunk must fit the layout")
+            }
+        };
+
+        self.used.set(self.used.get() + end - self.offset.get());
+        self.offset.set(end);
+        Ok(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_allocator_grows_and_merges_chunks() {
+        let mut allocator = FrameAllocator::new(16);
+        for frame in 0..3 {
+            let values = (0..10)
+                .map(|i| allocator.alloc(i * frame).unwrap())
+                .collect::<Vec<_>>();
+            assert!(values.iter().enumerate().all(|(i, v)| **v == i * frame));
+            allocator.reset();
+        }
+
+        let capacity = allocator.capacity();
+        assert!(capacity >= 10 * std::mem::size_of::<usize>());
+        assert!(allocator.peak() <= capacity);
+
+        // The merged chunk fits the whole frame.
+        for i in 0..10usize {
+            allocator.alloc(i).unwrap();
+        }
+        assert_eq!(allocator.chunks.borrow().len(), 1);
+    }
+}
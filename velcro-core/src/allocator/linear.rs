@@ -0,0 +1,87 @@
+use crate::allocator::{raw::Buffer, AllocError, RawAllocator};
+use std::{alloc::Layout, cell::Cell, fmt, ptr::NonNull};
+
+/// A bump allocator with fixed capacity. Allocation moves a pointer forward, individual blocks
+/// can't be freed, all memory is released at once with [`reset`](Self::reset).
+pub struct LinearAllocator {
+    buffer: Buffer,
+    offset: Cell<usize>,
+}
+
+impl fmt::Debug for LinearAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LinearAllocator")
+            .field("used", &self.used())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl LinearAllocator {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Buffer::new(capacity),
+            offset: Cell::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the amount of used bytes, including alignment padding.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.capacity() - self.used()
+    }
+
+    /// Releases all allocated memory.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.offset.set(0);
+    }
+}
+
+// SAFETY: Blocks never overlap, the offset moves back only in `reset`, which requires `&mut self`.
+unsafe impl RawAllocator for LinearAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let (ptr, end) = self
+            .buffer
+            .bump(self.offset.get(), layout)
+            .ok_or(AllocError)?;
+        self.offset.set(end);
+        Ok(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_allocator_bumps_and_resets() {
+        let mut allocator = LinearAllocator::new(64);
+        let a = allocator.alloc(1u8).unwrap();
+        let b = allocator.alloc(2u64).unwrap();
+        assert_eq!(b as *mut u64 as usize % 8, 0);
+        assert_eq!((*a, *b), (1, 2));
+        assert_eq!(allocator.used(), 16);
+
+        let slice = allocator.alloc_slice_fill_with(8, |i| i as u32).unwrap();
+        assert_eq!(slice[7], 7);
+        assert!(allocator.alloc([0u8; 64]).is_err());
+
+        allocator.reset();
+        assert_eq!(allocator.used(), 0);
+        assert_eq!(*allocator.alloc_slice_copy(&[3u8; 64]).unwrap(), [3u8; 64]);
+    }
+}
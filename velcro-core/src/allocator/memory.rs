@@ -1,4 +1,6 @@
 use std::cell::UnsafeCell;
+#[cfg(feature = "alloc-tracking")]
+use std::panic::Location;
 
 pub trait MemoryBlock: Sized {
     type Element: Sized;
@@ -58,16 +60,23 @@ impl<T> MemoryBlock for Option<T> {
     }
 }
 
+/// A slot for one element. With the `alloc-tracking` feature it keeps per-type statistics of
+/// `velcro_rtti::memory::allocation_stats` in sync and remembers where the element was allocated.
+/// Dropping a slot together with its element is normal ownership, only pools report leaks.
 #[derive(Debug)]
-pub struct Memory<P>(pub UnsafeCell<P>);
+pub struct Memory<P: MemoryBlock>(
+    pub UnsafeCell<P>,
+    #[cfg(feature = "alloc-tracking")] Option<&'static Location<'static>>,
+);
 
 impl<T, P> Clone for Memory<P>
 where
     T: Sized,
     P: MemoryBlock<Element = T> + Clone,
 {
+    #[cfg_attr(feature = "alloc-tracking", track_caller)]
     fn clone(&self) -> Self {
-        Self(UnsafeCell::new(self.get().clone()))
+        Self::from_block(self.get().clone())
     }
 }
 
@@ -76,12 +85,30 @@ where
     T: Sized,
     P: MemoryBlock<Element = T>,
 {
+    #[cfg_attr(feature = "alloc-tracking", track_caller)]
     pub fn new(data: T) -> Self {
-        Self(UnsafeCell::new(P::new(data)))
+        Self::from_block(P::new(data))
     }
 
     pub fn new_empty() -> Self {
-        Self(UnsafeCell::new(P::new_empty()))
+        Self::from_block(P::new_empty())
+    }
+
+    #[cfg_attr(feature = "alloc-tracking", track_caller)]
+    fn from_block(block: P) -> Self {
+        #[cfg(feature = "alloc-tracking")]
+        {
+            let site = if block.is_some() {
+                velcro_rtti::memory::track_spawn::<T>(1);
+                Some(Location::caller())
+            } else {
+                None
+            };
+            Self(UnsafeCell::new(block), site)
+        }
+
+        #[cfg(not(feature = "alloc-tracking"))]
+        Self(UnsafeCell::new(block))
     }
 
     pub fn get(&self) -> &P {
@@ -104,29 +131,96 @@ where
         self.get_mut().as_mut()
     }
 
+    #[cfg_attr(feature = "alloc-tracking", track_caller)]
     pub fn replace(&mut self, element: T) -> Option<T> {
-        self.get_mut().replace(element)
+        let old = self.get_mut().replace(element);
+        #[cfg(feature = "alloc-tracking")]
+        if old.is_none() {
+            velcro_rtti::memory::track_spawn::<T>(1);
+            self.1 = Some(Location::caller());
+        }
+        old
     }
 
     pub fn take(&mut self) -> Option<T> {
-        self.get_mut().take()
+        let element = self.get_mut().take();
+        #[cfg(feature = "alloc-tracking")]
+        if element.is_some() {
+            velcro_rtti::memory::track_free::<T>(1);
+            self.1 = None;
+        }
+        element
+    }
+
+    /// Returns where the element was allocated, `None` if the slot is empty.
+    #[cfg(feature = "alloc-tracking")]
+    pub fn site(&self) -> Option<&'static Location<'static>> {
+        self.1
     }
 }
 
-// SAFETY: This is safe, because Payload is never directly exposed to the call site. It is always
+/// Keeps per-type statistics of `velcro_rtti::memory::allocation_stats` in sync.
+#[cfg(feature = "alloc-tracking")]
+impl<P: MemoryBlock> Drop for Memory<P> {
+    fn drop(&mut self) {
+        if self.0.get_mut().is_some() {
+            velcro_rtti::memory::track_free::<P::Element>(1);
+        }
+    }
+}
+
+// SAFETY: This is safe, because Memory is never directly exposed to the call site. It is always
 // accessed using a sort of read-write lock that forces borrowing rules at runtime.
-unsafe impl<T, P> Sync for Payload<P>
+unsafe impl<T, P> Sync for Memory<P>
 where
     T: Sized,
     P: MemoryBlock<Element = T>,
 {
 }
 
-// SAFETY: This is safe, because Payload is never directly exposed to the call site. It is always
+// SAFETY: This is safe, because Memory is never directly exposed to the call site. It is always
 // accessed using a sort of read-write lock that forces borrowing rules at runtime.
-unsafe impl<T, P> Send for Payload<P>
+unsafe impl<T, P> Send for Memory<P>
 where
     T: Sized,
     P: MemoryBlock<Element = T>,
 {
-}
\ No newline at end of file
+}
+#[cfg(all(test, feature = "alloc-tracking"))]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use velcro_rtti::memory::{allocation_stats_of, set_leak_handler, LeakReport};
+
+    #[derive(Clone)]
+    struct Slot(#[allow(dead_code)] u32);
+
+    static REPORTS: Mutex<Vec<LeakReport>> = Mutex::new(Vec::new());
+
+    fn collect_report(report: &LeakReport) {
+        if report.type_name == std::any::type_name::<Slot>() {
+            REPORTS.lock().push(report.clone());
+        }
+    }
+
+    #[test]
+    fn memory_tracks_sites_and_stats() {
+        set_leak_handler(collect_report);
+
+        let (mut memory, line) = (Memory::<Option<Slot>>::new(Slot(1)), line!());
+        assert_eq!(memory.site().unwrap().line(), line);
+        let (copy, line) = (memory.clone(), line!());
+        assert_eq!(copy.site().unwrap().line(), line);
+        assert_eq!(allocation_stats_of::<Slot>().unwrap().live, 2);
+
+        assert!(memory.take().is_some());
+        assert!(memory.site().is_none());
+        drop(memory);
+        assert!(REPORTS.lock().is_empty());
+
+        // A slot dropped with its element owns it, it is not a leak.
+        drop(copy);
+        assert_eq!(allocation_stats_of::<Slot>().unwrap().live, 0);
+        assert!(REPORTS.lock().is_empty());
+    }
+}
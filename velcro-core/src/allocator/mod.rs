@@ -0,0 +1,21 @@
+//! Memory allocators.
+//!
+//! Besides [`Memory`], the module has allocators for short-lived data that would otherwise go
+//! through the global heap: [`LinearAllocator`] and [`FrameAllocator`] (bump arenas released all
+//! at once), [`StackAllocator`] (LIFO with markers) and [`PoolAllocator`] (blocks of the same
+//! size). All of them implement [`RawAllocator`], which has typed helpers such as
+//! [`RawAllocator::alloc`].
+
+mod memory;
+mod raw;
+mod linear;
+mod frame;
+mod stack;
+mod pool;
+
+pub use memory::*;
+pub use raw::{AllocBox, AllocError, RawAllocator};
+pub use linear::*;
+pub use frame::*;
+pub use stack::*;
+pub use pool::*;
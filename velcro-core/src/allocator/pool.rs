@@ -0,0 +1,157 @@
+use crate::allocator::{raw::Buffer, AllocError, RawAllocator};
+use std::{alloc::Layout, cell::Cell, fmt, mem, ptr::NonNull};
+
+/// An allocator of blocks of the same size with fixed capacity. Free blocks are kept in an
+/// intrusive list, so allocation and deallocation are `O(1)` and never touch the heap.
+///
+/// Requests for blocks that are bigger (or have bigger alignment) than the block of the pool
+/// fail with [`AllocError`].
+pub struct PoolAllocator {
+    buffer: Buffer,
+    block_layout: Layout,
+    block_count: usize,
+    /// Head of the list of released blocks, each of them stores a pointer to the next one.
+    free_head: Cell<Option<NonNull<u8>>>,
+    /// Blocks after this one were never allocated.
+    untouched: Cell<usize>,
+    allocated: Cell<usize>,
+}
+
+impl fmt::Debug for PoolAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolAllocator")
+            .field("block_size", &self.block_size())
+            .field("allocated", &self.allocated())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl PoolAllocator {
+    /// Creates a pool of `block_count` blocks that fit the layout.
+    pub fn new(block_layout: Layout, block_count: usize) -> Self {
+        // A free block must be able to hold a pointer to the next free block.
+        let block_layout = Layout::from_size_align(
+            block_layout.size().max(mem::size_of::<usize>()),
+            block_layout.align().max(mem::align_of::<usize>()),
+        )
+        .expect("invalid block layout")
+        .pad_to_align();
+
+        let len = block_layout
+            .size()
+            .checked_mul(block_count)
+            .and_then(|len| len.checked_add(block_layout.align()))
+            .expect("pool is too big");
+
+        Self {
+            buffer: Buffer::new(len),
+            block_layout,
+            block_count,
+            free_head: Cell::new(None),
+            untouched: Cell::new(0),
+            allocated: Cell::new(0),
+        }
+    }
+
+    /// Creates a pool for objects of the type.
+    pub fn for_type<T>(block_count: usize) -> Self {
+        Self::new(Layout::new::<T>(), block_count)
+    }
+
+    /// Returns size of a block, it can be bigger than requested in [`Self::new`].
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        self.block_layout.size()
+    }
+
+    /// Returns the total number of blocks.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.block_count
+    }
+
+    /// Returns the number of allocated blocks.
+    #[inline]
+    pub fn allocated(&self) -> usize {
+        self.allocated.get()
+    }
+
+    /// Releases all blocks.
+    pub fn reset(&mut self) {
+        self.free_head.set(None);
+        self.untouched.set(0);
+        self.allocated.set(0);
+    }
+}
+
+// SAFETY: Every block is given out at most once until it is deallocated, blocks don't overlap
+// and fit the layout of the pool, which is checked against the requested layout.
+unsafe impl RawAllocator for PoolAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() > self.block_layout.size() || layout.align() > self.block_layout.align()
+        {
+            return Err(AllocError);
+        }
+
+        let block = match self.free_head.get() {
+            Some(block) => {
+                // SAFETY: Free blocks store a pointer to the next free block.
+                let next = unsafe { block.cast::<Option<NonNull<u8>>>().as_ptr().read() };
+                self.free_head.set(next);
+                block
+            }
+            None => {
+                let index = self.untouched.get();
+                if index == self.block_count {
+                    return Err(AllocError);
+                }
+                self.untouched.set(index + 1);
+
+                let (block, _) = self
+                    .buffer
+                    .bump(index * self.block_layout.size(), self.block_layout)
+                    .expect("blocks must fit the buffer");
+                block
+            }
+        };
+
+        self.allocated.set(self.allocated.get() + 1);
+        Ok(block)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        ptr.cast::<Option<NonNull<u8>>>()
+            .as_ptr()
+            .write(self.free_head.get());
+        self.free_head.set(Some(ptr));
+        self.allocated.set(self.allocated.get() - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_allocator_reuses_blocks() {
+        let mut pool = PoolAllocator::for_type::<[u64; 2]>(2);
+        assert_eq!(pool.block_size(), 16);
+
+        let first = pool.alloc_boxed([1u64, 2]).unwrap();
+        let second = pool.alloc_boxed([3u64, 4]).unwrap();
+        assert!(pool.alloc_boxed([0u64; 2]).is_err());
+        assert!(pool.alloc([0u64; 4]).is_err());
+
+        let address = &*second as *const _ as usize;
+        drop(second);
+        let third = pool.alloc_boxed([5u64, 6]).unwrap();
+        assert_eq!(&*third as *const _ as usize, address);
+        assert_eq!(first[1] + third[0], 7);
+        assert_eq!(pool.allocated(), 2);
+
+        drop((first, third));
+        pool.reset();
+        assert_eq!(pool.allocated(), 0);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    alloc::Layout,
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// The allocator has no memory left for the requested layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl Display for AllocError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "memory allocation failed")
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+/// A stable counterpart of `std::alloc::Allocator` implemented by the allocators of this module.
+/// Memory is allocated through a shared reference, so many objects can be allocated and used at
+/// the same time. Releasing all memory at once (`reset` and friends) requires a mutable reference,
+/// which guarantees that nothing allocated earlier is still borrowed.
+///
+/// Typed helpers ([`alloc`](Self::alloc) and friends) never run destructors of the objects,
+/// use [`alloc_boxed`](Self::alloc_boxed) for objects that must be dropped.
+///
+/// # Safety
+///
+/// Implementations must return blocks that fit the layout and do not overlap any other block
+/// that is still allocated. Blocks must stay valid until they are deallocated or the allocator
+/// is reset through a mutable reference.
+pub unsafe trait RawAllocator {
+    /// Allocates a block of memory that fits the layout.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// Gives a block back to the allocator. Allocators that release memory all at once may ignore
+    /// it.
+    ///
+    /// # Safety
+    ///
+    /// The block must be allocated by this allocator with the same layout and must not be used
+    /// after the call.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Moves the value into the allocator.
+    #[allow(clippy::mut_from_ref)]
+    fn alloc<T>(&self, value: T) -> Result<&mut T, AllocError>
+    where
+        Self: Sized,
+    {
+        let ptr = self.allocate(Layout::new::<T>())?.cast::<T>();
+        // SAFETY: The block fits `T` and is not used by anything else.
+        unsafe {
+            ptr.as_ptr().write(value);
+            Ok(&mut *ptr.as_ptr())
+        }
+    }
+
+    /// Copies the slice into the allocator.
+    #[allow(clippy::mut_from_ref)]
+    fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> Result<&mut [T], AllocError>
+    where
+        Self: Sized,
+    {
+        let layout = Layout::array::<T>(src.len()).map_err(|_| AllocError)?;
+        let ptr = self.allocate(layout)?.cast::<T>();
+        // SAFETY: The block fits the slice and is not used by anything else.
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), ptr.as_ptr(), src.len());
+            Ok(slice::from_raw_parts_mut(ptr.as_ptr(), src.len()))
+        }
+    }
+
+    /// Allocates a slice and fills it with values returned by the function for each index.
+    #[allow(clippy::mut_from_ref)]
+    fn alloc_slice_fill_with<T, F>(&self, len: usize, mut func: F) -> Result<&mut [T], AllocError>
+    where
+        Self: Sized,
+        F: FnMut(usize) -> T,
+    {
+        let layout = Layout::array::<T>(len).map_err(|_| AllocError)?;
+        let ptr = self.allocate(layout)?.cast::<T>();
+        // SAFETY: The block fits the slice and is not used by anything else. If the function
+        // panics, the initialized items are leaked.
+        unsafe {
+            for i in 0..len {
+                ptr.as_ptr().add(i).write(func(i));
+            }
+            Ok(slice::from_raw_parts_mut(ptr.as_ptr(), len))
+        }
+    }
+
+    /// Moves the value into the allocator, the value is dropped and its memory is given back to
+    /// the allocator when the box is dropped.
+    fn alloc_boxed<T>(&self, value: T) -> Result<AllocBox<'_, T, Self>, AllocError>
+    where
+        Self: Sized,
+    {
+        let ptr = self.allocate(Layout::new::<T>())?.cast::<T>();
+        // SAFETY: The block fits `T` and is not used by anything else.
+        unsafe { ptr.as_ptr().write(value) };
+        Ok(AllocBox {
+            ptr,
+            allocator: self,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// An owned value in memory of a [`RawAllocator`], created with
+/// [`RawAllocator::alloc_boxed`].
+pub struct AllocBox<'a, T, A: RawAllocator> {
+    ptr: NonNull<T>,
+    allocator: &'a A,
+    marker: PhantomData<T>,
+}
+
+impl<T, A: RawAllocator> AllocBox<'_, T, A> {
+    /// Moves the value out of the allocator.
+    pub fn into_inner(this: Self) -> T {
+        // SAFETY: The value is initialized, the box is forgotten, so it won't be dropped twice.
+        let value = unsafe { this.ptr.as_ptr().read() };
+        unsafe {
+            this.allocator
+                .deallocate(this.ptr.cast(), Layout::new::<T>())
+        };
+        mem::forget(this);
+        value
+    }
+}
+
+impl<T, A: RawAllocator> Deref for AllocBox<'_, T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The value is initialized and owned by the box.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T, A: RawAllocator> DerefMut for AllocBox<'_, T, A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The value is initialized and owned by the box.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: Debug, A: RawAllocator> Debug for AllocBox<'_, T, A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T, A: RawAllocator> Drop for AllocBox<'_, T, A> {
+    fn drop(&mut self) {
+        // SAFETY: The value is initialized and owned by the box, the block was allocated by the
+        // allocator with the layout of `T`.
+        unsafe {
+            ptr::drop_in_place(self.ptr.as_ptr());
+            self.allocator
+                .deallocate(self.ptr.cast(), Layout::new::<T>());
+        }
+    }
+}
+
+/// Alignment of the memory of buffers, allocations with bigger alignment are padded.
+const BUFFER_ALIGN: usize = 16;
+
+/// A block of heap memory that allocators take their memory from.
+pub(super) struct Buffer {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Buffer {
+    pub(super) fn new(len: usize) -> Self {
+        if len == 0 {
+            return Self {
+                ptr: NonNull::dangling(),
+                len,
+            };
+        }
+
+        let layout = Self::layout(len);
+        // SAFETY: The layout has non-zero size.
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        match NonNull::new(ptr) {
+            Some(ptr) => Self { ptr, len },
+            None => std::alloc::handle_alloc_error(layout),
+        }
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len, BUFFER_ALIGN).expect("buffer is too big")
+    }
+
+    #[inline]
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the offset of the pointer in the buffer.
+    #[inline]
+    pub(super) fn offset_of(&self, ptr: NonNull<u8>) -> usize {
+        ptr.as_ptr() as usize - self.ptr.as_ptr() as usize
+    }
+
+    /// Places a block with the layout at the first suitable address starting from the offset.
+    /// Returns the block and the offset right after it, `None` if there is not enough space.
+    pub(super) fn bump(&self, offset: usize, layout: Layout) -> Option<(NonNull<u8>, usize)> {
+        let base = self.ptr.as_ptr() as usize;
+        let start = base.checked_add(offset)?;
+        let aligned = start.checked_add(layout.align() - 1)? & !(layout.align() - 1);
+        let end = aligned.checked_add(layout.size())? - base;
+        if end > self.len {
+            return None;
+        }
+
+        // SAFETY: The block is inside of the buffer.
+        let ptr = unsafe { NonNull::new_unchecked(self.ptr.as_ptr().add(aligned - base)) };
+        Some((ptr, end))
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // SAFETY: The memory was allocated with the same layout.
+            unsafe { std::alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.len)) };
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::allocator::{raw::Buffer, AllocError, RawAllocator};
+use std::{alloc::Layout, cell::Cell, fmt, ptr::NonNull};
+
+/// A position in a [`StackAllocator`], everything allocated after it can be released with
+/// [`StackAllocator::free_to_marker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StackMarker(usize);
+
+/// A LIFO allocator with fixed capacity. Memory is released in the reverse order of allocation:
+/// either block by block (only the top block is actually released by
+/// [`deallocate`](RawAllocator::deallocate)), or everything above a [`StackMarker`] at once.
+///
+/// [`scope`](Self::scope) is the most convenient way to use it: everything allocated through the
+/// [`StackScope`] is released when the scope ends, and the borrow checker makes sure that nothing
+/// allocated there is used after that.
+pub struct StackAllocator {
+    buffer: Buffer,
+    top: Cell<usize>,
+}
+
+impl fmt::Debug for StackAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackAllocator")
+            .field("used", &self.used())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl StackAllocator {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Buffer::new(capacity),
+            top: Cell::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the amount of used bytes, including alignment padding.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.top.get()
+    }
+
+    /// Returns the current top of the stack.
+    #[inline]
+    pub fn marker(&self) -> StackMarker {
+        StackMarker(self.top.get())
+    }
+
+    /// Releases everything allocated after the marker was taken.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the marker is above the top of the stack, which means that the memory it points
+    /// to was released already.
+    pub fn free_to_marker(&mut self, marker: StackMarker) {
+        assert!(
+            marker.0 <= self.top.get(),
+            "Stack marker {} is above the top of the stack {}!",
+            marker.0,
+            self.top.get()
+        );
+        self.top.set(marker.0);
+    }
+
+    /// Releases all allocated memory.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.top.set(0);
+    }
+
+    /// Calls the function with a [`StackScope`] and releases everything allocated through it when
+    /// the function returns. The allocator is borrowed mutably, so nothing can be allocated in the
+    /// stack past the scope, and blocks allocated through the scope can't outlive it.
+    pub fn scope<R>(&mut self, func: impl for<'s> FnOnce(&'s StackScope<'s>) -> R) -> R {
+        let scope = StackScope {
+            allocator: self,
+            marker: self.marker(),
+        };
+        func(&scope)
+    }
+}
+
+/// Allocates in a [`StackAllocator`] within [`StackAllocator::scope`], everything allocated
+/// through it is released when it is dropped.
+pub struct StackScope<'a> {
+    allocator: &'a StackAllocator,
+    marker: StackMarker,
+}
+
+impl StackScope<'_> {
+    /// Returns the amount of used bytes of the whole stack, including alignment padding.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.allocator.used()
+    }
+}
+
+impl fmt::Debug for StackScope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackScope")
+            .field("marker", &self.marker)
+            .field("allocator", self.allocator)
+            .finish()
+    }
+}
+
+impl Drop for StackScope<'_> {
+    fn drop(&mut self) {
+        let top = &self.allocator.top;
+        top.set(top.get().min(self.marker.0));
+    }
+}
+
+// SAFETY: Blocks allocated through the scope borrow it, so they can't be used after the top moves
+// back in `drop`. The allocator itself is borrowed mutably by `StackAllocator::scope`, so no other
+// block is allocated above the marker.
+unsafe impl RawAllocator for StackScope<'_> {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.allocator.allocate(layout)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.allocator.deallocate(ptr, layout)
+    }
+}
+
+// SAFETY: Blocks never overlap. The top moves back either in methods that require `&mut self`,
+// or past blocks that are released already: in `deallocate` and when a `StackScope` is dropped,
+// where blocks allocated through the scope can't be borrowed anymore.
+unsafe impl RawAllocator for StackAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let (ptr, end) = self.buffer.bump(self.top.get(), layout).ok_or(AllocError)?;
+        self.top.set(end);
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let offset = self.buffer.offset_of(ptr);
+        if offset + layout.size() == self.top.get() {
+            self.top.set(offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_allocator_releases_in_reverse_order() {
+        let mut allocator = StackAllocator::new(128);
+        let base = allocator.alloc(1u32).unwrap();
+        *base += 1;
+
+        let sum = allocator.scope(|stack| {
+            let values = stack.alloc_slice_copy(&[1u32, 2, 3]).unwrap();
+            assert_eq!(stack.used(), 16);
+            values.iter().sum::<u32>()
+        });
+        assert_eq!(sum, 6);
+        assert_eq!(allocator.used(), 4);
+
+        {
+            let first = allocator.alloc_boxed(String::from("first")).unwrap();
+            let second = allocator.alloc_boxed(7u64).unwrap();
+            assert_eq!((first.as_str(), *second), ("first", 7));
+            drop(second);
+            assert_eq!(allocator.used(), 8 + std::mem::size_of::<String>());
+        }
+
+        let marker = allocator.marker();
+        allocator.alloc([0u8; 32]).unwrap();
+        allocator.free_to_marker(marker);
+        assert_eq!(allocator.used(), marker.0);
+        allocator.reset();
+        assert_eq!(allocator.used(), 0);
+    }
+}
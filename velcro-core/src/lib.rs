@@ -15,8 +15,9 @@ pub use parking_lot;
 /*pub mod reflect;*/
 
 mod math;
-mod parallel;
+pub mod parallel;
 mod interface;
+pub mod allocator;
 
 
 pub use math::random::*;
@@ -61,7 +62,7 @@ mod tests {
     #[test]
     fn it_work_parallel() {
         let sp = spin_mutex::SpinMutex::new(false);
-        sp.lock();
-        sp.unlock();
+        *sp.lock() = true;
+        assert!(*sp.lock());
     }
 }
\ No newline at end of file
@@ -0,0 +1,183 @@
+use parking_lot::Mutex;
+use std::{
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+#[derive(Clone, Copy)]
+enum Deadline {
+    Tick(u64),
+    Time(Duration),
+}
+
+impl Deadline {
+    fn is_reached(self, tick: u64, elapsed: Duration) -> bool {
+        match self {
+            Deadline::Tick(deadline) => tick >= deadline,
+            Deadline::Time(deadline) => elapsed >= deadline,
+        }
+    }
+}
+
+struct TimerShared {
+    fired: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+struct Timer {
+    deadline: Deadline,
+    /// Timers of dropped [`Sleep`] futures are removed on the next tick.
+    shared: Weak<TimerShared>,
+}
+
+#[derive(Default)]
+struct ClockState {
+    tick: u64,
+    elapsed: Duration,
+    timers: Vec<Timer>,
+}
+
+/// Engine time used by timers of the executors. The clock is advanced explicitly by the frame
+/// loop (executors do it in their `update` methods), it is never advanced by wall clock time.
+/// Clones of the clock share the same time, so a clone can be moved into tasks.
+#[derive(Clone, Default)]
+pub struct Clock {
+    state: Arc<Mutex<ClockState>>,
+}
+
+impl Debug for Clock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("Clock")
+            .field("tick", &state.tick)
+            .field("elapsed", &state.elapsed)
+            .field("timers", &state.timers.len())
+            .finish()
+    }
+}
+
+impl Clock {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of ticks since the clock was created.
+    #[inline]
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        self.state.lock().tick
+    }
+
+    /// Returns the engine time since the clock was created.
+    #[inline]
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().elapsed
+    }
+
+    /// Advances the clock by one tick that lasted `delta` and wakes the tasks whose timers are
+    /// reached.
+    pub fn advance(&self, delta: Duration) {
+        let mut reached = Vec::new();
+        {
+            let mut state = self.state.lock();
+            state.tick += 1;
+            state.elapsed += delta;
+
+            let (tick, elapsed) = (state.tick, state.elapsed);
+            state.timers.retain(|timer| match timer.shared.upgrade() {
+                Some(shared) if timer.deadline.is_reached(tick, elapsed) => {
+                    reached.push(shared);
+                    false
+                }
+                Some(_) => true,
+                None => false,
+            });
+        }
+
+        // Wakers are called without the lock, a woken task may create new timers right away.
+        for shared in reached {
+            shared.fired.store(true, Ordering::SeqCst);
+            if let Some(waker) = shared.waker.lock().take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Returns a future that is ready after the given number of ticks.
+    pub fn sleep_ticks(&self, ticks: u64) -> Sleep {
+        let tick = self.tick() + ticks;
+        self.sleep_until(Deadline::Tick(tick))
+    }
+
+    /// Returns a future that is ready on the next tick.
+    pub fn next_tick(&self) -> Sleep {
+        self.sleep_ticks(1)
+    }
+
+    /// Returns a future that is ready on the first tick when at least `duration` of engine time
+    /// has passed.
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        let time = self.elapsed() + duration;
+        self.sleep_until(Deadline::Time(time))
+    }
+
+    fn sleep_until(&self, deadline: Deadline) -> Sleep {
+        Sleep {
+            clock: self.clone(),
+            deadline,
+            shared: None,
+        }
+    }
+}
+
+/// A future created by [`Clock::sleep`] and friends.
+#[must_use = "futures do nothing unless awaited"]
+pub struct Sleep {
+    clock: Clock,
+    deadline: Deadline,
+    shared: Option<Arc<TimerShared>>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(shared) = &self.shared {
+            // The waker is stored before the flag is checked again, so a timer that fires
+            // meanwhile always finds it.
+            *shared.waker.lock() = Some(cx.waker().clone());
+            return if shared.fired.load(Ordering::SeqCst) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            };
+        }
+
+        let mut state = self.clock.state.lock();
+        if self.deadline.is_reached(state.tick, state.elapsed) {
+            return Poll::Ready(());
+        }
+
+        let shared = Arc::new(TimerShared {
+            fired: AtomicBool::new(false),
+            waker: Mutex::new(Some(cx.waker().clone())),
+        });
+        state.timers.push(Timer {
+            deadline: self.deadline,
+            shared: Arc::downgrade(&shared),
+        });
+        drop(state);
+
+        self.shared = Some(shared);
+        Poll::Pending
+    }
+}
@@ -0,0 +1,282 @@
+use crate::parallel::executor::{task, Clock, JoinHandle};
+use parking_lot::Mutex;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+use velcro_rtti::memory::{Allocator, Handle};
+
+type LocalFuture = Pin<Box<dyn Future<Output = ()> + 'static>>;
+
+type ReadyQueue = Arc<Mutex<VecDeque<Handle<LocalTask>>>>;
+
+struct LocalTask {
+    future: LocalFuture,
+    waker: Arc<LocalWaker>,
+}
+
+/// Wakers must be `Send` even for tasks that are not, so the waker only queues the handle of the
+/// task and the executor looks the task up on its own thread.
+struct LocalWaker {
+    handle: Handle<LocalTask>,
+    scheduled: AtomicBool,
+    ready: ReadyQueue,
+}
+
+impl Wake for LocalWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.scheduled.swap(true, Ordering::AcqRel) {
+            self.ready.lock().push_back(self.handle);
+        }
+    }
+}
+
+/// Spawns tasks on a [`LocalExecutor`], the spawner can be cloned and moved into tasks to spawn
+/// new tasks from them.
+#[derive(Clone, Default)]
+pub struct LocalSpawner {
+    spawned: Rc<RefCell<Vec<LocalFuture>>>,
+}
+
+impl Debug for LocalSpawner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalSpawner").finish_non_exhaustive()
+    }
+}
+
+impl LocalSpawner {
+    /// Queues the future, it is polled for the first time by the next
+    /// [`LocalExecutor::run_until_stalled`].
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        let (task, handle) = task(future);
+        self.spawned.borrow_mut().push(Box::pin(task));
+        handle
+    }
+}
+
+/// Executor that polls tasks on the thread that runs it, see the [module docs](super). Tasks
+/// don't have to be `Send`, so they can hold `Rc`s and other thread-bound data of the engine.
+#[derive(Default)]
+pub struct LocalExecutor {
+    tasks: Allocator<LocalTask>,
+    ready: ReadyQueue,
+    spawner: LocalSpawner,
+    clock: Clock,
+}
+
+impl Debug for LocalExecutor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalExecutor")
+            .field("task_count", &self.task_count())
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for LocalExecutor {
+    fn drop(&mut self) {
+        // Unfinished tasks are cancelled, they are not leaks of the task allocator.
+        self.tasks.clear();
+    }
+}
+
+impl LocalExecutor {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an executor that uses the given clock, so several executors can share the same
+    /// engine time.
+    #[must_use]
+    pub fn with_clock(clock: Clock) -> Self {
+        let mut executor = Self::default();
+        executor.clock = clock;
+        executor
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn spawner(&self) -> LocalSpawner {
+        self.spawner.clone()
+    }
+
+    /// Queues the future, it is polled for the first time by the next [`Self::run_until_stalled`].
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        self.spawner.spawn(future)
+    }
+
+    /// Returns the number of tasks that are not finished yet.
+    #[must_use]
+    pub fn task_count(&self) -> usize {
+        self.tasks.alive_count() as usize + self.spawner.spawned.borrow().len()
+    }
+
+    /// Advances the clock by one tick and polls the tasks, meant to be called once per frame.
+    pub fn update(&mut self, delta: Duration) {
+        self.clock.advance(delta);
+        self.run_until_stalled();
+    }
+
+    /// Polls tasks until none of them can make progress without a tick of the clock or some
+    /// other event. A task that panics is dropped and its handle resolves to
+    /// [`JoinError::Panicked`](super::JoinError::Panicked).
+    pub fn run_until_stalled(&mut self) {
+        loop {
+            let spawned = mem::take(&mut *self.spawner.spawned.borrow_mut());
+            for future in spawned {
+                let ready = self.ready.clone();
+                let handle = self.tasks.spawn_with(|handle| LocalTask {
+                    future,
+                    waker: Arc::new(LocalWaker {
+                        handle,
+                        scheduled: AtomicBool::new(true),
+                        ready,
+                    }),
+                });
+                self.ready.lock().push_back(handle);
+            }
+
+            let Some(handle) = self.ready.lock().pop_front() else {
+                if self.spawner.spawned.borrow().is_empty() {
+                    break;
+                }
+                continue;
+            };
+
+            // The task could be finished after it was woken.
+            let Some(task) = self.tasks.try_borrow_mut(handle) else {
+                continue;
+            };
+            task.waker.scheduled.store(false, Ordering::Release);
+            let waker = Waker::from(task.waker.clone());
+            let poll = panic::catch_unwind(AssertUnwindSafe(|| {
+                task.future.as_mut().poll(&mut Context::from_waker(&waker))
+            }));
+            if !matches!(poll, Ok(Poll::Pending)) {
+                self.tasks.free(handle);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel::executor::JoinError;
+    use std::cell::Cell;
+
+    #[test]
+    fn local_executor_runs_tasks_by_engine_ticks() {
+        let mut executor = LocalExecutor::new();
+        let clock = executor.clock().clone();
+        let spawner = executor.spawner();
+
+        // Tasks can share thread-bound state and spawn other tasks.
+        let frames = Rc::new(Cell::new(0));
+        let mut loading = executor.spawn({
+            let (clock, frames) = (clock.clone(), frames.clone());
+            async move {
+                let parts = (1..=3)
+                    .map(|part| {
+                        let clock = clock.clone();
+                        spawner.spawn(async move {
+                            clock.sleep_ticks(part).await;
+                            part * 10
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut sum = 0;
+                for part in parts {
+                    sum += part.await.unwrap();
+                }
+                frames.set(clock.tick());
+                sum
+            }
+        });
+
+        let mut forever = executor.spawn({
+            let clock = clock.clone();
+            async move {
+                loop {
+                    clock.next_tick().await;
+                }
+            }
+        });
+
+        executor.run_until_stalled();
+        assert_eq!(executor.task_count(), 5);
+        executor.update(Duration::from_millis(16));
+        executor.update(Duration::from_millis(16));
+        assert!(!loading.is_finished());
+
+        executor.update(Duration::from_millis(16));
+        assert_eq!(loading.take_result(), Some(Ok(60)));
+        assert_eq!(frames.get(), 3);
+        assert_eq!(executor.task_count(), 1);
+
+        forever.cancel();
+        executor.run_until_stalled();
+        assert_eq!(forever.take_result(), Some(Err(JoinError::Cancelled)));
+        assert_eq!(executor.task_count(), 0);
+
+        let timeout = executor.spawn(clock.sleep(Duration::from_millis(100)));
+        for _ in 0..6 {
+            executor.update(Duration::from_millis(16));
+        }
+        assert!(!timeout.is_finished());
+        executor.update(Duration::from_millis(16));
+        assert!(timeout.is_finished());
+
+        // A task that panics is finished with an error, other tasks keep running.
+        let failing = executor.spawn(async { "frames".parse::<u32>().unwrap() });
+        let mut waiting = executor.spawn(failing);
+        let mut normal = executor.spawn({
+            let clock = clock.clone();
+            async move {
+                clock.next_tick().await;
+                7
+            }
+        });
+        executor.run_until_stalled();
+        assert_eq!(waiting.take_result(), Some(Ok(Err(JoinError::Panicked))));
+        assert!(!normal.is_finished());
+        executor.update(Duration::from_millis(16));
+        assert_eq!(normal.take_result(), Some(Ok(7)));
+        assert_eq!(executor.task_count(), 0);
+
+        // Unfinished tasks are cancelled when the executor is dropped.
+        let mut pending = executor.spawn(clock.next_tick());
+        drop(executor);
+        assert_eq!(pending.take_result(), Some(Err(JoinError::Cancelled)));
+    }
+}
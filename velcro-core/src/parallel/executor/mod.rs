@@ -0,0 +1,228 @@
+#![warn(clippy::pedantic)]
+
+//! Async executors that run engine tasks inside of the frame loop.
+//!
+//! Executors don't own threads and never block: the frame loop calls `update` once per frame,
+//! which advances the [`Clock`] and polls every task that can make progress until all of them
+//! wait for something. [`LocalExecutor`] polls tasks on the calling thread and accepts futures
+//! that are not `Send`, [`Executor`] polls tasks in parallel on a
+//! [`JobSystem`](crate::parallel::job_system::JobSystem). A task that panics is finished with
+//! [`JoinError::Panicked`], other tasks keep running.
+//!
+//! Timers ([`Clock::sleep`], [`Clock::sleep_ticks`]) measure engine time instead of wall clock
+//! time, so a flow that waits for three frames always takes three calls of `update`, which keeps
+//! tests deterministic.
+//!
+//! ```
+//! use std::time::Duration;
+//! use velcro_core::parallel::executor::LocalExecutor;
+//!
+//! let mut executor = LocalExecutor::new();
+//! let clock = executor.clock().clone();
+//! let mut handle = executor.spawn(async move {
+//!     clock.sleep(Duration::from_millis(50)).await;
+//!     "loaded"
+//! });
+//!
+//! for _ in 0..4 {
+//!     executor.update(Duration::from_millis(16));
+//! }
+//! assert!(!handle.is_finished());
+//!
+//! executor.update(Duration::from_millis(16));
+//! assert_eq!(handle.take_result(), Some(Ok("loaded")));
+//! ```
+
+mod clock;
+mod local;
+mod threaded;
+
+pub use clock::*;
+pub use local::*;
+pub use threaded::*;
+
+use parking_lot::Mutex;
+use std::{
+    fmt::{self, Display, Formatter},
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// The reason why a task finished without a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    /// The task was cancelled with [`JoinHandle::cancel`] or its executor was dropped before the
+    /// task was finished.
+    Cancelled,
+    /// The task panicked while it was polled, the panic message is printed by the panic hook.
+    Panicked,
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Cancelled => write!(f, "task was cancelled"),
+            JoinError::Panicked => write!(f, "task panicked"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+struct JoinInner<T> {
+    result: Option<Result<T, JoinError>>,
+    finished: bool,
+    /// Waker of the task that awaits the handle.
+    join_waker: Option<Waker>,
+}
+
+struct JoinState<T> {
+    inner: Mutex<JoinInner<T>>,
+    cancel_requested: AtomicBool,
+    /// Waker of the task itself, used to poll the task once more when it is cancelled.
+    task_waker: Mutex<Option<Waker>>,
+}
+
+impl<T> JoinState<T> {
+    fn complete(&self, result: Result<T, JoinError>) {
+        let join_waker = {
+            let mut inner = self.inner.lock();
+            if inner.finished {
+                return;
+            }
+            inner.finished = true;
+            inner.result = Some(result);
+            inner.join_waker.take()
+        };
+        self.task_waker.lock().take();
+
+        if let Some(waker) = join_waker {
+            waker.wake();
+        }
+    }
+}
+
+/// A handle to the result of a spawned task. The handle can be awaited by other tasks or checked
+/// every frame with [`Self::take_result`]. Dropping the handle detaches the task, it keeps running.
+pub struct JoinHandle<T> {
+    state: Arc<JoinState<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Requests cancellation of the task, the task is dropped the next time its executor polls
+    /// tasks and the handle resolves to [`JoinError::Cancelled`]. Does nothing if the task is already
+    /// finished.
+    pub fn cancel(&self) {
+        self.state.cancel_requested.store(true, Ordering::SeqCst);
+        let task_waker = self.state.task_waker.lock().clone();
+        if let Some(waker) = task_waker {
+            waker.wake();
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.state.inner.lock().finished
+    }
+
+    /// Takes the result of the task if it is finished. The result can be taken only once.
+    pub fn take_result(&mut self) -> Option<Result<T, JoinError>> {
+        self.state.inner.lock().result.take()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.state.inner.lock();
+        if let Some(result) = inner.result.take() {
+            return Poll::Ready(result);
+        }
+
+        assert!(
+            !inner.finished,
+            "JoinHandle polled after its result was taken!"
+        );
+        inner.join_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Wraps a spawned future and passes its result to the [`JoinHandle`].
+struct TaskFuture<F: Future> {
+    future: Pin<Box<F>>,
+    state: Arc<JoinState<F::Output>>,
+}
+
+impl<F: Future> Future for TaskFuture<F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        // The waker is stored before the flag is checked, so a cancellation that comes during the
+        // poll always wakes the task.
+        *this.state.task_waker.lock() = Some(cx.waker().clone());
+        if this.state.cancel_requested.load(Ordering::SeqCst) {
+            this.state.complete(Err(JoinError::Cancelled));
+            return Poll::Ready(());
+        }
+
+        // Executors catch panics of tasks and drop them, the guard finishes the task with an
+        // error while the poll unwinds, so tasks that await it don't hang.
+        let guard = PanicGuard(&this.state);
+        let poll = this.future.as_mut().poll(cx);
+        mem::forget(guard);
+        match poll {
+            Poll::Ready(result) => {
+                this.state.complete(Ok(result));
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F: Future> Drop for TaskFuture<F> {
+    fn drop(&mut self) {
+        // Tasks that are dropped unfinished (together with their executor) count as cancelled.
+        self.state.complete(Err(JoinError::Cancelled));
+    }
+}
+
+/// Finishes the task with [`JoinError::Panicked`] when dropped, forgotten if the poll returns.
+struct PanicGuard<'a, T>(&'a JoinState<T>);
+
+impl<T> Drop for PanicGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.complete(Err(JoinError::Panicked));
+    }
+}
+
+fn task<F: Future>(future: F) -> (TaskFuture<F>, JoinHandle<F::Output>) {
+    let state = Arc::new(JoinState {
+        inner: Mutex::new(JoinInner {
+            result: None,
+            finished: false,
+            join_waker: None,
+        }),
+        cancel_requested: AtomicBool::new(false),
+        task_waker: Mutex::new(None),
+    });
+
+    (
+        TaskFuture {
+            future: Box::pin(future),
+            state: state.clone(),
+        },
+        JoinHandle { state },
+    )
+}
@@ -0,0 +1,323 @@
+use crate::parallel::{
+    executor::{task, Clock, JoinHandle},
+    job_system::JobSystem,
+};
+use parking_lot::Mutex;
+use std::{
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+use velcro_rtti::memory::{Allocator, Handle};
+
+type SendFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+struct Task {
+    handle: Handle<Arc<Task>>,
+    future: Mutex<Option<SendFuture>>,
+    scheduled: AtomicBool,
+    shared: Weak<Shared>,
+}
+
+impl Task {
+    fn poll(self: &Arc<Self>) {
+        self.scheduled.store(false, Ordering::Release);
+
+        let mut future = self.future.lock();
+        let Some(pinned) = future.as_mut() else {
+            return;
+        };
+
+        let waker = Waker::from(self.clone());
+        // A panicking task is dropped like a finished one, its handle resolves to an error.
+        let poll = panic::catch_unwind(AssertUnwindSafe(|| {
+            pinned.as_mut().poll(&mut Context::from_waker(&waker))
+        }));
+        if !matches!(poll, Ok(Poll::Pending)) {
+            *future = None;
+            if let Some(shared) = self.shared.upgrade() {
+                shared.tasks.lock().free(self.handle);
+            }
+        }
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if let Some(shared) = self.shared.upgrade() {
+            if !self.scheduled.swap(true, Ordering::AcqRel) {
+                shared.ready.lock().push(self.clone());
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    tasks: Mutex<Allocator<Arc<Task>>>,
+    ready: Mutex<Vec<Arc<Task>>>,
+}
+
+impl Shared {
+    fn spawn<F>(self: &Arc<Self>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, handle) = task(future);
+        let future: SendFuture = Box::pin(task);
+
+        let task = {
+            let mut tasks = self.tasks.lock();
+            let task_handle = tasks.spawn_with(|handle| {
+                Arc::new(Task {
+                    handle,
+                    future: Mutex::new(Some(future)),
+                    scheduled: AtomicBool::new(true),
+                    shared: Arc::downgrade(self),
+                })
+            });
+            tasks.borrow(task_handle).clone()
+        };
+        self.ready.lock().push(task);
+
+        handle
+    }
+}
+
+/// Spawns tasks on an [`Executor`], the spawner can be cloned and moved into tasks to spawn new
+/// tasks from them. Tasks spawned after the executor is dropped are cancelled right away.
+#[derive(Clone)]
+pub struct Spawner {
+    shared: Weak<Shared>,
+}
+
+impl Debug for Spawner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spawner").finish_non_exhaustive()
+    }
+}
+
+impl Spawner {
+    /// Queues the future, it is polled for the first time by the next
+    /// [`Executor::run_until_stalled`].
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match self.shared.upgrade() {
+            Some(shared) => shared.spawn(future),
+            // Dropping the task cancels it.
+            None => task(future).1,
+        }
+    }
+}
+
+/// Executor that polls tasks in parallel on a [`JobSystem`], see the [module docs](super). A task
+/// is never polled by two threads at the same time, but it can move between threads, so tasks
+/// must be `Send`.
+#[derive(Default)]
+pub struct Executor {
+    shared: Arc<Shared>,
+    clock: Clock,
+}
+
+impl Debug for Executor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Executor")
+            .field("task_count", &self.task_count())
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        // Tasks and their wakers reference each other, dropping the futures breaks the cycles and
+        // cancels unfinished tasks. Cancelled tasks are cleared, they are not leaks.
+        let mut tasks = mem::take(&mut *self.shared.tasks.lock());
+        self.shared.ready.lock().clear();
+        for task in tasks.iter() {
+            let future = task.future.lock().take();
+            drop(future);
+        }
+        tasks.clear();
+    }
+}
+
+impl Executor {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an executor that uses the given clock, so several executors can share the same
+    /// engine time.
+    #[must_use]
+    pub fn with_clock(clock: Clock) -> Self {
+        Self {
+            shared: Arc::default(),
+            clock,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            shared: Arc::downgrade(&self.shared),
+        }
+    }
+
+    /// Queues the future, it is polled for the first time by the next [`Self::run_until_stalled`].
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn(future)
+    }
+
+    /// Returns the number of tasks that are not finished yet.
+    #[must_use]
+    pub fn task_count(&self) -> usize {
+        self.shared.tasks.lock().alive_count() as usize
+    }
+
+    /// Advances the clock by one tick and polls the tasks, meant to be called once per frame.
+    pub fn update(&self, delta: Duration, jobs: &JobSystem) {
+        self.clock.advance(delta);
+        self.run_until_stalled(jobs);
+    }
+
+    /// Polls tasks in parallel until none of them can make progress without a tick of the clock
+    /// or some other event. A task that panics is dropped and its handle resolves to
+    /// [`JoinError::Panicked`](super::JoinError::Panicked).
+    pub fn run_until_stalled(&self, jobs: &JobSystem) {
+        loop {
+            let mut ready = mem::take(&mut *self.shared.ready.lock());
+            if ready.is_empty() {
+                break;
+            }
+            // Tasks woken during the batch are queued for the next one, so every task is polled
+            // by one thread at a time.
+            jobs.parallel_for_slice(&mut ready, |task| task.poll());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel::executor::JoinError;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn executor_polls_tasks_on_job_system() {
+        let jobs = JobSystem::new(3);
+        let executor = Executor::new();
+        let clock = executor.clock().clone();
+        let spawner = executor.spawner();
+
+        let polls = Arc::new(AtomicUsize::new(0));
+        let mut handles = (0..100u64)
+            .map(|i| {
+                let (clock, polls) = (clock.clone(), polls.clone());
+                executor.spawn(async move {
+                    polls.fetch_add(1, Ordering::Relaxed);
+                    clock.sleep_ticks(i % 3).await;
+                    i * 2
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut fan_out = executor.spawn({
+            let clock = clock.clone();
+            async move {
+                let halves = [20, 22].map(|value| {
+                    let clock = clock.clone();
+                    spawner.spawn(async move {
+                        clock.next_tick().await;
+                        value
+                    })
+                });
+                let mut sum = 0;
+                for half in halves {
+                    sum += half.await.unwrap();
+                }
+                sum
+            }
+        });
+
+        let mut forever = executor.spawn({
+            let clock = clock.clone();
+            async move {
+                loop {
+                    clock.next_tick().await;
+                }
+            }
+        });
+
+        executor.run_until_stalled(&jobs);
+        assert_eq!(polls.load(Ordering::Relaxed), 100);
+        assert_eq!(executor.task_count(), 2 * 100 / 3 + 1 + 2 + 1);
+
+        executor.update(Duration::from_millis(16), &jobs);
+        assert_eq!(fan_out.take_result(), Some(Ok(42)));
+        executor.update(Duration::from_millis(16), &jobs);
+        let sum = handles
+            .iter_mut()
+            .map(|handle| handle.take_result().unwrap().unwrap())
+            .sum::<u64>();
+        assert_eq!(sum, 99 * 100);
+
+        forever.cancel();
+        executor.run_until_stalled(&jobs);
+        assert_eq!(forever.take_result(), Some(Err(JoinError::Cancelled)));
+        assert_eq!(executor.task_count(), 0);
+
+        // A task that panics is finished with an error, other tasks keep running.
+        let failing = executor.spawn(async { "frames".parse::<u32>().unwrap() });
+        let mut waiting = executor.spawn(failing);
+        let mut normal = executor.spawn({
+            let clock = clock.clone();
+            async move {
+                clock.next_tick().await;
+                7
+            }
+        });
+        executor.run_until_stalled(&jobs);
+        assert_eq!(waiting.take_result(), Some(Ok(Err(JoinError::Panicked))));
+        assert!(!normal.is_finished());
+        executor.update(Duration::from_millis(16), &jobs);
+        assert_eq!(normal.take_result(), Some(Ok(7)));
+        assert_eq!(executor.task_count(), 0);
+
+        // Unfinished tasks are cancelled when the executor is dropped.
+        let spawner = executor.spawner();
+        let mut pending = executor.spawn(clock.next_tick());
+        drop(executor);
+        assert_eq!(pending.take_result(), Some(Err(JoinError::Cancelled)));
+        let mut late = spawner.spawn(async {});
+        assert_eq!(late.take_result(), Some(Err(JoinError::Cancelled)));
+    }
+}
@@ -0,0 +1,384 @@
+#![warn(clippy::pedantic)]
+
+//! Declarative graph of jobs with dependencies.
+//!
+//! Nodes of a graph are named, every node lists names of the nodes it depends on and starts only
+//! after all of them are finished. Nodes without work ([barriers](JobGraphBuilder::add_barrier))
+//! join several branches into one, [continuations](JobGraphBuilder::add_continuation) run on the
+//! same thread right after their node. The graph is validated once by [`JobGraphBuilder::build`]
+//! and the compiled [`JobGraph`] can be executed any number of times, e.g. every frame.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use velcro_core::parallel::{job_graph::JobGraphBuilder, job_system::JobSystem};
+//!
+//! let log = Arc::new(Mutex::new(Vec::new()));
+//! let mut builder = JobGraphBuilder::new();
+//! for (name, dependencies) in [
+//!     ("input", &[][..]),
+//!     ("simulation", &["input"][..]),
+//!     ("culling", &["simulation"][..]),
+//!     ("replication", &["simulation"][..]),
+//! ] {
+//!     let log = log.clone();
+//!     builder.add(name, dependencies, move || log.lock().unwrap().push(name));
+//! }
+//! builder.add_barrier("frame_end", &["culling", "replication"]);
+//!
+//! let mut graph = builder.build().unwrap();
+//! let jobs = JobSystem::new(2);
+//! graph.run(&jobs);
+//!
+//! let log = log.lock().unwrap();
+//! assert_eq!(log[..2], ["input", "simulation"]);
+//! assert_eq!(log.len(), 4);
+//! ```
+
+use crate::parallel::job_system::{JobScope, JobSystem};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+type Task = Box<dyn FnMut() + Send + 'static>;
+
+/// An error that prevents a job graph from being built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobGraphError {
+    /// There are two nodes with the same name.
+    DuplicateNode(String),
+    /// A node depends on a node that does not exist.
+    UnknownDependency { node: String, dependency: String },
+    /// A continuation is added to a node that does not exist.
+    UnknownNode(String),
+    /// Nodes depend on each other, the names form a cycle where every node depends on the next
+    /// one and the last node depends on the first one.
+    Cycle(Vec<String>),
+}
+
+impl Display for JobGraphError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateNode(name) => write!(f, "job graph node {name} is defined twice"),
+            Self::UnknownDependency { node, dependency } => {
+                write!(
+                    f,
+                    "job graph node {node} depends on unknown node {dependency}"
+                )
+            }
+            Self::UnknownNode(name) => write!(f, "job graph node {name} does not exist"),
+            Self::Cycle(names) => {
+                write!(f, "job graph has a cycle: ")?;
+                for name in names {
+                    write!(f, "{name} -> ")?;
+                }
+                write!(f, "{}", names.first().map_or("", String::as_str))
+            }
+        }
+    }
+}
+
+impl std::error::Error for JobGraphError {}
+
+struct NodeDefinition {
+    name: String,
+    dependencies: Vec<String>,
+    tasks: Vec<Task>,
+}
+
+/// Collects nodes of a [`JobGraph`], see the [module docs](self).
+#[derive(Default)]
+pub struct JobGraphBuilder {
+    nodes: Vec<NodeDefinition>,
+    continuations: Vec<(String, Task)>,
+}
+
+impl JobGraphBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node that executes the function after all its dependencies are finished.
+    pub fn add<F>(&mut self, name: impl Into<String>, dependencies: &[&str], func: F) -> &mut Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.add_node(name.into(), dependencies, vec![Box::new(func)])
+    }
+
+    /// Adds a node without work, it is finished as soon as all its dependencies are finished.
+    pub fn add_barrier(&mut self, name: impl Into<String>, dependencies: &[&str]) -> &mut Self {
+        self.add_node(name.into(), dependencies, Vec::new())
+    }
+
+    /// Adds a function that is executed on the same thread right after the node and before any of
+    /// its dependents. Continuations of a node are executed in the order they were added.
+    pub fn add_continuation<F>(&mut self, name: impl Into<String>, func: F) -> &mut Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.continuations.push((name.into(), Box::new(func)));
+        self
+    }
+
+    fn add_node(&mut self, name: String, dependencies: &[&str], tasks: Vec<Task>) -> &mut Self {
+        self.nodes.push(NodeDefinition {
+            name,
+            dependencies: dependencies.iter().map(ToString::to_string).collect(),
+            tasks,
+        });
+        self
+    }
+
+    /// Resolves dependencies and checks that the graph has no cycles.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if names of the nodes are not unique, a dependency or a node of a
+    /// continuation does not exist or the graph has a cycle.
+    pub fn build(self) -> Result<JobGraph, JobGraphError> {
+        let mut indices = HashMap::with_capacity(self.nodes.len());
+        for (index, node) in self.nodes.iter().enumerate() {
+            if indices.insert(node.name.clone(), index).is_some() {
+                return Err(JobGraphError::DuplicateNode(node.name.clone()));
+            }
+        }
+
+        let mut dependencies = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let mut resolved = node
+                .dependencies
+                .iter()
+                .map(|dependency| {
+                    indices.get(dependency).copied().ok_or_else(|| {
+                        JobGraphError::UnknownDependency {
+                            node: node.name.clone(),
+                            dependency: dependency.clone(),
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            resolved.sort_unstable();
+            resolved.dedup();
+            dependencies.push(resolved);
+        }
+
+        let mut nodes = self
+            .nodes
+            .into_iter()
+            .zip(&dependencies)
+            .map(|(definition, dependencies)| Node {
+                name: definition.name,
+                tasks: Mutex::new(definition.tasks),
+                dependency_count: dependencies.len(),
+                dependents: Vec::new(),
+                remaining: AtomicUsize::new(0),
+            })
+            .collect::<Vec<_>>();
+
+        for (name, continuation) in self.continuations {
+            match indices.get(&name) {
+                Some(&index) => nodes[index].tasks.get_mut().push(continuation),
+                None => return Err(JobGraphError::UnknownNode(name)),
+            }
+        }
+
+        for (index, dependencies) in dependencies.iter().enumerate() {
+            for &dependency in dependencies {
+                nodes[dependency].dependents.push(index);
+            }
+        }
+
+        check_cycles(&nodes, &dependencies)?;
+
+        let roots = (0..nodes.len())
+            .filter(|&index| nodes[index].dependency_count == 0)
+            .collect();
+
+        Ok(JobGraph { nodes, roots })
+    }
+}
+
+/// Sorts the nodes topologically and returns one of the cycles if the sort is not possible.
+fn check_cycles(nodes: &[Node], dependencies: &[Vec<usize>]) -> Result<(), JobGraphError> {
+    let mut remaining = nodes
+        .iter()
+        .map(|node| node.dependency_count)
+        .collect::<Vec<_>>();
+    let mut ready = (0..nodes.len())
+        .filter(|&index| remaining[index] == 0)
+        .collect::<Vec<_>>();
+    let mut sorted = 0;
+    while let Some(index) = ready.pop() {
+        sorted += 1;
+        for &dependent in &nodes[index].dependents {
+            remaining[dependent] -= 1;
+            if remaining[dependent] == 0 {
+                ready.push(dependent);
+            }
+        }
+    }
+
+    if sorted == nodes.len() {
+        return Ok(());
+    }
+
+    // Every node that is not sorted has a dependency that is not sorted too, so following such
+    // dependencies must eventually visit some node twice.
+    let mut path = Vec::new();
+    let mut current = (0..nodes.len())
+        .find(|&index| remaining[index] != 0)
+        .expect("unsorted node must exist");
+    while !path.contains(&current) {
+        path.push(current);
+        current = dependencies[current]
+            .iter()
+            .copied()
+            .find(|&dependency| remaining[dependency] != 0)
+            .expect("unsorted node must have an unsorted dependency");
+    }
+
+    let start = path.iter().position(|&index| index == current).unwrap();
+    Err(JobGraphError::Cycle(
+        path[start..]
+            .iter()
+            .map(|&index| nodes[index].name.clone())
+            .collect(),
+    ))
+}
+
+struct Node {
+    name: String,
+    /// The work of the node followed by its continuations.
+    tasks: Mutex<Vec<Task>>,
+    dependency_count: usize,
+    dependents: Vec<usize>,
+    /// Number of dependencies that are not finished yet in the current run.
+    remaining: AtomicUsize,
+}
+
+/// A validated graph of jobs created with [`JobGraphBuilder`], see the [module docs](self).
+pub struct JobGraph {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+}
+
+impl JobGraph {
+    #[inline]
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.nodes.iter().any(|node| node.name == name)
+    }
+
+    /// Executes all nodes of the graph on the job system and waits until they are finished.
+    ///
+    /// # Panics
+    ///
+    /// Propagates the panic of a node, dependents of the node are not executed in this case.
+    pub fn run(&mut self, jobs: &JobSystem) {
+        for node in &mut self.nodes {
+            *node.remaining.get_mut() = node.dependency_count;
+        }
+
+        let graph = &*self;
+        jobs.scope(|scope| {
+            for &root in &graph.roots {
+                scope.spawn(move || graph.run_node(scope, root));
+            }
+        });
+    }
+
+    fn run_node<'scope>(&'scope self, scope: &'scope JobScope<'scope, '_>, index: usize) {
+        let node = &self.nodes[index];
+        for task in node.tasks.lock().iter_mut() {
+            task();
+        }
+
+        for &dependent in &node.dependents {
+            if self.nodes[dependent]
+                .remaining
+                .fetch_sub(1, Ordering::AcqRel)
+                == 1
+            {
+                scope.spawn(move || self.run_node(scope, dependent));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn job_graph_respects_dependencies_and_detects_cycles() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let record = |name: &'static str| {
+            let log = log.clone();
+            move || log.lock().push(name)
+        };
+
+        let mut builder = JobGraphBuilder::new();
+        builder
+            .add(
+                "replication",
+                &["simulation", "culling"],
+                record("replication"),
+            )
+            .add("input", &[], record("input"))
+            .add("simulation", &["input"], record("simulation"))
+            .add("culling", &["input", "input"], record("culling"))
+            .add("audio", &["input"], record("audio"))
+            .add_barrier("frame_end", &["replication", "audio"])
+            .add_continuation("simulation", record("simulation_done"))
+            .add("present", &["frame_end"], record("present"));
+        let mut graph = builder.build().unwrap();
+        assert_eq!(graph.node_count(), 7);
+        assert!(graph.contains("frame_end"));
+
+        let jobs = JobSystem::new(3);
+        for frame in 1..=3 {
+            graph.run(&jobs);
+
+            let log = log.lock();
+            assert_eq!(log.len(), frame * 7);
+            let frame_log = &log[(frame - 1) * 7..];
+            let position = |name| frame_log.iter().position(|n| *n == name).unwrap();
+            assert_eq!(position("input"), 0);
+            assert_eq!(position("simulation_done"), position("simulation") + 1);
+            assert!(position("simulation_done") < position("replication"));
+            assert!(position("culling") < position("replication"));
+            assert_eq!(position("present"), 6);
+        }
+
+        let mut builder = JobGraphBuilder::new();
+        builder
+            .add_barrier("a", &["c"])
+            .add_barrier("b", &["a"])
+            .add_barrier("c", &["b"])
+            .add_barrier("d", &[]);
+        let Err(JobGraphError::Cycle(cycle)) = builder.build() else {
+            panic!("cycle must be detected");
+        };
+        assert_eq!(cycle.len(), 3);
+
+        let mut builder = JobGraphBuilder::new();
+        builder.add_barrier("a", &["missing"]);
+        assert_eq!(
+            builder.build().err(),
+            Some(JobGraphError::UnknownDependency {
+                node: "a".to_string(),
+                dependency: "missing".to_string(),
+            })
+        );
+    }
+}
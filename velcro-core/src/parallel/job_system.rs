@@ -0,0 +1,436 @@
+#![warn(clippy::pedantic)]
+
+//! Work-stealing job system.
+//!
+//! [`JobSystem`] owns a set of worker threads, every worker has its own deque of jobs. Jobs
+//! spawned by a worker go to its own deque (and are taken from its back, so recently spawned jobs
+//! are executed first while their data is still in cache), jobs spawned by other threads go to a
+//! shared queue. An idle worker takes jobs from the shared queue or steals them from the front of
+//! deques of other workers.
+//!
+//! A thread that waits for a job ([`JobHandle::wait`], [`JobSystem::scope`] and the `parallel_for`
+//! family) executes other jobs in the meantime, so jobs can wait for their own sub-jobs without
+//! blocking workers.
+//!
+//! ```
+//! use velcro_core::parallel::job_system::JobSystem;
+//!
+//! let jobs = JobSystem::new(2);
+//! let sum = jobs.spawn(|| (1..=10).sum::<u32>());
+//!
+//! let mut values = vec![1, 2, 3, 4];
+//! jobs.parallel_for_slice(&mut values, |value| *value *= 2);
+//!
+//! assert_eq!(sum.wait(), 55);
+//! assert_eq!(values, [2, 4, 6, 8]);
+//! ```
+
+use crate::parallel::exponential::ExponentialBackoff;
+use parking_lot::{Condvar, Mutex};
+use std::{
+    any::Any,
+    cell::Cell,
+    collections::VecDeque,
+    marker::PhantomData,
+    mem,
+    num::NonZeroUsize,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+use velcro_rtti::memory::{Allocator, Handle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+type Panic = Box<dyn Any + Send + 'static>;
+
+/// Number of chunks per worker that `parallel_for` splits its work into, a few chunks per worker
+/// balance uneven work without too much scheduling overhead.
+const CHUNKS_PER_WORKER: usize = 4;
+
+thread_local! {
+    /// Identifier of the job system and index of the worker that runs on the current thread.
+    static CURRENT_WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+struct Shared {
+    deques: Box<[Mutex<VecDeque<Job>>]>,
+    /// Jobs spawned outside of workers.
+    injector: Mutex<VecDeque<Job>>,
+    /// Number of jobs in all queues.
+    queued: AtomicUsize,
+    sleep: Mutex<()>,
+    wake: Condvar,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn id(self: &Arc<Self>) -> usize {
+        Arc::as_ptr(self) as usize
+    }
+
+    fn current_worker(self: &Arc<Self>) -> Option<usize> {
+        CURRENT_WORKER
+            .get()
+            .and_then(|(id, index)| (id == self.id()).then_some(index))
+    }
+
+    fn push(self: &Arc<Self>, job: Job) {
+        match self.current_worker() {
+            Some(index) => self.deques[index].lock().push_back(job),
+            None => self.injector.lock().push_back(job),
+        }
+        self.queued.fetch_add(1, Ordering::SeqCst);
+
+        // Taking the lock makes sure that a worker that is about to sleep either sees the new job
+        // or already waits for the notification.
+        drop(self.sleep.lock());
+        self.wake.notify_one();
+    }
+
+    fn find_job(&self, worker: Option<usize>) -> Option<Job> {
+        if self.queued.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+
+        let job = worker
+            .and_then(|index| self.deques[index].lock().pop_back())
+            .or_else(|| self.injector.lock().pop_front())
+            .or_else(|| {
+                let start = worker.map_or(0, |index| index + 1);
+                (0..self.deques.len())
+                    .map(|offset| (start + offset) % self.deques.len())
+                    .filter(|&victim| Some(victim) != worker)
+                    .find_map(|victim| self.deques[victim].lock().pop_front())
+            });
+
+        if job.is_some() {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+        }
+        job
+    }
+
+    /// Executes jobs until the condition is met.
+    fn wait_until(self: &Arc<Self>, condition: impl Fn() -> bool) {
+        let worker = self.current_worker();
+        let mut backoff = ExponentialBackoff::new();
+        while !condition() {
+            if let Some(job) = self.find_job(worker) {
+                job();
+                backoff.reset();
+            } else {
+                backoff.wait();
+            }
+        }
+    }
+
+    fn worker_loop(self: &Arc<Self>, index: usize) {
+        CURRENT_WORKER.set(Some((self.id(), index)));
+        loop {
+            if let Some(job) = self.find_job(Some(index)) {
+                job();
+                continue;
+            }
+
+            let mut guard = self.sleep.lock();
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            if self.queued.load(Ordering::SeqCst) == 0 {
+                self.wake.wait(&mut guard);
+            }
+        }
+        CURRENT_WORKER.set(None);
+    }
+}
+
+/// A pool of worker threads that execute jobs, see the [module docs](self).
+///
+/// Dropping the job system waits for all queued jobs, so it must not be dropped by its own job.
+pub struct JobSystem {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Default for JobSystem {
+    /// Creates a job system with a worker for every available core except the one of the main
+    /// thread.
+    fn default() -> Self {
+        let cores = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::new(cores.saturating_sub(1).max(1))
+    }
+}
+
+impl Drop for JobSystem {
+    /// Executes all queued jobs and stops the workers.
+    fn drop(&mut self) {
+        {
+            let _guard = self.shared.sleep.lock();
+            self.shared.shutdown.store(true, Ordering::SeqCst);
+            self.shared.wake.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl JobSystem {
+    /// Creates a job system with the given number of worker threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of workers is zero or a thread can't be spawned.
+    #[must_use]
+    pub fn new(worker_count: usize) -> Self {
+        assert_ne!(worker_count, 0, "Job system must have at least one worker!");
+
+        let shared = Arc::new(Shared {
+            deques: (0..worker_count).map(|_| Mutex::default()).collect(),
+            injector: Mutex::default(),
+            queued: AtomicUsize::new(0),
+            sleep: Mutex::new(()),
+            wake: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let workers = (0..worker_count)
+            .map(|index| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("velcro-worker-{index}"))
+                    .spawn(move || shared.worker_loop(index))
+                    .expect("unable to spawn a worker thread")
+            })
+            .collect();
+
+        Self { shared, workers }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn worker_count(&self) -> usize {
+        self.shared.deques.len()
+    }
+
+    /// Queues the function for execution and returns a handle to wait for its result.
+    pub fn spawn<F, R>(&self, func: F) -> JobHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let state = Arc::new(JobState {
+            result: Mutex::new(None),
+            finished: AtomicBool::new(false),
+        });
+        let job_state = state.clone();
+        self.shared.push(Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(func));
+            *job_state.result.lock() = Some(result);
+            job_state.finished.store(true, Ordering::Release);
+        }));
+
+        JobHandle {
+            state,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Creates a scope for jobs that can borrow data from the calling function, the method returns
+    /// only after all jobs spawned in the scope are finished.
+    ///
+    /// # Panics
+    ///
+    /// If the function or any job of the scope panics, the panic is propagated after all jobs of
+    /// the scope are finished.
+    pub fn scope<'env, F, R>(&self, func: F) -> R
+    where
+        F: for<'scope> FnOnce(&'scope JobScope<'scope, 'env>) -> R,
+    {
+        let scope = JobScope {
+            shared: &self.shared,
+            state: Arc::new(ScopeState {
+                pending: AtomicUsize::new(0),
+                panic: Mutex::new(None),
+            }),
+            scope: PhantomData,
+            env: PhantomData,
+        };
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| func(&scope)));
+        self.shared
+            .wait_until(|| scope.state.pending.load(Ordering::Acquire) == 0);
+
+        if let Some(payload) = scope.state.panic.lock().take() {
+            panic::resume_unwind(payload);
+        }
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    /// Calls the function for every index of the range, the range is split into chunks that are
+    /// processed in parallel.
+    pub fn parallel_for<F>(&self, range: Range<usize>, func: F)
+    where
+        F: Fn(usize) + Sync,
+    {
+        let chunk_size = self.chunk_size(range.len());
+        let func = &func;
+        self.scope(|scope| {
+            for start in range.clone().step_by(chunk_size) {
+                let end = (start + chunk_size).min(range.end);
+                scope.spawn(move || (start..end).for_each(func));
+            }
+        });
+    }
+
+    /// Calls the function for every item of the slice in parallel.
+    pub fn parallel_for_slice<T, F>(&self, slice: &mut [T], func: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Sync,
+    {
+        let chunk_size = self.chunk_size(slice.len());
+        let func = &func;
+        self.scope(|scope| {
+            for chunk in slice.chunks_mut(chunk_size) {
+                scope.spawn(move || chunk.iter_mut().for_each(func));
+            }
+        });
+    }
+
+    /// Calls the function for every object of the allocator in parallel.
+    pub fn parallel_for_allocator<T, F>(&self, allocator: &mut Allocator<T>, func: F)
+    where
+        T: Send + 'static,
+        F: Fn(Handle<T>, &mut T) + Sync,
+    {
+        let mut objects = allocator.pair_iter_mut().collect::<Vec<_>>();
+        self.parallel_for_slice(&mut objects, |(handle, object)| func(*handle, object));
+    }
+
+    fn chunk_size(&self, len: usize) -> usize {
+        len.div_ceil(self.worker_count() * CHUNKS_PER_WORKER).max(1)
+    }
+}
+
+struct JobState<R> {
+    result: Mutex<Option<thread::Result<R>>>,
+    finished: AtomicBool,
+}
+
+/// A handle to the result of a job spawned with [`JobSystem::spawn`]. Dropping the handle does not
+/// cancel the job.
+pub struct JobHandle<R> {
+    state: Arc<JobState<R>>,
+    shared: Arc<Shared>,
+}
+
+impl<R> JobHandle<R> {
+    #[inline]
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(Ordering::Acquire)
+    }
+
+    /// Waits for the job to finish (executing other jobs in the meantime) and returns its result.
+    ///
+    /// # Panics
+    ///
+    /// Propagates the panic of the job.
+    // Jobs that return nothing are waited for only for their side effects.
+    #[allow(clippy::must_use_candidate)]
+    pub fn wait(self) -> R {
+        self.shared.wait_until(|| self.is_finished());
+        match self.state.result.lock().take() {
+            Some(Ok(result)) => result,
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => unreachable!("finished job must have a result"),
+        }
+    }
+}
+
+struct ScopeState {
+    pending: AtomicUsize,
+    /// Payload of the first panic of a job of the scope.
+    panic: Mutex<Option<Panic>>,
+}
+
+/// A scope created with [`JobSystem::scope`].
+pub struct JobScope<'scope, 'env: 'scope> {
+    shared: &'scope Arc<Shared>,
+    state: Arc<ScopeState>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope> JobScope<'scope, '_> {
+    /// Queues the function for execution, it can borrow anything that outlives the scope.
+    pub fn spawn<F>(&'scope self, func: F)
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        let state = self.state.clone();
+        state.pending.fetch_add(1, Ordering::AcqRel);
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(func)) {
+                state.panic.lock().get_or_insert(payload);
+            }
+            state.pending.fetch_sub(1, Ordering::AcqRel);
+        });
+        // SAFETY: `JobSystem::scope` does not return until the job is finished, so everything the
+        // job borrows outlives it.
+        let job = unsafe { mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(job) };
+        self.shared.push(job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    #[test]
+    fn jobs_run_in_parallel_and_wait_for_each_other() {
+        let jobs = JobSystem::new(3);
+
+        let nested = jobs.spawn(|| 1 + 1);
+        assert_eq!(nested.wait(), 2);
+
+        let counter = AtomicU32::new(0);
+        jobs.parallel_for(0..1000, |i| {
+            counter.fetch_add(u32::try_from(i).unwrap(), Ordering::Relaxed);
+        });
+        assert_eq!(counter.load(Ordering::Relaxed), 999 * 1000 / 2);
+
+        let mut allocator = Allocator::new();
+        let handles = (0..100u32).map(|i| allocator.spawn(i)).collect::<Vec<_>>();
+        allocator.free(handles[10]);
+        jobs.parallel_for_allocator(&mut allocator, |handle, value| {
+            assert_eq!(handle.index(), *value);
+            *value *= 2;
+        });
+        assert_eq!(*allocator.borrow(handles[99]), 198);
+
+        // Jobs spawned by other jobs can be waited for on workers.
+        let answer = AtomicU32::new(0);
+        jobs.scope(|scope| {
+            scope.spawn(|| {
+                let halves = [jobs.spawn(|| 20), jobs.spawn(|| 22)];
+                answer.store(
+                    halves.into_iter().map(JobHandle::wait).sum(),
+                    Ordering::Relaxed,
+                );
+            });
+        });
+        assert_eq!(answer.load(Ordering::Relaxed), 42);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            jobs.scope(|scope| scope.spawn(|| panic!("job failed")));
+        }));
+        assert!(result.is_err());
+    }
+}
@@ -1,2 +1,10 @@
 pub mod exponential;
-pub mod spin_mutex;
\ No newline at end of file
+pub mod cache_padded;
+pub mod spin_mutex;
+pub mod spin_rw_lock;
+pub mod ticket_spin_lock;
+pub mod spsc_queue;
+pub mod mpmc_queue;
+pub mod job_system;
+pub mod job_graph;
+pub mod executor;
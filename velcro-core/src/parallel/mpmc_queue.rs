@@ -0,0 +1,285 @@
+#![warn(clippy::pedantic)]
+
+//! Bounded lock-free queue with many producers and many consumers.
+//!
+//! The queue is a ring buffer of slots with sequence numbers (Dmitry Vyukov's design): a slot's
+//! sequence tells whether it is ready for the producer or the consumer at the given position, so
+//! producers and consumers only contend on their own position counter. The queue is shared by
+//! reference (usually through an `Arc`), blocking methods spin with [`ExponentialBackoff`].
+//!
+//! ```
+//! use std::{sync::Arc, thread};
+//! use velcro_core::parallel::mpmc_queue::MpmcQueue;
+//!
+//! let queue = Arc::new(MpmcQueue::new(64));
+//! let producers = (0..4)
+//!     .map(|i| {
+//!         let queue = queue.clone();
+//!         thread::spawn(move || queue.push(i))
+//!     })
+//!     .collect::<Vec<_>>();
+//!
+//! let sum = (0..4).map(|_| queue.pop()).sum::<i32>();
+//! producers.into_iter().for_each(|producer| producer.join().unwrap());
+//! assert_eq!(sum, 6);
+//! ```
+
+use crate::parallel::{cache_padded::CachePadded, exponential::ExponentialBackoff};
+use std::{
+    cell::UnsafeCell,
+    cmp,
+    fmt::{self, Debug, Formatter},
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct Slot<T> {
+    /// Equals to the position of the slot if it is free for a producer and to the position plus
+    /// one if it holds a value for a consumer.
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A bounded queue that can be used from any number of threads at the same time, see the
+/// [module docs](self).
+pub struct MpmcQueue<T> {
+    slots: Box<[Slot<T>]>,
+    enqueue_position: CachePadded<AtomicUsize>,
+    dequeue_position: CachePadded<AtomicUsize>,
+}
+
+// SAFETY: A slot is accessed by a single thread that won the position of the slot, the sequence
+// hands the slot over with release-acquire ordering.
+unsafe impl<T: Send> Send for MpmcQueue<T> {}
+unsafe impl<T: Send> Sync for MpmcQueue<T> {}
+
+impl<T> Debug for MpmcQueue<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MpmcQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<T> Drop for MpmcQueue<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+impl<T> MpmcQueue<T> {
+    /// Creates a queue that holds at least `capacity` items, the capacity is rounded up to a power
+    /// of two and is at least two: with a single slot the sequence of a full slot would be equal
+    /// to the sequence of the same slot free for the next lap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is zero.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        assert_ne!(capacity, 0, "Queue capacity must be non-zero!");
+
+        Self {
+            slots: (0..capacity.next_power_of_two().max(2))
+                .map(|position| Slot {
+                    sequence: AtomicUsize::new(position),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+            enqueue_position: CachePadded::new(AtomicUsize::new(0)),
+            dequeue_position: CachePadded::new(AtomicUsize::new(0)),
+        }
+    }
+
+    #[inline]
+    fn slot(&self, position: usize) -> &Slot<T> {
+        // The capacity is a power of two, so the index stays continuous when the position wraps.
+        &self.slots[position & (self.slots.len() - 1)]
+    }
+
+    /// Pushes the value to the queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns the value if the queue is full.
+    pub fn try_push(&self, value: T) -> Result<(), T> {
+        let mut position = self.enqueue_position.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(position);
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            // Reinterpreting the difference as signed handles wrapping of the positions.
+            #[allow(clippy::cast_possible_wrap)]
+            let difference = sequence.wrapping_sub(position) as isize;
+
+            match difference.cmp(&0) {
+                cmp::Ordering::Equal => {
+                    match self.enqueue_position.compare_exchange_weak(
+                        position,
+                        position.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            // SAFETY: The slot is free and the position is won by this thread.
+                            unsafe { (*slot.value.get()).write(value) };
+                            slot.sequence
+                                .store(position.wrapping_add(1), Ordering::Release);
+                            return Ok(());
+                        }
+                        Err(current) => position = current,
+                    }
+                }
+                cmp::Ordering::Less => {
+                    // The slot still holds a value from the previous lap.
+                    return Err(value);
+                }
+                cmp::Ordering::Greater => {
+                    position = self.enqueue_position.load(Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Spins until there is space in the queue and pushes the value.
+    pub fn push(&self, mut value: T) {
+        let mut backoff = ExponentialBackoff::new();
+        while let Err(rejected) = self.try_push(value) {
+            value = rejected;
+            backoff.wait();
+        }
+    }
+
+    /// Pops the oldest value of the queue, `None` if the queue is empty.
+    pub fn try_pop(&self) -> Option<T> {
+        let mut position = self.dequeue_position.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(position);
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            #[allow(clippy::cast_possible_wrap)]
+            let difference = sequence.wrapping_sub(position.wrapping_add(1)) as isize;
+
+            match difference.cmp(&0) {
+                cmp::Ordering::Equal => {
+                    match self.dequeue_position.compare_exchange_weak(
+                        position,
+                        position.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            // SAFETY: The slot holds a value and the position is won by this thread.
+                            let value = unsafe { (*slot.value.get()).assume_init_read() };
+                            slot.sequence
+                                .store(position.wrapping_add(self.slots.len()), Ordering::Release);
+                            return Some(value);
+                        }
+                        Err(current) => position = current,
+                    }
+                }
+                cmp::Ordering::Less => {
+                    // The slot was not written yet.
+                    return None;
+                }
+                cmp::Ordering::Greater => {
+                    position = self.dequeue_position.load(Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Spins until there is a value in the queue and pops it.
+    pub fn pop(&self) -> T {
+        let mut backoff = ExponentialBackoff::new();
+        loop {
+            if let Some(value) = self.try_pop() {
+                return value;
+            }
+            backoff.wait();
+        }
+    }
+
+    /// Returns the number of values in the queue, the result may be outdated if other threads use
+    /// the queue at the same time.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let dequeue = self.dequeue_position.load(Ordering::Acquire);
+        let enqueue = self.enqueue_position.load(Ordering::Acquire);
+        enqueue.wrapping_sub(dequeue).min(self.capacity())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{atomic::AtomicBool, Arc},
+        thread,
+    };
+
+    #[test]
+    fn mpmc_queue_delivers_every_value_once() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 20_000;
+
+        let queue = MpmcQueue::new(30);
+        assert_eq!(queue.capacity(), 32);
+
+        let seen = (0..THREADS * COUNT)
+            .map(|_| AtomicBool::new(false))
+            .collect::<Vec<_>>();
+        thread::scope(|scope| {
+            for producer in 0..THREADS {
+                let queue = &queue;
+                scope.spawn(move || {
+                    for value in 0..COUNT {
+                        queue.push(producer * COUNT + value);
+                    }
+                });
+            }
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..COUNT {
+                        let value = queue.pop();
+                        assert!(!seen[value].swap(true, Ordering::Relaxed));
+                    }
+                });
+            }
+        });
+        assert!(seen.iter().all(|seen| seen.load(Ordering::Relaxed)));
+        assert!(queue.is_empty());
+
+        // Items left in the queue are dropped with it.
+        let value = Arc::new(());
+        let queue = MpmcQueue::new(2);
+        queue.try_push(value.clone()).unwrap();
+        queue.try_push(value.clone()).unwrap();
+        assert!(queue.try_push(value.clone()).is_err());
+        assert_eq!(queue.len(), 2);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+
+        let queue = MpmcQueue::new(1);
+        assert_eq!(queue.capacity(), 2);
+        for value in 0..10 {
+            queue.try_push(value).unwrap();
+            queue.try_push(value + 1).unwrap();
+            assert_eq!(queue.try_push(value + 2), Err(value + 2));
+            assert_eq!(queue.try_pop(), Some(value));
+            assert_eq!(queue.try_pop(), Some(value + 1));
+            assert_eq!(queue.try_pop(), None);
+        }
+    }
+}
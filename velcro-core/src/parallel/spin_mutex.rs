@@ -1,41 +1,193 @@
-
 #![warn(clippy::pedantic)]
-#![allow(clippy::many_single_char_names)]
+
+//! Mutual exclusion lock that spins instead of putting the thread to sleep, it is meant for very
+//! short critical sections where parking a thread costs more than waiting.
+//!
+//! ```
+//! use velcro_core::parallel::spin_mutex::SpinMutex;
+//!
+//! let counter = SpinMutex::new(0);
+//! *counter.lock() += 1;
+//! assert_eq!(*counter.lock(), 1);
+//! ```
 
 use crate::parallel::exponential::ExponentialBackoff;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A spin lock that owns the data it protects, the data is accessible through the guard returned
+/// by [`Self::lock`]. The lock is not fair, use
+/// [`TicketSpinLock`](crate::parallel::ticket_spin_lock::TicketSpinLock) if waiting threads must
+/// get the lock in order.
+pub struct SpinMutex<T: ?Sized> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: The lock gives access to the data to one thread at a time.
+unsafe impl<T: ?Sized + Send> Send for SpinMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SpinMutex<T> {}
 
-pub struct SpinMutex {
-    _flag: AtomicBool
+impl<T: Default> Default for SpinMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
 }
 
-impl SpinMutex {
-    pub fn new(is_locked: bool) -> Self {
-        SpinMutex {
-            _flag: AtomicBool::new(is_locked)
+impl<T: ?Sized + Debug> Debug for SpinMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f.debug_struct("SpinMutex").field("data", &&*guard).finish(),
+            None => f
+                .debug_struct("SpinMutex")
+                .field("data", &"<locked>")
+                .finish(),
         }
     }
+}
 
-    pub fn lock(&self) {
-        let mut expected = false;
-        if self._flag.compare_exchange_weak(expected, true, Ordering::AcqRel, Ordering::Acquire).is_ok() {
-            let mut backoff: ExponentialBackoff = ExponentialBackoff::new();
-            loop {
-                expected = false;
-                if self._flag.compare_exchange_weak(expected, true, Ordering::AcqRel, Ordering::Acquire).is_ok() {
-                    break;
-                }
+impl<T> SpinMutex<T> {
+    #[inline]
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinMutex<T> {
+    /// Spins until the lock is acquired.
+    pub fn lock(&self) -> SpinMutexGuard<'_, T> {
+        let mut backoff = ExponentialBackoff::new();
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Plain loads keep the cache line shared between waiting threads until the lock is
+            // released.
+            while self.locked.load(Ordering::Relaxed) {
                 backoff.wait();
             }
         }
+        self.guard()
+    }
+
+    /// Acquires the lock if it is free, never spins.
+    pub fn try_lock(&self) -> Option<SpinMutexGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+            .then(|| self.guard())
+    }
+
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Returns the data without locking, the mutable reference guarantees that there are no
+    /// guards.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn guard(&self) -> SpinMutexGuard<'_, T> {
+        SpinMutexGuard {
+            locked: &self.locked,
+            data: self.data.get(),
+            marker: PhantomData,
+        }
     }
+}
+
+/// Gives access to the data of a locked [`SpinMutex`], the lock is released when the guard is
+/// dropped.
+#[must_use = "the lock is released immediately if the guard is not used"]
+pub struct SpinMutexGuard<'a, T: ?Sized> {
+    locked: &'a AtomicBool,
+    data: *mut T,
+    marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY: The guard is a unique reference to the data.
+unsafe impl<T: ?Sized + Send> Send for SpinMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for SpinMutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for SpinMutexGuard<'_, T> {
+    type Target = T;
 
-    pub fn try_lock(&self) -> bool {
-        let expected = false;
-        return self._flag.compare_exchange_weak(expected, true, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The lock is held by the guard.
+        unsafe { &*self.data }
     }
+}
 
-    pub fn unlock(&self) {
-        self._flag.store(false, Ordering::Release);
+impl<T: ?Sized> DerefMut for SpinMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The lock is held by the guard.
+        unsafe { &mut *self.data }
     }
-}
\ No newline at end of file
+}
+
+impl<T: ?Sized + Debug> Debug for SpinMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for SpinMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn spin_mutex_gives_exclusive_access() {
+        const THREADS: usize = 8;
+        const ITERATIONS: usize = 10_000;
+
+        // The pair is updated in two steps, a torn update would be visible to other threads.
+        let pair = SpinMutex::new((0usize, 0usize));
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..ITERATIONS {
+                        let mut guard = pair.lock();
+                        assert_eq!(guard.0, guard.1);
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            pair.into_inner(),
+            (THREADS * ITERATIONS, THREADS * ITERATIONS)
+        );
+
+        let mutex = SpinMutex::new(1);
+        let guard = mutex.lock();
+        assert!(mutex.is_locked());
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+        assert_eq!(mutex.try_lock().as_deref(), Some(&1));
+    }
+}
@@ -0,0 +1,283 @@
+#![warn(clippy::pedantic)]
+
+//! Reader-writer lock that spins instead of putting the thread to sleep.
+//!
+//! ```
+//! use velcro_core::parallel::spin_rw_lock::SpinRwLock;
+//!
+//! let lock = SpinRwLock::new(vec![1, 2]);
+//! lock.write().push(3);
+//!
+//! let (first, second) = (lock.read(), lock.read());
+//! assert_eq!(first.len() + second.len(), 6);
+//! ```
+
+use crate::parallel::exponential::ExponentialBackoff;
+use std::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The lock is held by a writer.
+const WRITER: usize = 1;
+/// A writer waits for the lock, new readers wait until it gets the lock, so a steady stream of
+/// readers can't starve writers.
+const WRITER_WAITING: usize = 2;
+/// Readers are counted in the rest of the bits.
+const READER: usize = 4;
+
+/// A spin lock that allows either many readers or a single writer at a time.
+pub struct SpinRwLock<T: ?Sized> {
+    state: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: The lock gives mutable access to one thread at a time and shared access only while
+// there is no mutable access.
+unsafe impl<T: ?Sized + Send> Send for SpinRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for SpinRwLock<T> {}
+
+impl<T: Default> Default for SpinRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for SpinRwLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.try_read() {
+            Some(guard) => f
+                .debug_struct("SpinRwLock")
+                .field("data", &&*guard)
+                .finish(),
+            None => f
+                .debug_struct("SpinRwLock")
+                .field("data", &"<locked>")
+                .finish(),
+        }
+    }
+}
+
+impl<T> SpinRwLock<T> {
+    #[inline]
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinRwLock<T> {
+    /// Spins until there are no writers and returns a guard for shared access.
+    pub fn read(&self) -> SpinRwLockReadGuard<'_, T> {
+        let mut backoff = ExponentialBackoff::new();
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            backoff.wait();
+        }
+    }
+
+    /// Returns a guard for shared access if there are no writers, never spins.
+    pub fn try_read(&self) -> Option<SpinRwLockReadGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & (WRITER | WRITER_WAITING) != 0 {
+            return None;
+        }
+        self.state
+            .compare_exchange(state, state + READER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| SpinRwLockReadGuard {
+                state: &self.state,
+                data: self.data.get(),
+                marker: PhantomData,
+            })
+    }
+
+    /// Spins until there are no readers and writers and returns a guard for mutable access.
+    pub fn write(&self) -> SpinRwLockWriteGuard<'_, T> {
+        let mut backoff = ExponentialBackoff::new();
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & !WRITER_WAITING == 0 {
+                if self
+                    .state
+                    .compare_exchange_weak(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return self.write_guard();
+                }
+            } else if state & WRITER_WAITING == 0 {
+                self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            }
+            backoff.wait();
+        }
+    }
+
+    /// Returns a guard for mutable access if there are no readers and writers, never spins.
+    pub fn try_write(&self) -> Option<SpinRwLockWriteGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & !WRITER_WAITING != 0 {
+            return None;
+        }
+        self.state
+            .compare_exchange(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| self.write_guard())
+    }
+
+    /// Returns the number of active readers.
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) / READER
+    }
+
+    #[inline]
+    pub fn is_locked_exclusive(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Returns the data without locking, the mutable reference guarantees that there are no
+    /// guards.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn write_guard(&self) -> SpinRwLockWriteGuard<'_, T> {
+        SpinRwLockWriteGuard {
+            state: &self.state,
+            data: self.data.get(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Gives shared access to the data of a [`SpinRwLock`], the lock is released when the guard is
+/// dropped.
+#[must_use = "the lock is released immediately if the guard is not used"]
+pub struct SpinRwLockReadGuard<'a, T: ?Sized> {
+    state: &'a AtomicUsize,
+    data: *const T,
+    marker: PhantomData<&'a T>,
+}
+
+// SAFETY: The guard is a shared reference to the data.
+unsafe impl<T: ?Sized + Sync> Send for SpinRwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for SpinRwLockReadGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for SpinRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: There are no writers while the guard is alive.
+        unsafe { &*self.data }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for SpinRwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for SpinRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.state.fetch_sub(READER, Ordering::Release);
+    }
+}
+
+/// Gives mutable access to the data of a [`SpinRwLock`], the lock is released when the guard is
+/// dropped.
+#[must_use = "the lock is released immediately if the guard is not used"]
+pub struct SpinRwLockWriteGuard<'a, T: ?Sized> {
+    state: &'a AtomicUsize,
+    data: *mut T,
+    marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY: The guard is a unique reference to the data.
+unsafe impl<T: ?Sized + Send> Send for SpinRwLockWriteGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for SpinRwLockWriteGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for SpinRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The lock is held exclusively by the guard.
+        unsafe { &*self.data }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The lock is held exclusively by the guard.
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for SpinRwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for SpinRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // Other writers may have announced themselves while the lock was held, keep their flag.
+        self.state.fetch_and(!WRITER, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn spin_rw_lock_excludes_writers() {
+        const ITERATIONS: usize = 5_000;
+
+        let pair = SpinRwLock::new((0usize, 0usize));
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..ITERATIONS {
+                        let guard = pair.read();
+                        assert_eq!(guard.0, guard.1);
+                    }
+                });
+                scope.spawn(|| {
+                    for _ in 0..ITERATIONS {
+                        let mut guard = pair.write();
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(pair.into_inner(), (4 * ITERATIONS, 4 * ITERATIONS));
+
+        let lock = SpinRwLock::new(0);
+        let (first, second) = (lock.read(), lock.read());
+        assert_eq!(lock.reader_count(), 2);
+        assert!(lock.try_write().is_none());
+        drop((first, second));
+
+        let guard = lock.write();
+        assert!(lock.is_locked_exclusive());
+        assert!(lock.try_read().is_none());
+        drop(guard);
+        assert!(lock.try_write().is_some());
+    }
+}
@@ -0,0 +1,303 @@
+#![warn(clippy::pedantic)]
+
+//! Bounded lock-free queue with a single producer and a single consumer.
+//!
+//! The queue is a ring buffer shared by [`SpscProducer`] and [`SpscConsumer`], the halves can be
+//! moved to different threads but can't be cloned, which makes sure that there is only one thread
+//! on each side. Besides the non-blocking `try_` methods, both halves have blocking methods that
+//! spin with [`ExponentialBackoff`] and stop waiting when the other half is dropped.
+//!
+//! ```
+//! use std::thread;
+//! use velcro_core::parallel::spsc_queue;
+//!
+//! let (mut producer, mut consumer) = spsc_queue::channel(16);
+//! let receiver = thread::spawn(move || {
+//!     let mut sum = 0;
+//!     while let Some(value) = consumer.pop() {
+//!         sum += value;
+//!     }
+//!     sum
+//! });
+//!
+//! for value in 1..=100 {
+//!     producer.push(value).unwrap();
+//! }
+//! drop(producer);
+//! assert_eq!(receiver.join().unwrap(), 5050);
+//! ```
+
+use crate::parallel::{cache_padded::CachePadded, exponential::ExponentialBackoff};
+use std::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+struct Ring<T> {
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Position of the next item to pop, written only by the consumer.
+    head: CachePadded<AtomicUsize>,
+    /// Position of the next item to push, written only by the producer.
+    tail: CachePadded<AtomicUsize>,
+    /// Set when either half is dropped, released after the last push or pop of the dropped half.
+    disconnected: AtomicBool,
+}
+
+// SAFETY: A slot is accessed either by the producer or by the consumer, the positions hand slots
+// over with release-acquire ordering.
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    #[inline]
+    fn slot(&self, position: usize) -> *mut MaybeUninit<T> {
+        // The capacity is a power of two, so the index stays continuous when the position wraps.
+        self.buffer[position & (self.buffer.len() - 1)].get()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.head.load(Ordering::Acquire))
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.get_mut(), *self.tail.get_mut());
+        let mut position = head;
+        while position != tail {
+            // SAFETY: Slots between the head and the tail are initialized.
+            unsafe { (*self.slot(position)).assume_init_drop() };
+            position = position.wrapping_add(1);
+        }
+    }
+}
+
+/// Creates a queue that holds at least `capacity` items, the capacity is rounded up to a power of
+/// two.
+///
+/// # Panics
+///
+/// Panics if the capacity is zero.
+#[must_use]
+pub fn channel<T>(capacity: usize) -> (SpscProducer<T>, SpscConsumer<T>) {
+    assert_ne!(capacity, 0, "Queue capacity must be non-zero!");
+
+    let ring = Arc::new(Ring {
+        buffer: (0..capacity.next_power_of_two())
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        head: CachePadded::new(AtomicUsize::new(0)),
+        tail: CachePadded::new(AtomicUsize::new(0)),
+        disconnected: AtomicBool::new(false),
+    });
+
+    (SpscProducer { ring: ring.clone() }, SpscConsumer { ring })
+}
+
+/// The sending half of a queue created with [`channel`].
+pub struct SpscProducer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Debug for SpscProducer<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpscProducer")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<T> Drop for SpscProducer<T> {
+    fn drop(&mut self) {
+        self.ring.disconnected.store(true, Ordering::Release);
+    }
+}
+
+impl<T> SpscProducer<T> {
+    /// Pushes the value to the queue, returns it back if the queue is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns the value if the queue is full.
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let head = self.ring.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == self.ring.buffer.len() {
+            return Err(value);
+        }
+
+        // SAFETY: The slot is free, the consumer won't touch it until the tail is moved.
+        unsafe { (*self.ring.slot(tail)).write(value) };
+        self.ring
+            .tail
+            .store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Spins until there is space in the queue and pushes the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns the value if the consumer is dropped, nobody would ever pop it.
+    pub fn push(&mut self, mut value: T) -> Result<(), T> {
+        let mut backoff = ExponentialBackoff::new();
+        loop {
+            match self.try_push(value) {
+                Ok(()) => return Ok(()),
+                Err(rejected) if self.is_disconnected() => return Err(rejected),
+                Err(rejected) => value = rejected,
+            }
+            backoff.wait();
+        }
+    }
+
+    /// Returns `true` if the consumer is dropped.
+    #[inline]
+    #[must_use]
+    pub fn is_disconnected(&self) -> bool {
+        self.ring.disconnected.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.ring.buffer.len()
+    }
+}
+
+/// The receiving half of a queue created with [`channel`].
+pub struct SpscConsumer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Debug for SpscConsumer<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpscConsumer")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<T> Drop for SpscConsumer<T> {
+    fn drop(&mut self) {
+        self.ring.disconnected.store(true, Ordering::Release);
+    }
+}
+
+impl<T> SpscConsumer<T> {
+    /// Pops the oldest value of the queue, `None` if the queue is empty.
+    pub fn try_pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        // SAFETY: The slot is initialized, the producer won't touch it until the head is moved.
+        let value = unsafe { (*self.ring.slot(head)).assume_init_read() };
+        self.ring
+            .head
+            .store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Spins until there is a value in the queue and pops it. Returns `None` if the queue is empty
+    /// and the producer is dropped.
+    pub fn pop(&mut self) -> Option<T> {
+        let mut backoff = ExponentialBackoff::new();
+        loop {
+            if let Some(value) = self.try_pop() {
+                return Some(value);
+            }
+            // The producer could push a value right before it was dropped, the acquire load of
+            // the flag makes that value visible to the last pop.
+            if self.is_disconnected() {
+                return self.try_pop();
+            }
+            backoff.wait();
+        }
+    }
+
+    /// Returns `true` if the producer is dropped.
+    #[inline]
+    #[must_use]
+    pub fn is_disconnected(&self) -> bool {
+        self.ring.disconnected.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.ring.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn spsc_queue_keeps_order_across_threads() {
+        const COUNT: usize = 100_000;
+
+        let (mut producer, mut consumer) = channel(10);
+        assert_eq!(producer.capacity(), 16);
+
+        let sender = thread::spawn(move || {
+            for value in 0..COUNT {
+                producer.push(Box::new(value)).unwrap();
+            }
+        });
+        for expected in 0..COUNT {
+            assert_eq!(consumer.pop().as_deref(), Some(&expected));
+        }
+        sender.join().unwrap();
+        assert!(consumer.is_disconnected());
+        assert_eq!(consumer.pop(), None);
+
+        // Items left in the queue are dropped with it.
+        let value = Arc::new(());
+        let (mut producer, consumer) = channel(2);
+        producer.try_push(value.clone()).unwrap();
+        producer.try_push(value.clone()).unwrap();
+        assert!(producer.try_push(value.clone()).is_err());
+        drop(consumer);
+        assert!(producer.push(value.clone()).is_err());
+        drop(producer);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,208 @@
+#![warn(clippy::pedantic)]
+
+//! Fair spin lock, threads get the lock in the order they asked for it.
+//!
+//! ```
+//! use velcro_core::parallel::ticket_spin_lock::TicketSpinLock;
+//!
+//! let queue = TicketSpinLock::new(Vec::new());
+//! queue.lock().push("first");
+//! assert_eq!(queue.lock().len(), 1);
+//! ```
+
+use crate::parallel::exponential::ExponentialBackoff;
+use std::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A spin lock that serves waiting threads in FIFO order: every thread takes a ticket and waits
+/// until its number is served, so no thread can be starved. Waiting is slower than with
+/// [`SpinMutex`](crate::parallel::spin_mutex::SpinMutex) when there are more waiting threads than
+/// cores, because a preempted thread holds up everyone behind it.
+pub struct TicketSpinLock<T: ?Sized> {
+    next_ticket: AtomicUsize,
+    now_serving: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: The lock gives access to the data to one thread at a time.
+unsafe impl<T: ?Sized + Send> Send for TicketSpinLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for TicketSpinLock<T> {}
+
+impl<T: Default> Default for TicketSpinLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for TicketSpinLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f
+                .debug_struct("TicketSpinLock")
+                .field("data", &&*guard)
+                .finish(),
+            None => f
+                .debug_struct("TicketSpinLock")
+                .field("data", &"<locked>")
+                .finish(),
+        }
+    }
+}
+
+impl<T> TicketSpinLock<T> {
+    #[inline]
+    pub const fn new(data: T) -> Self {
+        Self {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> TicketSpinLock<T> {
+    /// Takes a ticket and spins until it is served.
+    pub fn lock(&self) -> TicketSpinLockGuard<'_, T> {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let mut backoff = ExponentialBackoff::new();
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            backoff.wait();
+        }
+        self.guard(ticket)
+    }
+
+    /// Acquires the lock if nobody holds or waits for it, never spins.
+    pub fn try_lock(&self) -> Option<TicketSpinLockGuard<'_, T>> {
+        let ticket = self.now_serving.load(Ordering::Acquire);
+        self.next_ticket
+            .compare_exchange(
+                ticket,
+                ticket.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+            .then(|| self.guard(ticket))
+    }
+
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+    }
+
+    /// Returns the data without locking, the mutable reference guarantees that there are no
+    /// guards.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn guard(&self, ticket: usize) -> TicketSpinLockGuard<'_, T> {
+        TicketSpinLockGuard {
+            now_serving: &self.now_serving,
+            ticket,
+            data: self.data.get(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Gives access to the data of a locked [`TicketSpinLock`], the lock is passed to the next ticket
+/// when the guard is dropped.
+#[must_use = "the lock is released immediately if the guard is not used"]
+pub struct TicketSpinLockGuard<'a, T: ?Sized> {
+    now_serving: &'a AtomicUsize,
+    ticket: usize,
+    data: *mut T,
+    marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY: The guard is a unique reference to the data.
+unsafe impl<T: ?Sized + Send> Send for TicketSpinLockGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for TicketSpinLockGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for TicketSpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The lock is held by the guard.
+        unsafe { &*self.data }
+    }
+}
+
+impl<T: ?Sized> DerefMut for TicketSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The lock is held by the guard.
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for TicketSpinLockGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for TicketSpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.now_serving
+            .store(self.ticket.wrapping_add(1), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn ticket_spin_lock_serves_threads_in_order() {
+        const THREADS: usize = 8;
+        const ITERATIONS: usize = 5_000;
+
+        let pair = TicketSpinLock::new((0usize, 0usize));
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..ITERATIONS {
+                        let mut guard = pair.lock();
+                        assert_eq!(guard.0, guard.1);
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            pair.into_inner(),
+            (THREADS * ITERATIONS, THREADS * ITERATIONS)
+        );
+
+        // Threads take their tickets one after another while the lock is held, so they must get
+        // the lock in the same order.
+        let order = TicketSpinLock::new(Vec::new());
+        let guard = order.lock();
+        thread::scope(|scope| {
+            for i in 0..4 {
+                let order = &order;
+                scope.spawn(move || order.lock().push(i));
+                while order.next_ticket.load(Ordering::Relaxed) != i + 2 {
+                    thread::yield_now();
+                }
+            }
+            assert!(order.try_lock().is_none());
+            drop(guard);
+        });
+        assert_eq!(order.into_inner(), [0, 1, 2, 3]);
+    }
+}
@@ -6,7 +6,7 @@ use proc_macro2::TokenStream as TokenStream2;
 use quote::*;
 use syn::*;
 
-// impl `#[derive(Visit)]` for `struct` or `enum`
+// impl `#[derive(Context)]` for `struct` or `enum`
 pub fn impl_context(ast: DeriveInput) -> TokenStream2 {
     let ty_args = args::TypeArgs::from_derive_input(&ast).unwrap();
     match &ty_args.data {
@@ -44,7 +44,7 @@ fn impl_context_struct(
     utils::create_impl(ty_args, field_args.iter().cloned(), context_fn_body)
 }
 
-/// impl `Visit` for `enum`
+/// impl `Context` for `enum`
 fn impl_visit_enum(ty_args: &args::TypeArgs, variant_args: &[args::VariantArgs]) -> TokenStream2 {
     let ty_ident = &ty_args.ident;
     let ty_name = format!("{}", ty_ident);
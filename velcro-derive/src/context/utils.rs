@@ -19,12 +19,12 @@ pub fn create_impl(
 
     quote! {
         #[allow(clippy::question_mark)]
-        impl #impl_generics Visit for #ty_ident #ty_generics #where_clause {
-            fn visit(
+        impl #impl_generics Context for #ty_ident #ty_generics #where_clause {
+            fn context(
                 &mut self,
                 name: &str,
-                visitor: &mut Visitor,
-            ) -> VisitResult {
+                reflect_context: &mut ReflectContext,
+            ) -> ReflectResult {
                 #impl_body
             }
         }
@@ -42,7 +42,7 @@ fn create_impl_generics(
         field_args
             .filter(|f| !f.skip)
             .map(|f| f.ty)
-            .map::<WherePredicate, _>(|ty| parse_quote! { #ty: Visit }),
+            .map::<WherePredicate, _>(|ty| parse_quote! { #ty: Context }),
     );
 
     generics
@@ -112,7 +112,7 @@ pub fn create_field_contexts<'a>(
     let mut no_dup = VHashSet::default();
     for name in context_args.iter().map(|(_, name, _)| name) {
         if !no_dup.insert(name) {
-            panic!("duplicate visiting names detected!");
+            panic!("duplicate context names detected!");
         }
     }
 
@@ -123,11 +123,11 @@ pub fn create_field_contexts<'a>(
         .map(|(ident, name, optional)| {
             if optional_override || *optional {
                 quote! {
-                    #prefix #ident.visit(#name, &mut region).ok();
+                    #prefix #ident.context(#name, &mut region).ok();
                 }
             } else {
                 quote! {
-                    if let Err(err) = #prefix #ident.visit(#name, &mut region) {
+                    if let Err(err) = #prefix #ident.context(#name, &mut region) {
                         return Err(err);
                     }
                 }
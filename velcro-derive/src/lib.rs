@@ -49,6 +49,16 @@ pub fn impl_reflect(input: TokenStream) -> TokenStream {
     TokenStream::from(reflect_impl)
 }
 
+/// Implements `ReflectMethods` for methods marked with `#[reflect(method)]` in the impl block
+///
+/// The type has to be marked with `#[reflect(methods)]` to make the methods available through
+/// `Reflect::call_method`.
+#[proc_macro_attribute]
+pub fn reflect_methods(_attr: TokenStream, input: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(input as syn::ItemImpl);
+    TokenStream::from(reflect::method::impl_reflect_methods(item))
+}
+
 #[proc_macro]
 pub fn impl_context(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
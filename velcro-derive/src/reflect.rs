@@ -1,4 +1,5 @@
 pub mod args;
+pub mod method;
 mod property;
 mod syntax;
 
@@ -211,14 +212,22 @@ fn struct_set_field_body(ty_args: &args::TypeArgs) -> Option<TokenStream2> {
                     let prev = self.#setter(value);
                     Ok(Box::new(prev))
                 }
-                Err(current) => {
-                    Err(current)
+                Err(value) => {
+                    Err(SetFieldError::TypeMismatch {
+                        name: name.to_string(),
+                        value,
+                    })
                 }
             })
         }}
     });
 
     Some(quote! {
+        let value = match self.check_field_value(name, value) {
+            Ok(value) => value,
+            Err(err) => return func(Err(err)),
+        };
+
         match name {
             #(
                 #prop_values => #set_fields,
@@ -228,8 +237,14 @@ fn struct_set_field_body(ty_args: &args::TypeArgs) -> Option<TokenStream2> {
                 self.field_mut(name, &mut move |field| {
                     let value = opt_value.take().unwrap();
                     match field {
-                        Some(f) => func(f.set(value)),
-                        None => func(Err(value)),
+                        Some(f) => func(f.set(value).map_err(|value| SetFieldError::TypeMismatch {
+                            name: name.to_string(),
+                            value,
+                        })),
+                        None => func(Err(SetFieldError::NoSuchField {
+                            name: name.to_string(),
+                            value,
+                        })),
                     };
                 });
             },
@@ -238,6 +253,126 @@ fn struct_set_field_body(ty_args: &args::TypeArgs) -> Option<TokenStream2> {
 }
 
 fn impl_reflect_enum(ty_args: &args::TypeArgs, variant_args: &[args::VariantArgs]) -> TokenStream2 {
+    let reflect_impl = self::impl_reflect_enum_fields(ty_args, variant_args);
+    let reflect_enum_impl = self::gen_reflect_enum_impl(ty_args, variant_args);
+
+    quote! {
+        #reflect_impl
+
+        #reflect_enum_impl
+    }
+}
+
+/// Implements `ReflectEnum`, new variants are created with `Default` values of their fields, or
+/// with `default_value` for fields whose type isn't known to implement `Default`.
+fn gen_reflect_enum_impl(
+    ty_args: &args::TypeArgs,
+    variant_args: &[args::VariantArgs],
+) -> TokenStream2 {
+    let ty_ident = &ty_args.ident;
+    let generics = ty_args.impl_generics();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let variant_idents = variant_args.iter().map(|v| &v.ident).collect::<Vec<_>>();
+    let variant_names = variant_idents
+        .iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>();
+
+    let constructors = variant_args.iter().zip(&variant_names).map(|(v, variant_name)| {
+        let variant_ident = &v.ident;
+        let values = v.fields.iter().enumerate().map(|(i, f)| {
+            let ty = &f.ty;
+            let field_name = match &f.ident {
+                Some(ident) => ident.to_string(),
+                None => i.to_string(),
+            };
+            // Hidden fields may not implement `Reflect`, so only `Default` is tried for them.
+            let fallback = if f.hidden {
+                quote!()
+            } else {
+                quote!(.or_else(default_value::<#ty>))
+            };
+            quote! {
+                match (&Probe::<#ty>(::core::marker::PhantomData)).probe_default()#fallback {
+                    Some(value) => value,
+                    None => {
+                        return Err(SetVariantError::NoDefaultValue {
+                            variant: #variant_name,
+                            field: #field_name,
+                        })
+                    }
+                }
+            }
+        });
+        let value = match v.fields.style {
+            ast::Style::Struct => {
+                let idents = v.fields.iter().map(|f| f.ident.as_ref().unwrap());
+                quote!(#ty_ident::#variant_ident { #( #idents: #values ),* })
+            }
+            ast::Style::Tuple => quote!(#ty_ident::#variant_ident ( #( #values ),* )),
+            ast::Style::Unit => quote!(#ty_ident::#variant_ident),
+        };
+        quote! {
+            #variant_name => #value,
+        }
+    });
+
+    quote! {
+        #[allow(warnings)]
+        impl #impl_generics ReflectEnum for #ty_ident #ty_generics #where_clause {
+            fn variant_name(&self) -> &'static str {
+                match self {
+                    #( #ty_ident::#variant_idents { .. } => #variant_names, )*
+                }
+            }
+
+            fn variant_names(&self) -> &'static [&'static str] {
+                &[ #( #variant_names ),* ]
+            }
+
+            fn set_variant(&mut self, name: &str) -> Result<(), SetVariantError> {
+                struct Probe<T>(::core::marker::PhantomData<T>);
+
+                trait DefaultProbe<T> {
+                    fn probe_default(&self) -> Option<T>;
+                }
+
+                impl<T: Default> DefaultProbe<T> for Probe<T> {
+                    fn probe_default(&self) -> Option<T> {
+                        Some(T::default())
+                    }
+                }
+
+                trait DefaultFallback<T> {
+                    fn probe_default(&self) -> Option<T>;
+                }
+
+                impl<T> DefaultFallback<T> for &Probe<T> {
+                    fn probe_default(&self) -> Option<T> {
+                        None
+                    }
+                }
+
+                if self.variant_name() == name {
+                    return Ok(());
+                }
+
+                *self = match name {
+                    #( #constructors )*
+                    _ => return Err(SetVariantError::UnknownVariant(name.to_string())),
+                };
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn impl_reflect_enum_fields(
+    ty_args: &args::TypeArgs,
+    variant_args: &[args::VariantArgs],
+) -> TokenStream2 {
     let mut fields_list = Vec::new();
     let mut fields_list_mut = Vec::new();
     let mut fields_info = Vec::new();
@@ -404,13 +539,18 @@ fn gen_impl(
 
     let as_list_impl = ty_args.as_list_impl();
     let as_array_impl = ty_args.as_array_impl();
+    let as_enum_impl = ty_args.as_enum_impl();
+    let methods_impl = ty_args.methods_impl();
+    let clone_box_impl = ty_args.clone_box_impl();
+    let default_box_impl = ty_args.default_box_impl();
+    let reflect_eq_impl = ty_args.reflect_eq_impl();
 
     let doc = args::fetch_doc_comment(&ty_args.attrs);
     let assembly_name = std::env::var("CARGO_PKG_NAME").unwrap_or_default();
 
     let set_field = set_field.map(|set_field| {
         quote! {
-            fn set_field(&mut self, name: &str, value: Box<dyn Reflect>, func: &mut dyn FnMut(Result<Box<dyn Reflect>, Box<dyn Reflect>>),) {
+            fn set_field(&mut self, name: &str, value: Box<dyn Reflect>, func: &mut dyn FnMut(Result<Box<dyn Reflect>, SetFieldError>),) {
                 #set_field
             }
         }
@@ -495,6 +635,16 @@ fn gen_impl(
             #as_array_impl
 
             #as_list_impl
+
+            #as_enum_impl
+
+            #methods_impl
+
+            #clone_box_impl
+
+            #default_box_impl
+
+            #reflect_eq_impl
         }
     }
 }
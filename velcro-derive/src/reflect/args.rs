@@ -35,6 +35,25 @@ pub struct TypeArgs {
 
     #[darling(default, rename = "ReflectList")]
     pub impl_as_list: bool,
+
+    /// `#[reflect(no_clone)]`
+    ///
+    /// Do not implement `Reflect::clone_box` even if the type implements `Clone`
+    #[darling(default)]
+    pub no_clone: bool,
+
+    /// `#[reflect(no_eq)]`
+    ///
+    /// Do not implement `Reflect::reflect_eq` even if the type implements `PartialEq`
+    #[darling(default)]
+    pub no_eq: bool,
+
+    /// `#[reflect(methods)]`
+    ///
+    /// Forward `Reflect::call_method` and friends to the `ReflectMethods` impl created with
+    /// `#[reflect_methods]`
+    #[darling(default)]
+    pub methods: bool,
 }
 
 impl TypeArgs {
@@ -97,6 +116,283 @@ impl TypeArgs {
         }
     }
 
+    /// Returns `true` if the type has type parameters. Whether such a type implements `Clone` or
+    /// `PartialEq` depends on the parameters, which can't be checked without specialization.
+    fn has_type_params(&self) -> bool {
+        self.generics.type_params().next().is_some()
+    }
+
+    /// Returns paths and fields of the struct or of every variant of the enum, if every field has
+    /// a `Reflect` bound in [`Self::impl_generics`]. Generic types are cloned and compared field by
+    /// field through these bounds.
+    fn reflected_shapes(&self) -> Option<Vec<(TokenStream2, &Fields)>> {
+        let shapes = match &self.data {
+            // A generic unit struct can only be an opaque foreign type declared in `impl_reflect!`.
+            ast::Data::Struct(fields) if fields.style.is_unit() => return None,
+            ast::Data::Struct(fields) => vec![(quote!(Self), fields)],
+            ast::Data::Enum(variants) => variants
+                .iter()
+                .map(|v| {
+                    let ident = &v.ident;
+                    (quote!(Self::#ident), &v.fields)
+                })
+                .collect(),
+        };
+
+        let all_reflected = shapes.iter().all(|(_, fields)| {
+            fields
+                .iter()
+                .all(|f| !(f.hidden || f.deref || f.field.is_some()))
+        });
+        (all_reflected && !shapes.is_empty()).then_some(shapes)
+    }
+
+    /// Implements `Reflect::clone_box` if the type implements `Clone`. The check uses autoref-based
+    /// dispatch, which only works for types without type parameters: generic types are cloned
+    /// field by field with `Reflect::clone_box` instead.
+    pub fn clone_box_impl(&self) -> TokenStream2 {
+        if self.no_clone {
+            return quote!();
+        }
+
+        if self.has_type_params() {
+            let Some(shapes) = self.reflected_shapes() else {
+                return quote!();
+            };
+            let arms = shapes.iter().map(|(path, fields)| {
+                let bindings = bindings(fields, "this");
+                let values = bindings
+                    .iter()
+                    .map(|binding| quote!(Reflect::clone_box(#binding)?.take().ok()?))
+                    .collect::<Vec<_>>();
+                let pattern = compose(path, fields, &bindings);
+                let value = compose(path, fields, &values);
+                quote!(#pattern => #value,)
+            });
+
+            return quote! {
+                fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+                    Some(Box::new(match self {
+                        #( #arms )*
+                    }))
+                }
+            };
+        }
+
+        quote! {
+            fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+                struct Probe<'a, T>(&'a T);
+
+                trait CloneProbe {
+                    fn probe_clone(&self) -> Option<Box<dyn Reflect>>;
+                }
+
+                impl<T: Clone + Reflect> CloneProbe for Probe<'_, T> {
+                    fn probe_clone(&self) -> Option<Box<dyn Reflect>> {
+                        Some(Box::new(self.0.clone()))
+                    }
+                }
+
+                trait CloneFallback {
+                    fn probe_clone(&self) -> Option<Box<dyn Reflect>>;
+                }
+
+                impl<T> CloneFallback for &Probe<'_, T> {
+                    fn probe_clone(&self) -> Option<Box<dyn Reflect>> {
+                        None
+                    }
+                }
+
+                (&Probe(self)).probe_clone()
+            }
+        }
+    }
+
+    /// Implements `Reflect::default_box` if the type implements `Default`, see
+    /// [`Self::clone_box_impl`]. Generic structs are created field by field with `default_value`,
+    /// generic enums have no variant that could be picked as the default one.
+    pub fn default_box_impl(&self) -> TokenStream2 {
+        if self.has_type_params() {
+            let (ast::Data::Struct(_), Some(shapes)) = (&self.data, self.reflected_shapes()) else {
+                return quote!();
+            };
+            let [(path, fields)] = shapes.as_slice() else {
+                return quote!();
+            };
+            let values = fields
+                .iter()
+                .map(|f| {
+                    let ty = &f.ty;
+                    quote!(default_value::<#ty>()?)
+                })
+                .collect::<Vec<_>>();
+            let value = compose(path, fields, &values);
+
+            return quote! {
+                fn default_box() -> Option<Box<dyn Reflect>> {
+                    Some(Box::new(#value))
+                }
+            };
+        }
+
+        quote! {
+            fn default_box() -> Option<Box<dyn Reflect>> {
+                struct Probe<T>(::core::marker::PhantomData<T>);
+
+                trait DefaultProbe {
+                    fn probe_default(&self) -> Option<Box<dyn Reflect>>;
+                }
+
+                impl<T: Default + Reflect> DefaultProbe for Probe<T> {
+                    fn probe_default(&self) -> Option<Box<dyn Reflect>> {
+                        Some(Box::new(T::default()))
+                    }
+                }
+
+                trait DefaultFallback {
+                    fn probe_default(&self) -> Option<Box<dyn Reflect>>;
+                }
+
+                impl<T> DefaultFallback for &Probe<T> {
+                    fn probe_default(&self) -> Option<Box<dyn Reflect>> {
+                        None
+                    }
+                }
+
+                (&Probe::<Self>(::core::marker::PhantomData)).probe_default()
+            }
+        }
+    }
+
+    /// Implements `Reflect::reflect_eq` if the type implements `PartialEq`, generic types are
+    /// compared field by field with `Reflect::reflect_eq`. See [`Self::clone_box_impl`].
+    pub fn reflect_eq_impl(&self) -> TokenStream2 {
+        if self.no_eq {
+            return quote!();
+        }
+
+        if self.has_type_params() {
+            let Some(shapes) = self.reflected_shapes() else {
+                return quote!();
+            };
+            let arms = shapes.iter().map(|(path, fields)| {
+                let these = bindings(fields, "this");
+                let others = bindings(fields, "other");
+                let this_pattern = compose(path, fields, &these);
+                let other_pattern = compose(path, fields, &others);
+                let equal = if these.is_empty() {
+                    quote!(Some(true))
+                } else {
+                    quote! {{
+                        let mut equal = Some(true);
+                        for (a, b) in [ #( (#these as &dyn Reflect, #others as &dyn Reflect) ),* ] {
+                            match a.reflect_eq(b) {
+                                Some(true) => (),
+                                not_equal => {
+                                    equal = not_equal;
+                                    break;
+                                }
+                            }
+                        }
+                        equal
+                    }}
+                };
+                quote!((#this_pattern, #other_pattern) => #equal,)
+            });
+
+            return quote! {
+                fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+                    let mut result = Some(false);
+                    other.as_any(&mut |any| {
+                        if let Some(other) = any.downcast_ref::<Self>() {
+                            result = match (self, other) {
+                                #( #arms )*
+                                _ => Some(false),
+                            };
+                        }
+                    });
+                    result
+                }
+            };
+        }
+
+        quote! {
+            fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+                struct Probe<'a, T>(&'a T);
+
+                trait EqProbe {
+                    fn probe_eq(&self, other: &dyn Reflect) -> Option<bool>;
+                }
+
+                impl<T: PartialEq + Reflect> EqProbe for Probe<'_, T> {
+                    fn probe_eq(&self, other: &dyn Reflect) -> Option<bool> {
+                        let mut equal = false;
+                        other.as_any(&mut |any| {
+                            equal = any.downcast_ref::<T>().map_or(false, |other| self.0 == other)
+                        });
+                        Some(equal)
+                    }
+                }
+
+                trait EqFallback {
+                    fn probe_eq(&self, other: &dyn Reflect) -> Option<bool>;
+                }
+
+                impl<T> EqFallback for &Probe<'_, T> {
+                    fn probe_eq(&self, other: &dyn Reflect) -> Option<bool> {
+                        None
+                    }
+                }
+
+                (&Probe(self)).probe_eq(other)
+            }
+        }
+    }
+
+    pub fn methods_impl(&self) -> TokenStream2 {
+        if !self.methods {
+            return quote!();
+        }
+
+        quote! {
+            fn methods_info(&self) -> &'static [MethodInfo] {
+                <Self as ReflectMethods>::methods()
+            }
+
+            fn call_method(
+                &mut self,
+                name: &str,
+                args: Vec<Box<dyn Reflect>>,
+            ) -> Result<Box<dyn Reflect>, CallError> {
+                ReflectMethods::call_method(self, name, args)
+            }
+
+            fn call_method_ref(
+                &self,
+                name: &str,
+                args: Vec<Box<dyn Reflect>>,
+            ) -> Result<Box<dyn Reflect>, CallError> {
+                ReflectMethods::call_method_ref(self, name, args)
+            }
+        }
+    }
+
+    pub fn as_enum_impl(&self) -> TokenStream2 {
+        if self.hide_all || !matches!(self.data, ast::Data::Enum(_)) {
+            return quote!();
+        }
+
+        quote! {
+            fn as_enum(&self, func: &mut dyn FnMut(Option<&dyn ReflectEnum>)) {
+                func(Some(self))
+            }
+
+            fn as_enum_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectEnum>)) {
+                func(Some(self))
+            }
+        }
+    }
+
     pub fn as_array_impl(&self) -> TokenStream2 {
         if !self.impl_as_array {
             return quote!();
@@ -114,6 +410,29 @@ impl TypeArgs {
     }
 }
 
+/// Names of variables that fields are bound to in a pattern: `prefix0`, `prefix1`, ...
+fn bindings(fields: &Fields, prefix: &str) -> Vec<TokenStream2> {
+    (0..fields.fields.len())
+        .map(|i| {
+            let ident = quote::format_ident!("{}{}", prefix, i);
+            quote!(#ident)
+        })
+        .collect()
+}
+
+/// `path { a: values[0], .. }`, `path(values[0], ..)` or `path`, depending on the style of the
+/// fields. Works both as a pattern and as an expression.
+fn compose(path: &TokenStream2, fields: &Fields, values: &[TokenStream2]) -> TokenStream2 {
+    match fields.style {
+        ast::Style::Struct => {
+            let idents = fields.iter().map(|f| f.ident.as_ref().unwrap());
+            quote!(#path { #( #idents: #values ),* })
+        }
+        ast::Style::Tuple => quote!(#path ( #( #values ),* )),
+        ast::Style::Unit => quote!(#path),
+    }
+}
+
 /// 字段
 #[derive(FromField, Clone, PartialEq)]
 #[darling(attributes(reflect), forward_attrs(doc))]
@@ -0,0 +1,246 @@
+//! `#[reflect_methods]` impl block attribute.
+
+use darling::FromAttributes;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::*;
+use syn::*;
+
+use crate::reflect::args;
+
+/// `#[reflect(method)]` or `#[reflect(method, name = "..")]` on a method inside of an impl block.
+#[derive(FromAttributes)]
+#[darling(attributes(reflect))]
+struct MethodArgs {
+    /// `#[reflect(method)]`
+    ///
+    /// Exposes the method to `Reflect::call_method`.
+    #[darling(default)]
+    method: bool,
+
+    /// `#[reflect(name = "..")]`
+    ///
+    /// Method name override (default: name of the method).
+    #[darling(default)]
+    name: Option<String>,
+}
+
+/// A method marked with `#[reflect(method)]`.
+struct Method {
+    ident: Ident,
+    name: String,
+    doc: String,
+    /// `None` for associated functions, `Some(true)` for `&mut self`.
+    receiver: Option<bool>,
+    params: Vec<(String, Type)>,
+    return_type: String,
+}
+
+pub fn impl_reflect_methods(mut item: ItemImpl) -> TokenStream2 {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Error::new_spanned(path, "`#[reflect_methods]` can't be used on trait impls")
+            .to_compile_error();
+    }
+
+    let mut methods = Vec::new();
+    let mut errors = TokenStream2::new();
+
+    for impl_item in item.items.iter_mut() {
+        let ImplItem::Fn(f) = impl_item else {
+            continue;
+        };
+
+        let method_args = match MethodArgs::from_attributes(&f.attrs) {
+            Ok(method_args) => method_args,
+            Err(err) => {
+                errors.extend(err.write_errors());
+                continue;
+            }
+        };
+        f.attrs.retain(|attr| !attr.path().is_ident("reflect"));
+
+        if !method_args.method {
+            continue;
+        }
+
+        match self::parse_method(f, method_args) {
+            Ok(method) => methods.push(method),
+            Err(err) => errors.extend(err.to_compile_error()),
+        }
+    }
+
+    let ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+
+    let infos = methods.iter().map(|m| {
+        let name = &m.name;
+        let doc = &m.doc;
+        let receiver = match m.receiver {
+            None => quote!(MethodReceiver::None),
+            Some(false) => quote!(MethodReceiver::Ref),
+            Some(true) => quote!(MethodReceiver::Mut),
+        };
+        let params = m.params.iter().map(|(name, ty)| {
+            let type_name = self::type_name(ty);
+            quote! {
+                ParamInfo {
+                    name: #name,
+                    type_name: #type_name,
+                }
+            }
+        });
+        let return_type = &m.return_type;
+
+        quote! {
+            MethodInfo {
+                name: #name,
+                doc: #doc,
+                receiver: #receiver,
+                params: &[#(#params),*],
+                return_type: #return_type,
+            }
+        }
+    });
+
+    let calls = methods.iter().map(|m| self::gen_call(m, true));
+    let ref_calls = methods.iter().map(|m| self::gen_call(m, false));
+
+    quote! {
+        #item
+
+        #errors
+
+        #[allow(warnings)]
+        impl #impl_generics ReflectMethods for #ty #where_clause {
+            fn methods() -> &'static [MethodInfo] {
+                &[#(#infos),*]
+            }
+
+            fn call_method(
+                &mut self,
+                name: &str,
+                args: Vec<Box<dyn Reflect>>,
+            ) -> Result<Box<dyn Reflect>, CallError> {
+                match name {
+                    #(#calls)*
+                    _ => Err(CallError::NoSuchMethod { name: name.to_string() }),
+                }
+            }
+
+            fn call_method_ref(
+                &self,
+                name: &str,
+                args: Vec<Box<dyn Reflect>>,
+            ) -> Result<Box<dyn Reflect>, CallError> {
+                match name {
+                    #(#ref_calls)*
+                    _ => Err(CallError::NoSuchMethod { name: name.to_string() }),
+                }
+            }
+        }
+    }
+}
+
+fn parse_method(f: &ImplItemFn, method_args: MethodArgs) -> Result<Method> {
+    let sig = &f.sig;
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &sig.generics,
+            "reflected methods can't be generic",
+        ));
+    }
+
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new_spanned(
+            asyncness,
+            "reflected methods can't be async",
+        ));
+    }
+
+    let receiver = match sig.receiver() {
+        None => None,
+        Some(receiver) if receiver.reference.is_some() => Some(receiver.mutability.is_some()),
+        Some(receiver) => {
+            return Err(Error::new_spanned(
+                receiver,
+                "reflected methods must take `self` by reference",
+            ))
+        }
+    };
+
+    let mut params = Vec::new();
+    for input in sig.inputs.iter() {
+        let FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+
+        if let Type::Reference(_) = &*pat_type.ty {
+            return Err(Error::new_spanned(
+                &pat_type.ty,
+                "reflected methods must take arguments by value",
+            ));
+        }
+
+        let name = match &*pat_type.pat {
+            Pat::Ident(pat) => pat.ident.to_string(),
+            _ => format!("arg{}", params.len()),
+        };
+        params.push((name, (*pat_type.ty).clone()));
+    }
+
+    let return_type = match &sig.output {
+        ReturnType::Default => "()".to_string(),
+        ReturnType::Type(_, ty) => self::type_name(ty),
+    };
+
+    Ok(Method {
+        ident: sig.ident.clone(),
+        name: method_args.name.unwrap_or_else(|| sig.ident.to_string()),
+        doc: args::fetch_doc_comment(&f.attrs),
+        receiver,
+        params,
+        return_type,
+    })
+}
+
+/// Generates a match arm that calls the method. Methods that take `&mut self` can't be called
+/// from `call_method_ref` (`mutable` is `false`).
+fn gen_call(m: &Method, mutable: bool) -> TokenStream2 {
+    let name = &m.name;
+
+    if m.receiver == Some(true) && !mutable {
+        return quote! {
+            #name => Err(CallError::MutableReceiver { method: #name }),
+        };
+    }
+
+    let ident = &m.ident;
+    let count = m.params.len();
+    let arg_idents = (0..count)
+        .map(|i| format_ident!("arg{}", i))
+        .collect::<Vec<_>>();
+    let arg_types = m.params.iter().map(|(_, ty)| ty);
+
+    let call = match m.receiver {
+        None => quote!(Self::#ident(#(#arg_idents),*)),
+        Some(_) => quote!(self.#ident(#(#arg_idents),*)),
+    };
+
+    quote! {
+        #name => {
+            let mut args = MethodArgs::new(#name, #count, args)?;
+            #(
+                let #arg_idents = args.take_next::<#arg_types>()?;
+            )*
+            Ok(Box::new(#call))
+        }
+    }
+}
+
+/// Returns a type as written in the source code, without spaces inserted by the tokenizer.
+fn type_name(ty: &Type) -> String {
+    ty.to_token_stream()
+        .to_string()
+        .replace(' ', "")
+        .replace(',', ", ")
+}
@@ -1,10 +1,11 @@
-mod type_traits;
-mod reflect;
-mod reflect_context;
+pub mod type_traits;
+pub mod type_registry;
+pub mod reflect;
+pub mod reflect_context;
 mod sstorage;
-mod memory;
+pub mod memory;
 
-mod variable;
+pub mod variable;
 
 #[macro_use]
 extern crate memoffset;
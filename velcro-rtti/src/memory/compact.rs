@@ -0,0 +1,342 @@
+//! Compaction of an [`Allocator`].
+//!
+//! After many objects were spawned and freed, live objects of an allocator end up scattered among
+//! free records. [`Allocator::compact`] moves live objects into the free records with the lowest
+//! indices, so they occupy a dense prefix of the allocator again, and returns a [`HandleMap`] that
+//! maps old handles of moved objects to the new ones. Objects that store handles can be fixed up
+//! with [`HandleMap::remap_handles`], which finds every [`Handle`] and [`ErasedHandle`] inside of
+//! an object using reflection. [`Allocator::compact_and_remap`] does it for the objects of the
+//! allocator itself, which is enough for self-referencing structures such as scene graphs.
+//!
+//! ```
+//! # use velcro_rtti::{memory::{Allocator, Handle}, reflect::prelude::*};
+//! #[derive(Reflect, Debug, Default)]
+//! struct Node {
+//!     parent: Handle<Node>,
+//! }
+//!
+//! let mut allocator = Allocator::<Node>::new();
+//! let garbage = allocator.spawn(Node::default());
+//! let parent = allocator.spawn(Node::default());
+//! let child = allocator.spawn(Node { parent });
+//! allocator.free(garbage);
+//!
+//! let map = allocator.compact_and_remap();
+//! let child = map.get(child);
+//! assert_eq!(child.index(), 1);
+//! assert_eq!(allocator.borrow(child).parent, map.get(parent));
+//! ```
+
+use crate::{
+    memory::{Allocator, ErasedHandle, Handle, MemoryBlockContainer},
+    reflect::prelude::Reflect,
+};
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    fmt::{self, Debug, Formatter},
+};
+
+/// A table of handles of objects moved by [`Allocator::compact`], see the [module docs](self).
+pub struct HandleMap<T> {
+    map: HashMap<Handle<T>, Handle<T>>,
+}
+
+impl<T> Default for HandleMap<T> {
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+        }
+    }
+}
+
+impl<T> Clone for HandleMap<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T> Debug for HandleMap<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.map.iter()).finish()
+    }
+}
+
+impl<T> HandleMap<T> {
+    /// Returns the new handle of an object, or the same handle if the object wasn't moved.
+    #[inline]
+    pub fn get(&self, handle: Handle<T>) -> Handle<T> {
+        self.map.get(&handle).copied().unwrap_or(handle)
+    }
+
+    /// Replaces the handle with the new one. Returns `true` if the handle has changed.
+    #[inline]
+    pub fn remap(&self, handle: &mut Handle<T>) -> bool {
+        match self.map.get(handle) {
+            Some(new) => {
+                *handle = *new;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Same as [`Self::remap`], but for type-erased handles. An erased handle doesn't know the
+    /// allocator it belongs to, so it is replaced if it matches any of the moved objects.
+    #[inline]
+    pub fn remap_erased(&self, handle: &mut ErasedHandle) -> bool {
+        let mut typed = Handle::<T>::from(*handle);
+        let remapped = self.remap(&mut typed);
+        *handle = typed.into();
+        remapped
+    }
+
+    /// Returns the number of moved objects.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Creates an iterator over pairs of old and new handles of the moved objects.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, Handle<T>)> + '_ {
+        self.map.iter().map(|(old, new)| (*old, *new))
+    }
+
+    fn insert(&mut self, old: Handle<T>, new: Handle<T>) {
+        self.map.insert(old, new);
+    }
+}
+
+impl<T: Reflect> HandleMap<T> {
+    /// Replaces every [`Handle<T>`] and [`ErasedHandle`] inside of the object with new handles.
+    /// Fields, items of arrays and lists, and values of hash maps are visited recursively. Returns
+    /// the number of replaced handles.
+    pub fn remap_handles(&self, object: &mut dyn Reflect) -> usize {
+        let mut count = 0;
+        self::visit_handles(object, &mut |any| {
+            if let Some(handle) = any.downcast_mut::<Handle<T>>() {
+                count += self.remap(handle) as usize;
+                true
+            } else if let Some(handle) = any.downcast_mut::<ErasedHandle>() {
+                count += self.remap_erased(handle) as usize;
+                true
+            } else {
+                false
+            }
+        });
+        count
+    }
+}
+
+/// Calls the function for every value in the object, the function returns `true` if the value
+/// mustn't be visited deeper.
+fn visit_handles(object: &mut dyn Reflect, func: &mut dyn FnMut(&mut dyn Any) -> bool) {
+    let mut done = false;
+    object.as_any_mut(&mut |any| done = func(any));
+    if done {
+        return;
+    }
+
+    object.as_array_mut(&mut |array| {
+        if let Some(array) = array {
+            for i in 0..array.reflect_len() {
+                if let Some(item) = array.reflect_index_mut(i) {
+                    self::visit_handles(item, func);
+                }
+            }
+            done = true;
+        }
+    });
+    if done {
+        return;
+    }
+
+    object.as_hash_map_mut(&mut |map| {
+        if let Some(map) = map {
+            for i in 0..map.reflect_len() {
+                if let Some(value) = map.reflect_get_nth_value_mut(i) {
+                    self::visit_handles(value, func);
+                }
+            }
+            done = true;
+        }
+    });
+    if done {
+        return;
+    }
+
+    object.fields_mut(&mut |fields| {
+        for field in fields.iter_mut() {
+            self::visit_handles(*field, func);
+        }
+    });
+}
+
+impl<T, M> Allocator<T, M>
+where
+    M: MemoryBlockContainer<Element = T> + 'static,
+{
+    /// Moves live objects into the free records with the lowest indices, so they occupy a dense
+    /// prefix of the allocator, see the [module docs](self). Relative order of the objects is
+    /// preserved, objects taken with [`Allocator::take_reserve`] are never moved. Returns the
+    /// table of new handles of the moved objects, all other handles stay valid.
+    ///
+    /// Records are not deallocated, so handles to objects that were freed before the compaction
+    /// stay invalid. Moved objects get a new generation for the same reason.
+    pub fn compact(&mut self) -> HandleMap<T> {
+        let mut free = vec![false; self.records.len()];
+        for &index in self.free_stack.iter() {
+            free[index as usize] = true;
+        }
+
+        let mut map = HandleMap::default();
+        // Always sorted, the lowest index is at the front.
+        let mut vacant = VecDeque::new();
+        for (index, &is_free) in free.iter().enumerate() {
+            if is_free {
+                vacant.push_back(index);
+                continue;
+            }
+
+            let record = &mut self.records[index];
+            // Empty records that aren't free are reserved by a ticket.
+            if !record.block.is_some() {
+                continue;
+            }
+
+            if let Some(target) = vacant.pop_front() {
+                let old = Handle::new(index as u32, record.generation);
+                let payload = record.block.take().expect("record must have an object");
+                #[cfg(feature = "alloc-tracking")]
+                let site = record.site.take();
+
+                let target_record = &mut self.records[target];
+                let generation = target_record.generation + 1;
+                target_record.generation = generation;
+                target_record.block.replace(payload);
+                #[cfg(feature = "alloc-tracking")]
+                {
+                    target_record.site = site;
+                }
+
+                map.insert(old, Handle::new(target as u32, generation));
+                vacant.push_back(index);
+            }
+        }
+
+        // Spawn takes free records from the end of the stack, lowest indices go first.
+        self.free_stack = vacant.into_iter().rev().map(|index| index as u32).collect();
+
+        map
+    }
+}
+
+impl<T, M> Allocator<T, M>
+where
+    T: Reflect,
+    M: MemoryBlockContainer<Element = T> + 'static,
+{
+    /// Compacts the allocator (see [`Self::compact`]) and replaces handles to the moved objects
+    /// in all objects of the allocator using [`HandleMap::remap_handles`].
+    pub fn compact_and_remap(&mut self) -> HandleMap<T> {
+        let map = self.compact();
+        if !map.is_empty() {
+            for object in self.iter_mut() {
+                map.remap_handles(object);
+            }
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{reflect::prelude::*, variable::InheritableVariable};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Reflect, Debug, Default)]
+    struct Node {
+        name: String,
+        parent: Handle<Node>,
+        children: Vec<Handle<Node>>,
+        target: Option<ErasedHandle>,
+        tagged: HashMap<String, Handle<Node>>,
+        owner: InheritableVariable<Handle<Node>>,
+    }
+
+    #[test]
+    fn compact_moves_objects_and_remaps_handles() {
+        let mut allocator = Allocator::<Node>::new();
+        let garbage = (0..3)
+            .map(|_| allocator.spawn(Node::default()))
+            .collect::<Vec<_>>();
+        let root = allocator.spawn(Node {
+            name: "root".to_string(),
+            ..Default::default()
+        });
+        let reserved = allocator.spawn(Node::default());
+        let child = allocator.spawn(Node {
+            name: "child".to_string(),
+            parent: root,
+            target: Some(root.into()),
+            ..Default::default()
+        });
+        {
+            let root = allocator.borrow_mut(root);
+            root.children.push(child);
+            root.tagged.insert("child".to_string(), child);
+        }
+        let changes = Arc::new(AtomicUsize::new(0));
+        {
+            let child = allocator.borrow_mut(child);
+            child.owner = InheritableVariable::new_non_modified(root);
+            let changes = changes.clone();
+            child.owner.subscribe(move |_| {
+                changes.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        for handle in garbage.iter() {
+            allocator.free(*handle);
+        }
+        let (ticket, reserved_node) = allocator.take_reserve(reserved);
+
+        let map = allocator.compact_and_remap();
+        assert_eq!(map.len(), 2);
+
+        let new_root = map.get(root);
+        let new_child = map.get(child);
+        assert_eq!((new_root.index(), new_child.index()), (0, 1));
+        assert!(!allocator.is_valid_handle(root));
+        assert!(!allocator.is_valid_handle(child));
+        assert!(garbage.iter().all(|h| !allocator.is_valid_handle(*h)));
+
+        assert_eq!(allocator.borrow(new_root).name, "root");
+        assert_eq!(allocator.borrow(new_root).children, vec![new_child]);
+        assert_eq!(allocator.borrow(new_root).tagged["child"], new_child);
+        assert_eq!(allocator.borrow(new_child).parent, new_root);
+        assert_eq!(allocator.borrow(new_child).target, Some(new_root.into()));
+
+        // Remapping isn't an edit, flags of variables stay untouched.
+        let owner = &allocator.borrow(new_child).owner;
+        assert_eq!(**owner, new_root);
+        assert!(!owner.is_modified() && !owner.need_sync());
+        assert_eq!(changes.load(Ordering::Relaxed), 0);
+
+        // The reserved record stays in place, new objects fill the lowest free records.
+        assert_eq!(allocator.put_back(ticket, reserved_node), reserved);
+        assert_eq!(allocator.spawn(Node::default()).index(), 2);
+        assert_eq!(allocator.compact().get(reserved).index(), 3);
+    }
+}
@@ -0,0 +1,571 @@
+//! Thread-safe allocator.
+//!
+//! [`ConcurrentAllocator`] allows to spawn, free and borrow objects from many threads at once
+//! through a shared reference. Records are stored in segments that are never moved or shrunk, so
+//! a borrowed object stays in place while other threads spawn new ones. Free records are kept in a
+//! lock-free stack, and every record has its own atomic state that works as a tiny read-write
+//! lock: an object can be borrowed either mutably once or immutably any number of times, and it
+//! can't be freed while it is borrowed. Handles are checked for generation the same way as in
+//! [`Allocator`](super::Allocator).
+//!
+//! ```
+//! use velcro_rtti::memory::ConcurrentAllocator;
+//!
+//! let allocator = ConcurrentAllocator::<u32>::new();
+//! let handles = std::thread::scope(|scope| {
+//!     let allocator = &allocator;
+//!     let workers = (0..4)
+//!         .map(|i| scope.spawn(move || allocator.spawn(i)))
+//!         .collect::<Vec<_>>();
+//!     workers.into_iter().map(|w| w.join().unwrap()).collect::<Vec<_>>()
+//! });
+//!
+//! assert_eq!(allocator.alive_count(), 4);
+//! *allocator.borrow_mut(handles[0]) = 10;
+//! assert_eq!(*allocator.borrow(handles[0]), 10);
+//!
+//! let reader = allocator.borrow(handles[1]);
+//! assert!(allocator.try_free(handles[1]).is_none());
+//! let value = *reader;
+//! drop(reader);
+//! assert_eq!(allocator.try_free(handles[1]), Some(value));
+//! assert!(allocator.try_borrow(handles[1]).is_none());
+//! ```
+
+use crate::memory::{Handle, INVALID_GENERATION};
+use std::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{self, AtomicPtr, AtomicU32, AtomicU64},
+};
+
+/// The first segment has `2^FIRST_SEGMENT_BITS` records, each next one is twice as large.
+const FIRST_SEGMENT_BITS: u32 = 6;
+/// Enough segments to address almost the whole `u32` index range.
+const SEGMENT_COUNT: usize = 26;
+/// Total amount of records in all segments.
+const CAPACITY: u64 = ((1 << SEGMENT_COUNT) - 1) << FIRST_SEGMENT_BITS;
+
+/// The record has an object.
+const OCCUPIED: u32 = 1 << 31;
+/// The object is borrowed mutably, or the record is being freed.
+const WRITER: u32 = 1 << 30;
+/// Mask of the amount of shared borrows.
+const READERS: u32 = WRITER - 1;
+
+/// End of the free list.
+const NIL: u32 = u32::MAX;
+
+struct Slot<T> {
+    state: AtomicU32,
+    generation: AtomicU32,
+    next_free: AtomicU32,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    fn new() -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            generation: AtomicU32::new(INVALID_GENERATION),
+            next_free: AtomicU32::new(NIL),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+/// Returns segment and offset in the segment of a record.
+fn location(index: u32) -> (usize, usize) {
+    let position = index as u64 + (1 << FIRST_SEGMENT_BITS);
+    let segment = 63 - position.leading_zeros() - FIRST_SEGMENT_BITS;
+    let offset = position - (1 << (segment + FIRST_SEGMENT_BITS));
+    (segment as usize, offset as usize)
+}
+
+fn segment_len(segment: usize) -> usize {
+    1 << (segment + FIRST_SEGMENT_BITS as usize)
+}
+
+/// Pool that can be shared between threads, see the [module docs](self).
+pub struct ConcurrentAllocator<T> {
+    segments: [AtomicPtr<Slot<T>>; SEGMENT_COUNT],
+    /// Index of the top free record in the low half, ABA counter in the high half.
+    free_head: AtomicU64,
+    /// Amount of records ever used.
+    next_index: AtomicU32,
+    alive: AtomicU32,
+}
+
+// SAFETY: Objects are moved in and out of the allocator by any thread, and shared references to
+// them can be obtained from any thread, so `T` must be both `Send` and `Sync` to share the
+// allocator.
+unsafe impl<T: Send> Send for ConcurrentAllocator<T> {}
+unsafe impl<T: Send + Sync> Sync for ConcurrentAllocator<T> {}
+
+impl<T> Default for ConcurrentAllocator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for ConcurrentAllocator<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrentAllocator")
+            .field("alive", &self.alive_count())
+            .finish()
+    }
+}
+
+impl<T> ConcurrentAllocator<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            segments: std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            free_head: AtomicU64::new(NIL as u64),
+            next_index: AtomicU32::new(0),
+            alive: AtomicU32::new(0),
+        }
+    }
+
+    /// Returns a record that was already used at least once.
+    fn slot(&self, index: u32) -> Option<&Slot<T>> {
+        if index >= self.next_index.load(atomic::Ordering::Acquire) {
+            return None;
+        }
+
+        let (segment, offset) = location(index);
+        let segment = self.segments.get(segment)?.load(atomic::Ordering::Acquire);
+        if segment.is_null() {
+            // The record is being created by another thread right now.
+            return None;
+        }
+
+        // SAFETY: The offset is within the segment, segments live as long as the allocator.
+        Some(unsafe { &*segment.add(offset) })
+    }
+
+    /// Returns a record allocating its segment if needed.
+    fn slot_or_insert(&self, index: u32) -> &Slot<T> {
+        let (segment, offset) = location(index);
+        let cell = &self.segments[segment];
+
+        let mut ptr = cell.load(atomic::Ordering::Acquire);
+        if ptr.is_null() {
+            let new = Box::into_raw(
+                (0..segment_len(segment))
+                    .map(|_| Slot::new())
+                    .collect::<Box<[Slot<T>]>>(),
+            ) as *mut Slot<T>;
+
+            match cell.compare_exchange(
+                ptr::null_mut(),
+                new,
+                atomic::Ordering::AcqRel,
+                atomic::Ordering::Acquire,
+            ) {
+                Ok(_) => ptr = new,
+                Err(existing) => {
+                    // Another thread was faster.
+                    // SAFETY: The segment was never shared.
+                    drop(unsafe {
+                        Box::from_raw(ptr::slice_from_raw_parts_mut(new, segment_len(segment)))
+                    });
+                    ptr = existing;
+                }
+            }
+        }
+
+        // SAFETY: The offset is within the segment, segments live as long as the allocator.
+        unsafe { &*ptr.add(offset) }
+    }
+
+    fn pop_free(&self) -> Option<u32> {
+        let mut head = self.free_head.load(atomic::Ordering::Acquire);
+        loop {
+            let index = head as u32;
+            if index == NIL {
+                return None;
+            }
+
+            let next = self
+                .slot(index)
+                .expect("free records must exist")
+                .next_free
+                .load(atomic::Ordering::Relaxed);
+            let new_head = ((head >> 32).wrapping_add(1) << 32) | next as u64;
+
+            match self.free_head.compare_exchange_weak(
+                head,
+                new_head,
+                atomic::Ordering::Acquire,
+                atomic::Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(index),
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn push_free(&self, index: u32, slot: &Slot<T>) {
+        let mut head = self.free_head.load(atomic::Ordering::Relaxed);
+        loop {
+            slot.next_free.store(head as u32, atomic::Ordering::Relaxed);
+            let new_head = ((head >> 32).wrapping_add(1) << 32) | index as u64;
+
+            match self.free_head.compare_exchange_weak(
+                head,
+                new_head,
+                atomic::Ordering::Release,
+                atomic::Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// Puts an object in a free record, or in a new one if there are no free records.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all `u32` indices are in use.
+    #[inline]
+    pub fn spawn(&self, payload: T) -> Handle<T> {
+        self.spawn_with(|_| payload)
+    }
+
+    /// Same as [`Self::spawn`], but the object is created by the callback that receives the handle
+    /// of the object. The callback must not access the record of the object through the allocator.
+    pub fn spawn_with<F: FnOnce(Handle<T>) -> T>(&self, callback: F) -> Handle<T> {
+        let (index, slot) = match self.pop_free() {
+            Some(index) => (index, self.slot(index).expect("free records must exist")),
+            None => {
+                let index = self.next_index.fetch_add(1, atomic::Ordering::AcqRel);
+                if index as u64 >= CAPACITY {
+                    panic!("ConcurrentAllocator is out of indices!");
+                }
+                (index, self.slot_or_insert(index))
+            }
+        };
+
+        let mut generation = slot
+            .generation
+            .load(atomic::Ordering::Relaxed)
+            .wrapping_add(1);
+        if generation == INVALID_GENERATION {
+            generation += 1;
+        }
+
+        let handle = Handle::new(index, generation);
+        let payload = callback(handle);
+
+        // SAFETY: The record is empty and was taken from the free list (or is new), so no other
+        // thread can access its object.
+        unsafe { (*slot.value.get()).write(payload) };
+        slot.generation.store(generation, atomic::Ordering::Relaxed);
+        slot.state.store(OCCUPIED, atomic::Ordering::Release);
+        self.alive.fetch_add(1, atomic::Ordering::Relaxed);
+
+        handle
+    }
+
+    /// Locks the record with the `lock` state if it has an object that the handle points to and is
+    /// not borrowed.
+    fn lock(&self, handle: Handle<T>, lock: u32) -> Option<&Slot<T>> {
+        let slot = self.slot(handle.index())?;
+        slot.state
+            .compare_exchange(
+                OCCUPIED,
+                lock,
+                atomic::Ordering::Acquire,
+                atomic::Ordering::Relaxed,
+            )
+            .ok()?;
+
+        if slot.generation.load(atomic::Ordering::Relaxed) != handle.generation() {
+            slot.state.store(OCCUPIED, atomic::Ordering::Release);
+            return None;
+        }
+
+        Some(slot)
+    }
+
+    /// Borrows shared reference to an object. Returns `None` if the handle is invalid or the
+    /// object is borrowed mutably at the moment.
+    pub fn try_borrow(&self, handle: Handle<T>) -> Option<ConcurrentRef<'_, T>> {
+        let slot = self.slot(handle.index())?;
+        slot.state
+            .fetch_update(
+                atomic::Ordering::Acquire,
+                atomic::Ordering::Relaxed,
+                |state| {
+                    (state & OCCUPIED != 0 && state & WRITER == 0 && state & READERS != READERS)
+                        .then_some(state + 1)
+                },
+            )
+            .ok()?;
+
+        if slot.generation.load(atomic::Ordering::Relaxed) != handle.generation() {
+            slot.state.fetch_sub(1, atomic::Ordering::Release);
+            return None;
+        }
+
+        Some(ConcurrentRef { slot })
+    }
+
+    /// Borrows shared reference to an object.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases when [`Self::try_borrow`] returns `None`.
+    #[inline]
+    pub fn borrow(&self, handle: Handle<T>) -> ConcurrentRef<'_, T> {
+        self.try_borrow(handle).unwrap_or_else(|| {
+            panic!(
+                "Attempt to borrow invalid or mutably borrowed object at handle {:?}!",
+                handle
+            )
+        })
+    }
+
+    /// Borrows mutable reference to an object. Returns `None` if the handle is invalid or the
+    /// object is borrowed at the moment.
+    pub fn try_borrow_mut(&self, handle: Handle<T>) -> Option<ConcurrentRefMut<'_, T>> {
+        self.lock(handle, OCCUPIED | WRITER)
+            .map(|slot| ConcurrentRefMut { slot })
+    }
+
+    /// Borrows mutable reference to an object.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases when [`Self::try_borrow_mut`] returns `None`.
+    #[inline]
+    pub fn borrow_mut(&self, handle: Handle<T>) -> ConcurrentRefMut<'_, T> {
+        self.try_borrow_mut(handle).unwrap_or_else(|| {
+            panic!(
+                "Attempt to mutably borrow invalid or borrowed object at handle {:?}!",
+                handle
+            )
+        })
+    }
+
+    /// Destroys object by given handle and returns it. Returns `None` if the handle is invalid or
+    /// the object is borrowed at the moment.
+    pub fn try_free(&self, handle: Handle<T>) -> Option<T> {
+        let slot = self.lock(handle, WRITER)?;
+
+        // SAFETY: The record is locked, and its object is initialized.
+        let payload = unsafe { (*slot.value.get()).assume_init_read() };
+        slot.state.store(0, atomic::Ordering::Release);
+        self.alive.fetch_sub(1, atomic::Ordering::Relaxed);
+        self.push_free(handle.index(), slot);
+
+        Some(payload)
+    }
+
+    /// Destroys object by given handle and returns it. All handles to the object will become
+    /// invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases when [`Self::try_free`] returns `None`.
+    #[inline]
+    pub fn free(&self, handle: Handle<T>) -> T {
+        self.try_free(handle).unwrap_or_else(|| {
+            panic!(
+                "Attempt to free invalid or borrowed object at handle {:?}!",
+                handle
+            )
+        })
+    }
+
+    /// Checks if given handle "points" to some object. The result may be outdated immediately if
+    /// other threads free objects.
+    #[inline]
+    pub fn is_valid_handle(&self, handle: Handle<T>) -> bool {
+        self.slot(handle.index()).is_some_and(|slot| {
+            slot.state.load(atomic::Ordering::Acquire) & OCCUPIED != 0
+                && slot.generation.load(atomic::Ordering::Relaxed) == handle.generation()
+        })
+    }
+
+    /// Returns the number of "alive" objects in the pool. This method is `O(1)`.
+    #[inline]
+    pub fn alive_count(&self) -> u32 {
+        self.alive.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Creates new iterator over all objects and their handles. Requires exclusive access, so
+    /// it can be used between frames when no other thread uses the allocator.
+    pub fn pair_iter_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        let count = (*self.next_index.get_mut() as u64).min(CAPACITY) as u32;
+        let this = &*self;
+        (0..count).filter_map(move |index| {
+            let slot = this.slot(index)?;
+            if slot.state.load(atomic::Ordering::Relaxed) & OCCUPIED == 0 {
+                return None;
+            }
+
+            let handle = Handle::new(index, slot.generation.load(atomic::Ordering::Relaxed));
+            // SAFETY: The allocator is borrowed mutably, so nothing else can access the object,
+            // and every record is visited once.
+            Some((handle, unsafe { (*slot.value.get()).assume_init_mut() }))
+        })
+    }
+}
+
+impl<T> Drop for ConcurrentAllocator<T> {
+    fn drop(&mut self) {
+        let count = (*self.next_index.get_mut() as u64).min(CAPACITY) as u32;
+        for index in 0..count {
+            if let Some(slot) = self.slot(index) {
+                if slot.state.load(atomic::Ordering::Relaxed) & OCCUPIED != 0 {
+                    // SAFETY: The record has an initialized object, nothing can borrow it anymore.
+                    unsafe { (*slot.value.get()).assume_init_drop() };
+                }
+            }
+        }
+
+        for (segment, ptr) in self.segments.iter_mut().enumerate() {
+            let ptr = *ptr.get_mut();
+            if !ptr.is_null() {
+                // SAFETY: The segment was created from a boxed slice of the same length.
+                drop(unsafe {
+                    Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, segment_len(segment)))
+                });
+            }
+        }
+    }
+}
+
+/// A shared reference to an object borrowed from a [`ConcurrentAllocator`].
+pub struct ConcurrentRef<'a, T> {
+    slot: &'a Slot<T>,
+}
+
+impl<T: Debug> Debug for ConcurrentRef<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Deref for ConcurrentRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The record is borrowed immutably, so the object can't be freed or changed.
+        unsafe { (*self.slot.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Drop for ConcurrentRef<'_, T> {
+    fn drop(&mut self) {
+        self.slot.state.fetch_sub(1, atomic::Ordering::Release);
+    }
+}
+
+/// A mutable reference to an object borrowed from a [`ConcurrentAllocator`].
+pub struct ConcurrentRefMut<'a, T> {
+    slot: &'a Slot<T>,
+}
+
+impl<T: Debug> Debug for ConcurrentRefMut<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Deref for ConcurrentRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The record is borrowed mutably by this reference only.
+        unsafe { (*self.slot.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T> DerefMut for ConcurrentRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The record is borrowed mutably by this reference only.
+        unsafe { (*self.slot.value.get()).assume_init_mut() }
+    }
+}
+
+impl<T> Drop for ConcurrentRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.slot.state.store(OCCUPIED, atomic::Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashSet, thread};
+
+    #[test]
+    fn concurrent_spawn_free_and_borrow() {
+        let allocator = ConcurrentAllocator::<String>::new();
+
+        let kept = thread::scope(|scope| {
+            let workers = (0..8)
+                .map(|worker| {
+                    let allocator = &allocator;
+                    scope.spawn(move || {
+                        let mut kept = Vec::new();
+                        for i in 0..1000 {
+                            let handle = allocator.spawn(format!("{worker}:{i}"));
+                            assert_eq!(*allocator.borrow(handle), format!("{worker}:{i}"));
+                            if i % 2 == 0 {
+                                assert_eq!(allocator.free(handle), format!("{worker}:{i}"));
+                                assert!(allocator.try_borrow(handle).is_none());
+                            } else {
+                                kept.push((handle, format!("{worker}:{i}")));
+                            }
+                        }
+                        kept
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(allocator.alive_count(), 4000);
+        let unique = kept.iter().map(|(h, _)| h.index()).collect::<HashSet<_>>();
+        assert_eq!(unique.len(), kept.len());
+
+        thread::scope(|scope| {
+            for chunk in kept.chunks(1000) {
+                let allocator = &allocator;
+                scope.spawn(move || {
+                    for (handle, value) in chunk {
+                        let first = allocator.borrow(*handle);
+                        let second = allocator.borrow(*handle);
+                        assert!(allocator.try_borrow_mut(*handle).is_none());
+                        assert_eq!(&*first, value);
+                        assert_eq!(&*second, value);
+                    }
+                });
+            }
+        });
+
+        // Freed records are reused with a new generation.
+        let (old, _) = kept[0];
+        allocator.free(old);
+        let new = allocator.spawn("new".to_string());
+        assert!(!allocator.is_valid_handle(old));
+        assert!(allocator.try_borrow_mut(old).is_none());
+        assert!(allocator.try_free(old).is_none());
+        assert_eq!(*allocator.borrow(new), "new");
+
+        let mut allocator = allocator;
+        assert_eq!(allocator.pair_iter_mut().count(), 4000);
+    }
+}
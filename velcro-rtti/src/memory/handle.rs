@@ -28,6 +28,13 @@ pub struct Handle<T> {
     pub(super) type_marker: PhantomData<T>,
 }
 
+impl<T> Clone for Handle<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
 impl<T> Copy for Handle<T> {}
 
 impl<T> Eq for Handle<T> {}
@@ -110,6 +117,17 @@ impl<T> Handle<T> {
     }
 }
 
+impl<T> Context for Handle<T> {
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut region = reflect_context.enter_region(name)?;
+
+        self.index.context("Index", &mut region)?;
+        self.generation.context("Generation", &mut region)?;
+
+        Ok(())
+    }
+}
+
 impl<T> TypeUuidProvider for Handle<T>
 where
     T: TypeUuidProvider,
@@ -228,15 +246,17 @@ impl Debug for AtomicHandle {
 
 /// Type-erased handle.
 #[derive(
-    Copy, Clone, Debug, Ord, PartialOrd, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize,
+    Copy, Clone, Debug, Ord, PartialOrd, PartialEq, Eq, Hash, Reflect, Context, Serialize, Deserialize,
 )]
 pub struct ErasedHandle {
     /// Index of object in pool.
     #[reflect(read_only)]
+    #[context(rename = "Index")]
     index: u32,
     /// Generation number, if it is same as generation of pool record at
     /// index of handle then this is valid handle.
     #[reflect(read_only)]
+    #[context(rename = "Generation")]
     generation: u32,
 }
 
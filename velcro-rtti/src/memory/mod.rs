@@ -8,12 +8,27 @@ use std::{
     sync::atomic::{self, AtomicIsize},
 };
 use crate::reflect::prelude::{Reflect, ReflectArray, ReflectList, ReflectHashMap, FieldInfo};
+use crate::reflect_context::prelude::*;
 
 mod memory_block;
 mod handle;
+mod multiborrow;
+mod concurrent;
+mod compact;
+mod shared;
+mod soa;
+#[cfg(feature = "alloc-tracking")]
+mod tracking;
 
 pub use handle::*;
 pub use memory_block::*;
+pub use multiborrow::*;
+pub use concurrent::*;
+pub use compact::*;
+pub use shared::*;
+pub use soa::*;
+#[cfg(feature = "alloc-tracking")]
+pub use tracking::*;
 
 const INVALID_GENERATION: u32 = 0;
 
@@ -168,6 +183,9 @@ where
     generation: u32,
     // Actual memory block.
     block: MemoryBlock<M>,
+    // Where the object was spawned.
+    #[cfg(feature = "alloc-tracking")]
+    site: Option<&'static std::panic::Location<'static>>,
 }
 
 impl<T, M> PartialEq for AllocatorRecord<T, M>
@@ -191,12 +209,68 @@ where
             refc: Default::default(),
             generation: INVALID_GENERATION,
             block: MemoryBlock::new_empty(),
+            #[cfg(feature = "alloc-tracking")]
+            site: None,
         }
     }
 }
 
 
 
+impl<T, M> Context for AllocatorRecord<T, M>
+where
+    T: 'static,
+    M: MemoryBlockContainer<Element = T> + Context + 'static,
+{
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut region = reflect_context.enter_region(name)?;
+
+        self.generation.context("Generation", &mut region)?;
+        self.block.get_mut().context("Block", &mut region)?;
+
+        Ok(())
+    }
+}
+
+/// Saves and loads every record together with its generation and the stack of free records, so
+/// handles stored anywhere (including inside of the objects of the allocator) stay valid after
+/// loading, and freed records are reused in the same order as before saving. Records reserved with
+/// a [`Ticket`] are saved as empty records and become free after loading. An allocator with
+/// outstanding tickets can't be loaded into, since the tickets would point to loaded records.
+impl<T, M> Context for Allocator<T, M>
+where
+    T: 'static,
+    M: MemoryBlockContainer<Element = T> + Context + 'static,
+{
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut region = reflect_context.enter_region(name)?;
+
+        if region.is_reading() && self.reserved_count() > 0 {
+            return Err(ContextError::User(
+                "unable to load into an allocator with reserved records".to_string(),
+            ));
+        }
+
+        #[cfg(feature = "alloc-tracking")]
+        let tracked = self.total_count() as usize;
+
+        self.records.context("Records", &mut region)?;
+        self.free_stack.context("FreeStack", &mut region)?;
+
+        if region.is_reading() {
+            self.validate_free_stack()?;
+
+            #[cfg(feature = "alloc-tracking")]
+            {
+                tracking::track_free::<T>(tracked);
+                tracking::track_spawn::<T>(self.total_count() as usize);
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl<T, M> Default for Allocator<T, M>
 where
     T: 'static,
@@ -208,6 +282,39 @@ where
     }
 }
 
+/// Reports objects that are still alive, see [`set_leak_handler`].
+#[cfg(feature = "alloc-tracking")]
+impl<T, M> Drop for Allocator<T, M>
+where
+    T: Sized,
+    M: MemoryBlockContainer<Element = T>,
+{
+    fn drop(&mut self) {
+        tracking::track_free::<T>(self.records.len() - self.free_stack.len());
+
+        let objects = self
+            .records
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.block.is_some())
+            .map(|(index, record)| LeakedObject {
+                index: index as u32,
+                generation: record.generation,
+                site: record.site,
+            })
+            .collect::<Vec<_>>();
+
+        if !objects.is_empty() {
+            tracking::report_leak(&LeakReport {
+                type_name: std::any::type_name::<T>(),
+                objects,
+            });
+        }
+    }
+}
+
+/// A reservation of an allocator record, returned by [`Allocator::take_reserve`]. It must be given
+/// back with [`Allocator::put_back`] or [`Allocator::forget_ticket`], dropping it panics.
 #[derive(Debug)]
 pub struct Ticket<T> {
     index:  u32,
@@ -231,6 +338,8 @@ impl<T: Clone> Clone for AllocatorRecord<T> {
             refc: Default::default(),
             generation: self.generation,
             block: self.block.clone(),
+            #[cfg(feature = "alloc-tracking")]
+            site: self.site,
         }
     }
 }
@@ -238,6 +347,9 @@ impl<T: Clone> Clone for AllocatorRecord<T> {
 impl<T: Clone> Clone for Allocator<T> {
     #[inline]
     fn clone(&self) -> Self {
+        #[cfg(feature = "alloc-tracking")]
+        tracking::track_spawn::<T>(self.records.len() - self.free_stack.len());
+
         Self {
             records: self.records.clone(),
             free_stack: self.free_stack.clone(),
@@ -281,11 +393,124 @@ where
     }
 
 
-     /// Forgets that value at ticket was reserved and makes it usable again.
+    /// Returns the number of records reserved with a [`Ticket`].
+    fn reserved_count(&self) -> usize {
+        let vacant = self
+            .records
+            .iter()
+            .filter(|record| !record.block.is_some())
+            .count();
+        vacant - self.free_stack.len()
+    }
+
+    /// Checks that the free stack references existing empty records, each at most once. Corrupted
+    /// data would otherwise cause a panic on the next spawn. Empty records missing from the stack
+    /// were reserved when the allocator was saved, nothing can put them back, so they are freed.
+    fn validate_free_stack(&mut self) -> ReflectResult {
+        let mut is_free = vec![false; self.records.len()];
+        for &index in self.free_stack.iter() {
+            let record = self.records_get(index).ok_or_else(|| {
+                ContextError::User(format!("free record index {} is out of bounds", index))
+            })?;
+            if record.block.is_some() {
+                return Err(ContextError::User(format!(
+                    "free record {} is occupied",
+                    index
+                )));
+            }
+            if std::mem::replace(&mut is_free[index as usize], true) {
+                return Err(ContextError::User(format!(
+                    "free record {} is listed twice",
+                    index
+                )));
+            }
+        }
+        for (index, record) in self.records.iter().enumerate() {
+            if !record.block.is_some() && !is_free[index] {
+                self.free_stack.push(index as u32);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves an object out of the allocator and reserves its record, so nothing else can be
+    /// spawned there. The object must be returned with [`put_back`](Self::put_back) using the
+    /// ticket, the handle stays valid after that. This is useful to call methods that need the
+    /// object and `&mut Allocator` at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is out of bounds, dangling or the record is empty (for example, the
+    /// object is already taken).
+    #[inline]
+    pub fn take_reserve(&mut self, handle: Handle<T>) -> (Ticket<T>, T) {
+        let record_count = self.records.len();
+        if let Some(record) = self.records_get_mut(handle.index) {
+            if record.generation == handle.generation {
+                if let Some(payload) = record.block.take() {
+                    let ticket = Ticket {
+                        index: handle.index,
+                        marker: PhantomData,
+                    };
+                    (ticket, payload)
+                } else {
+                    panic!(
+                        "Attempt to take already taken object at handle {:?}!",
+                        handle
+                    );
+                }
+            } else {
+                panic!(
+                    "Attempt to take object using dangling handle {:?}! Record generation is {}",
+                    handle, record.generation
+                );
+            }
+        } else {
+            panic!(
+                "Attempt to take object using out-of-bounds handle {:?}! Record count is {}",
+                handle, record_count
+            );
+        }
+    }
+
+    /// The same as [`take_reserve`](Self::take_reserve), but returns `None` instead of panicking.
+    #[inline]
+    pub fn try_take_reserve(&mut self, handle: Handle<T>) -> Option<(Ticket<T>, T)> {
+        let record = self.records_get_mut(handle.index)?;
+        if record.generation != handle.generation {
+            return None;
+        }
+
+        let payload = record.block.take()?;
+        let ticket = Ticket {
+            index: handle.index,
+            marker: PhantomData,
+        };
+        Some((ticket, payload))
+    }
+
+    /// Returns an object taken with [`take_reserve`](Self::take_reserve) to its record and returns
+    /// its handle, which is the same as the handle used to take the object.
+    #[inline]
+    pub fn put_back(&mut self, ticket: Ticket<T>, value: T) -> Handle<T> {
+        let record = self
+            .records_get_mut(ticket.index)
+            .expect("Ticket index was invalid");
+        let old = record.block.replace(value);
+        assert!(old.is_none(), "Record of a ticket was occupied!");
+        let handle = Handle::new(ticket.index, record.generation);
+        std::mem::forget(ticket);
+        handle
+    }
+
+    /// Forgets that value at ticket was reserved and makes it usable again.
     /// Useful when you don't need to put value back by ticket, but just make
     /// allocator record usable again.
     #[inline]
     pub fn forget_ticket(&mut self, ticket: Ticket<T>) {
+        #[cfg(feature = "alloc-tracking")]
+        tracking::track_free::<T>(1);
+
         self.free_stack.push(ticket.index);
         std::mem::forget(ticket);
     }
@@ -304,10 +529,35 @@ where
     /// Use this method cautiously if objects in pool have cross "references" (handles)
     /// to each other. This method will make all produced handles invalid and any further
     /// calls for [`borrow`](Self::borrow) or [`borrow_mut`](Self::borrow_mut) will raise panic.
+    ///
+    /// Records reserved with a [`Ticket`] are kept, so the tickets can still be given back.
     #[inline]
     pub fn clear(&mut self) {
-        self.records.clear();
+        #[cfg(feature = "alloc-tracking")]
+        tracking::track_free::<T>(self.alive_count() as usize);
+
+        if self.reserved_count() == 0 {
+            self.records.clear();
+            self.free_stack.clear();
+            return;
+        }
+
+        let mut is_free = vec![false; self.records.len()];
+        for &index in self.free_stack.iter() {
+            is_free[index as usize] = true;
+        }
         self.free_stack.clear();
+        // Spawn takes free records from the end of the stack, lowest indices go first.
+        for (index, record) in self.records.iter_mut().enumerate().rev() {
+            if record.block.is_some() || is_free[index] {
+                record.block.take();
+                #[cfg(feature = "alloc-tracking")]
+                {
+                    record.site = None;
+                }
+                self.free_stack.push(index as u32);
+            }
+        }
     }
 
     #[inline]
@@ -323,6 +573,207 @@ where
             .and_then(|rec| rec.block.get().as_ref())
     }
 
+    /// Moves object in the pool and returns its handle. A free record is reused if there is one,
+    /// otherwise a new record is created at the end of the pool.
+    #[inline]
+    #[must_use]
+    #[cfg_attr(feature = "alloc-tracking", track_caller)]
+    pub fn spawn(&mut self, payload: T) -> Handle<T> {
+        self.spawn_with(|_| payload)
+    }
+
+    /// Constructs a new object using the provided callback and returns its handle. The callback
+    /// receives the handle the object will have, which is handy for objects that must know it.
+    #[inline]
+    #[must_use]
+    #[cfg_attr(feature = "alloc-tracking", track_caller)]
+    pub fn spawn_with<F: FnOnce(Handle<T>) -> T>(&mut self, callback: F) -> Handle<T> {
+        #[cfg(feature = "alloc-tracking")]
+        tracking::track_spawn::<T>(1);
+
+        if let Some(free_index) = self.free_stack.pop() {
+            let record = self
+                .records_get_mut(free_index)
+                .expect("free stack contained invalid index");
+
+            if record.block.is_some() {
+                panic!(
+                    "Attempt to spawn an object at allocator record with payload! Record index is {}",
+                    free_index
+                );
+            }
+
+            let generation = record.generation + 1;
+            let handle = Handle::new(free_index, generation);
+
+            let payload = callback(handle);
+
+            record.generation = generation;
+            record.block.replace(payload);
+            #[cfg(feature = "alloc-tracking")]
+            {
+                record.site = Some(std::panic::Location::caller());
+            }
+
+            handle
+        } else {
+            // No free records, create new one
+            let generation = 1;
+            let handle = Handle::new(self.records_len(), generation);
+
+            let payload = callback(handle);
+
+            self.records.push(AllocatorRecord {
+                refc: Default::default(),
+                generation,
+                block: MemoryBlock::new(payload),
+                #[cfg(feature = "alloc-tracking")]
+                site: Some(std::panic::Location::caller()),
+            });
+
+            handle
+        }
+    }
+
+    /// Borrows shared reference to an object by its handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if handle is out of bounds, generation of handle does not match with
+    /// generation of the record handle points to, or the record is empty.
+    #[inline]
+    #[must_use]
+    pub fn borrow(&self, handle: Handle<T>) -> &T {
+        if let Some(record) = self.records_get(handle.index) {
+            if record.generation == handle.generation {
+                if let Some(payload) = record.block.as_ref() {
+                    payload
+                } else {
+                    panic!("Attempt to borrow destroyed object at {:?} handle.", handle);
+                }
+            } else {
+                panic!(
+                    "Attempt to use dangling handle {:?}. Record has generation {}!",
+                    handle, record.generation
+                );
+            }
+        } else {
+            panic!(
+                "Attempt to borrow object using out-of-bounds handle {:?}! Record count is {}",
+                handle,
+                self.records.len()
+            );
+        }
+    }
+
+    /// Borrows mutable reference to an object by its handle.
+    ///
+    /// # Panics
+    ///
+    /// See [`borrow`](Self::borrow).
+    #[inline]
+    #[must_use]
+    pub fn borrow_mut(&mut self, handle: Handle<T>) -> &mut T {
+        let record_count = self.records.len();
+        if let Some(record) = self.records_get_mut(handle.index) {
+            if record.generation == handle.generation {
+                if let Some(payload) = record.block.as_mut() {
+                    payload
+                } else {
+                    panic!("Attempt to borrow destroyed object at {:?} handle.", handle);
+                }
+            } else {
+                panic!(
+                    "Attempt to borrow object using dangling handle {:?}. Record has {} generation!",
+                    handle, record.generation
+                );
+            }
+        } else {
+            panic!(
+                "Attempt to borrow object using out-of-bounds handle {:?}! Record count is {}",
+                handle, record_count
+            );
+        }
+    }
+
+    /// Borrows shared reference to an object by its handle. Returns `None` if the handle is
+    /// out of bounds, dangling or points to an empty record.
+    #[inline]
+    #[must_use]
+    pub fn try_borrow(&self, handle: Handle<T>) -> Option<&T> {
+        self.records_get(handle.index).and_then(|r| {
+            if r.generation == handle.generation {
+                r.block.as_ref()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Borrows mutable reference to an object by its handle. Returns `None` if the handle is
+    /// out of bounds, dangling or points to an empty record.
+    #[inline]
+    #[must_use]
+    pub fn try_borrow_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.records_get_mut(handle.index).and_then(|r| {
+            if r.generation == handle.generation {
+                r.block.as_mut()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Destroys object by given handle and returns it. All handles to the object will become
+    /// invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is out of bounds, dangling or the object was already freed.
+    #[inline]
+    pub fn free(&mut self, handle: Handle<T>) -> T {
+        let index = usize::try_from(handle.index).expect("index overflowed usize");
+        if let Some(record) = self.records.get_mut(index) {
+            if record.generation == handle.generation {
+                // The record must be checked before its index is remembered, a double free would
+                // put the index on the free stack twice.
+                let Some(payload) = record.block.take() else {
+                    panic!("Attempt to double free object at handle {:?}!", handle);
+                };
+                // Remember this index as free
+                self.free_stack.push(handle.index);
+                #[cfg(feature = "alloc-tracking")]
+                {
+                    tracking::track_free::<T>(1);
+                    record.site = None;
+                }
+                // Return current payload.
+                payload
+            } else {
+                panic!(
+                    "Attempt to free object using dangling handle {:?}! Record generation is {}",
+                    handle, record.generation
+                );
+            }
+        } else {
+            panic!(
+                "Attempt to free destroyed object using out-of-bounds handle {:?}! Record count is {}",
+                handle,
+                self.records.len()
+            );
+        }
+    }
+
+    /// Checks if given handle "points" to some object.
+    #[inline]
+    pub fn is_valid_handle(&self, handle: Handle<T>) -> bool {
+        if let Some(record) = self.records_get(handle.index) {
+            record.block.is_some() && record.generation == handle.generation
+        } else {
+            false
+        }
+    }
+
     /// Returns the exact number of "alive" objects in the pool.
     ///
     /// Records that have been reserved (e.g. by [`take_reserve`]) are *not* counted.
@@ -476,6 +927,11 @@ where
             if !retain {
                 self.free_stack.push(i as u32);
                 record.block.take(); // and Drop
+                #[cfg(feature = "alloc-tracking")]
+                {
+                    tracking::track_free::<T>(1);
+                    record.site = None;
+                }
             }
         }
     }
@@ -609,4 +1065,136 @@ where
             None
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Debug, PartialEq, Context)]
+    struct Node {
+        name: String,
+        parent: Handle<Node>,
+        children: Vec<Handle<Node>>,
+    }
+
+    fn save(allocator: &mut Allocator<Node>) -> Vec<u8> {
+        let mut reflect_context = ReflectContext::new();
+        allocator.context("Nodes", &mut reflect_context).unwrap();
+        reflect_context.save_binary_to_vec().unwrap()
+    }
+
+    fn load(bytes: &[u8]) -> Result<Allocator<Node>, ContextError> {
+        let mut reflect_context = ReflectContext::load_from_memory(bytes).unwrap();
+        let mut allocator = Allocator::new();
+        allocator.context("Nodes", &mut reflect_context)?;
+        Ok(allocator)
+    }
+
+    #[test]
+    fn context_preserves_handles_and_free_records() {
+        let mut allocator = Allocator::<Node>::new();
+        let root = allocator.spawn(Node {
+            name: "Root".to_string(),
+            ..Default::default()
+        });
+        let temp = allocator.spawn(Node::default());
+        allocator.free(temp);
+        let reused = allocator.spawn(Node::default());
+        allocator.free(reused);
+        let child = allocator.spawn_with(|child| Node {
+            name: "Child".to_string(),
+            parent: root,
+            children: vec![child],
+        });
+        allocator.borrow_mut(root).children.push(child);
+        let gap = allocator.spawn(Node::default());
+        allocator.free(gap);
+
+        let mut loaded = load(&save(&mut allocator)).unwrap();
+        assert_eq!(loaded, allocator);
+        assert_eq!(loaded.total_count(), 2);
+
+        let loaded_child = loaded.borrow(root).children[0];
+        assert_eq!(loaded_child, child);
+        assert_eq!(loaded.borrow(loaded_child).parent, root);
+        assert!(!loaded.is_valid_handle(temp));
+        assert!(!loaded.is_valid_handle(gap));
+
+        // Free records are reused in the same order and with the same generations.
+        assert_eq!(
+            loaded.spawn(Node::default()),
+            allocator.spawn(Node::default())
+        );
+    }
+
+    #[test]
+    fn take_reserve_and_put_back() {
+        let mut allocator = Allocator::<Node>::new();
+        let root = allocator.spawn(Node::default());
+
+        let (ticket, mut node) = allocator.take_reserve(root);
+        assert!(!allocator.is_valid_handle(root));
+        assert!(allocator.try_take_reserve(root).is_none());
+        assert_eq!(allocator.total_count(), 1);
+        assert_eq!(allocator.alive_count(), 0);
+
+        // The reserved record is not reused while the object is taken.
+        let child = allocator.spawn(Node {
+            parent: root,
+            ..Default::default()
+        });
+        assert_ne!(child.index(), root.index());
+        node.children.push(child);
+
+        assert_eq!(allocator.put_back(ticket, node), root);
+        assert_eq!(allocator.borrow(root).children, [child]);
+
+        let (ticket, _) = allocator.try_take_reserve(child).unwrap();
+        allocator.forget_ticket(ticket);
+        assert!(!allocator.is_valid_handle(child));
+        assert_eq!(allocator.spawn(Node::default()).index(), child.index());
+        assert!(allocator.try_take_reserve(Handle::new(9, 1)).is_none());
+    }
+
+    #[test]
+    fn double_free_keeps_free_stack() {
+        let mut allocator = Allocator::<Node>::new();
+        let handle = allocator.spawn(Node::default());
+        allocator.free(handle);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            allocator.free(handle);
+        }));
+        assert!(result.is_err());
+        assert_eq!(allocator.free_stack, [handle.index()]);
+    }
+
+    #[test]
+    fn context_rejects_corrupted_free_stack() {
+        let mut allocator = Allocator::<Node>::new();
+        let handle = allocator.spawn(Node::default());
+        allocator.free_stack.push(handle.index());
+        assert!(load(&save(&mut allocator)).is_err());
+
+        allocator.free_stack = vec![7];
+        assert!(load(&save(&mut allocator)).is_err());
+    }
+
+    #[test]
+    fn context_frees_reserved_records() {
+        let mut allocator = Allocator::<Node>::new();
+        let first = allocator.spawn(Node::default());
+        let second = allocator.spawn(Node::default());
+        let (ticket, node) = allocator.take_reserve(first);
+        let bytes = save(&mut allocator);
+
+        let mut loaded = load(&bytes).unwrap();
+        assert_eq!(loaded.total_count(), 1);
+        assert!(loaded.is_valid_handle(second));
+        assert_eq!(loaded.spawn(Node::default()).index(), first.index());
+
+        // Tickets would point to loaded records.
+        let mut reflect_context = ReflectContext::load_from_memory(&bytes).unwrap();
+        assert!(allocator.context("Nodes", &mut reflect_context).is_err());
+        assert_eq!(allocator.put_back(ticket, node), first);
+    }
+}
@@ -0,0 +1,338 @@
+//! Simultaneous access to several objects of an [`Allocator`].
+//!
+//! [`MultiBorrowContext`] hands out shared ([`Ref`]) and mutable ([`RefMut`]) references to
+//! objects of different records at the same time. Borrowing rules are checked at runtime with the
+//! reference counter of each record: a record can be borrowed either mutably once or immutably any
+//! number of times, violations are reported as [`MultiBorrowError`] instead of a panic.
+//!
+//! ```
+//! use velcro_rtti::memory::Allocator;
+//!
+//! let mut allocator = Allocator::<u32>::new();
+//! let a = allocator.spawn(1);
+//! let b = allocator.spawn(2);
+//!
+//! let context = allocator.begin_multi_borrow();
+//! let mut first = context.get_mut(a);
+//! let mut second = context.get_mut(b);
+//! std::mem::swap(&mut *first, &mut *second);
+//! assert!(context.try_get(a).is_err());
+//! ```
+
+use crate::memory::{Allocator, AllocatorRecord, Handle, MemoryBlockContainer, RefCounter};
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    ops::{Deref, DerefMut},
+    sync::atomic,
+};
+
+/// An error that can occur when an object is borrowed from a [`MultiBorrowContext`].
+pub enum MultiBorrowError<T> {
+    /// The handle points outside of the allocator.
+    InvalidHandleIndex(Handle<T>),
+    /// The record has a different generation, the object the handle pointed to was freed.
+    InvalidHandleGeneration(Handle<T>),
+    /// The record has no object.
+    Empty(Handle<T>),
+    /// The object is already borrowed mutably.
+    MutablyBorrowed(Handle<T>),
+    /// The object is already borrowed immutably, so it can't be borrowed mutably.
+    ImmutablyBorrowed(Handle<T>),
+}
+
+impl<T> MultiBorrowError<T> {
+    /// Returns the handle that caused the error.
+    pub fn handle(&self) -> Handle<T> {
+        match self {
+            MultiBorrowError::InvalidHandleIndex(handle)
+            | MultiBorrowError::InvalidHandleGeneration(handle)
+            | MultiBorrowError::Empty(handle)
+            | MultiBorrowError::MutablyBorrowed(handle)
+            | MultiBorrowError::ImmutablyBorrowed(handle) => *handle,
+        }
+    }
+}
+
+impl<T> Debug for MultiBorrowError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<T> Display for MultiBorrowError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MultiBorrowError::InvalidHandleIndex(handle) => {
+                write!(f, "handle {} is out of bounds", handle)
+            }
+            MultiBorrowError::InvalidHandleGeneration(handle) => {
+                write!(f, "handle {} is dangling", handle)
+            }
+            MultiBorrowError::Empty(handle) => write!(f, "record at {} is empty", handle),
+            MultiBorrowError::MutablyBorrowed(handle) => {
+                write!(f, "object at {} is already borrowed mutably", handle)
+            }
+            MultiBorrowError::ImmutablyBorrowed(handle) => {
+                write!(f, "object at {} is already borrowed immutably", handle)
+            }
+        }
+    }
+}
+
+impl<T> PartialEq for MultiBorrowError<T> {
+    fn eq(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+            && self.handle() == other.handle()
+    }
+}
+
+impl<T> Eq for MultiBorrowError<T> {}
+
+impl<T> std::error::Error for MultiBorrowError<T> {}
+
+impl RefCounter {
+    /// Registers a shared borrow, fails if there is a mutable one.
+    fn try_borrow(&self) -> bool {
+        self.0
+            .fetch_update(atomic::Ordering::Acquire, atomic::Ordering::Relaxed, |count| {
+                (count >= 0).then_some(count + 1)
+            })
+            .is_ok()
+    }
+
+    /// Registers a mutable borrow, fails if there is any other borrow. Returns the current amount
+    /// of borrows on failure.
+    fn try_borrow_mut(&self) -> Result<(), isize> {
+        self.0
+            .compare_exchange(0, -1, atomic::Ordering::Acquire, atomic::Ordering::Relaxed)
+            .map(|_| ())
+    }
+}
+
+/// A shared reference to an object borrowed from a [`MultiBorrowContext`].
+pub struct Ref<'b, T> {
+    data: &'b T,
+    ref_counter: &'b RefCounter,
+}
+
+impl<T: Debug> Debug for Ref<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.data, f)
+    }
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+impl<T> Drop for Ref<'_, T> {
+    fn drop(&mut self) {
+        self.ref_counter.decrement();
+    }
+}
+
+/// A mutable reference to an object borrowed from a [`MultiBorrowContext`].
+pub struct RefMut<'b, T> {
+    data: &'b mut T,
+    ref_counter: &'b RefCounter,
+}
+
+impl<T: Debug> Debug for RefMut<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.data, f)
+    }
+}
+
+impl<T> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+impl<T> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.data
+    }
+}
+
+impl<T> Drop for RefMut<'_, T> {
+    fn drop(&mut self) {
+        self.ref_counter.increment();
+    }
+}
+
+/// Gives access to several objects of an allocator at once, see the [module docs](self). Created
+/// with [`Allocator::begin_multi_borrow`], the allocator itself is locked while the context exists,
+/// so objects can't be spawned or freed in the meantime.
+pub struct MultiBorrowContext<'a, T, M = Option<T>>
+where
+    M: MemoryBlockContainer<Element = T>,
+{
+    allocator: &'a mut Allocator<T, M>,
+}
+
+impl<'a, T, M> MultiBorrowContext<'a, T, M>
+where
+    M: MemoryBlockContainer<Element = T> + 'static,
+{
+    pub(super) fn new(allocator: &'a mut Allocator<T, M>) -> Self {
+        Self { allocator }
+    }
+
+    /// Finds a record with an object that the handle points to.
+    fn record_of(
+        &self,
+        handle: Handle<T>,
+    ) -> Result<&AllocatorRecord<T, M>, MultiBorrowError<T>> {
+        let record = self
+            .allocator
+            .records_get(handle.index())
+            .ok_or(MultiBorrowError::InvalidHandleIndex(handle))?;
+
+        if record.generation != handle.generation() {
+            return Err(MultiBorrowError::InvalidHandleGeneration(handle));
+        }
+
+        if !record.block.is_some() {
+            return Err(MultiBorrowError::Empty(handle));
+        }
+
+        Ok(record)
+    }
+
+    /// Borrows shared reference to an object.
+    pub fn try_get(&self, handle: Handle<T>) -> Result<Ref<'_, T>, MultiBorrowError<T>> {
+        let record = self.record_of(handle)?;
+
+        if !record.refc.try_borrow() {
+            return Err(MultiBorrowError::MutablyBorrowed(handle));
+        }
+
+        Ok(Ref {
+            // SAFETY: The record is not borrowed mutably, the reference counter ensures that it
+            // won't be until the reference is dropped.
+            data: unsafe { (*record.block.0.get()).as_ref().unwrap() },
+            ref_counter: &record.refc,
+        })
+    }
+
+    /// Borrows shared reference to an object.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases when [`Self::try_get`] returns an error.
+    pub fn get(&self, handle: Handle<T>) -> Ref<'_, T> {
+        self.try_get(handle).unwrap()
+    }
+
+    /// Borrows mutable reference to an object.
+    pub fn try_get_mut(&self, handle: Handle<T>) -> Result<RefMut<'_, T>, MultiBorrowError<T>> {
+        let record = self.record_of(handle)?;
+
+        match record.refc.try_borrow_mut() {
+            Ok(()) => (),
+            Err(count) if count < 0 => return Err(MultiBorrowError::MutablyBorrowed(handle)),
+            Err(_) => return Err(MultiBorrowError::ImmutablyBorrowed(handle)),
+        }
+
+        Ok(RefMut {
+            // SAFETY: The record is not borrowed at all, the reference counter ensures that it
+            // won't be until the reference is dropped.
+            data: unsafe { (*record.block.0.get()).as_mut().unwrap() },
+            ref_counter: &record.refc,
+        })
+    }
+
+    /// Borrows mutable reference to an object.
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases when [`Self::try_get_mut`] returns an error.
+    pub fn get_mut(&self, handle: Handle<T>) -> RefMut<'_, T> {
+        self.try_get_mut(handle).unwrap()
+    }
+
+    /// Returns `true` if the handle points to an object that can be borrowed mutably right now.
+    pub fn is_free(&self, handle: Handle<T>) -> bool {
+        self.record_of(handle)
+            .is_ok_and(|record| record.refc.0.load(atomic::Ordering::Relaxed) == 0)
+    }
+
+    /// Frees an object. Requires exclusive access to the context, so there can't be any borrowed
+    /// objects at this moment.
+    pub fn free(&mut self, handle: Handle<T>) -> Result<T, MultiBorrowError<T>> {
+        self.record_of(handle)?;
+        Ok(self.allocator.free(handle))
+    }
+}
+
+impl<T, M> Allocator<T, M>
+where
+    M: MemoryBlockContainer<Element = T> + 'static,
+{
+    /// Begins a multi-borrow that allows to borrow several objects at once, see
+    /// [`MultiBorrowContext`].
+    pub fn begin_multi_borrow(&mut self) -> MultiBorrowContext<'_, T, M> {
+        MultiBorrowContext::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_borrow_checks_borrows() {
+        let mut allocator = Allocator::<String>::new();
+        let a = allocator.spawn("a".to_string());
+        let b = allocator.spawn("b".to_string());
+        let freed = allocator.spawn("freed".to_string());
+        allocator.free(freed);
+
+        let mut context = allocator.begin_multi_borrow();
+        {
+            let mut first = context.get_mut(a);
+            let second = context.get(b);
+            let third = context.get(b);
+            first.push_str(&second);
+            first.push_str(&third);
+
+            assert_eq!(
+                context.try_get(a).unwrap_err(),
+                MultiBorrowError::MutablyBorrowed(a)
+            );
+            assert_eq!(
+                context.try_get_mut(a).unwrap_err(),
+                MultiBorrowError::MutablyBorrowed(a)
+            );
+            assert_eq!(
+                context.try_get_mut(b).unwrap_err(),
+                MultiBorrowError::ImmutablyBorrowed(b)
+            );
+            assert_eq!(
+                context.try_get(freed).unwrap_err(),
+                MultiBorrowError::Empty(freed)
+            );
+            assert_eq!(
+                context.try_get(Handle::new(freed.index(), 7)).unwrap_err(),
+                MultiBorrowError::InvalidHandleGeneration(Handle::new(freed.index(), 7))
+            );
+            assert_eq!(
+                context.try_get(Handle::new(10, 1)).unwrap_err(),
+                MultiBorrowError::InvalidHandleIndex(Handle::new(10, 1))
+            );
+            assert!(!context.is_free(b));
+        }
+
+        assert!(context.is_free(a) && context.is_free(b));
+        assert_eq!(&*context.get(a), "abb");
+        assert_eq!(context.free(b).unwrap(), "b");
+        assert!(context.try_get(b).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+//! Reference-counted handles.
+//!
+//! [`SharedAllocator`] is an [`Allocator`] that can be shared between owners and threads. Objects
+//! spawned in it are owned by [`StrongHandle`]s, the object is freed when the last strong handle
+//! is dropped, so there is no need to track when a shared resource (material, sound, etc.) is not
+//! used anymore. [`WeakHandle`] does not keep the object alive, it can be upgraded to a strong
+//! handle only while the object it was created for exists: when a record is reused by another
+//! object (with a new generation), old weak handles stay dead.
+//!
+//! ```
+//! use velcro_rtti::memory::SharedAllocator;
+//!
+//! let allocator = SharedAllocator::new();
+//! let material = allocator.spawn("Bricks".to_string());
+//! let copy = material.clone();
+//! let weak = material.downgrade();
+//!
+//! drop(material);
+//! assert_eq!(*weak.upgrade().unwrap().lock(), "Bricks");
+//!
+//! drop(copy);
+//! assert!(weak.upgrade().is_none());
+//! assert_eq!(allocator.alive_count(), 0);
+//! ```
+//!
+//! The allocator is locked when an object is spawned, freed or accessed with
+//! [`StrongHandle::lock`]. Dropping the last strong handle of an object while a lock guard of the
+//! same allocator is alive will deadlock.
+
+use crate::memory::{Allocator, Handle};
+use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
+use std::{
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    sync::{Arc, Weak},
+};
+
+/// Pool with reference-counted handles, see the [module docs](self).
+pub struct SharedAllocator<T: 'static> {
+    allocator: Arc<Mutex<Allocator<T>>>,
+}
+
+impl<T: 'static> Clone for SharedAllocator<T> {
+    fn clone(&self) -> Self {
+        Self {
+            allocator: self.allocator.clone(),
+        }
+    }
+}
+
+impl<T: 'static> Default for SharedAllocator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: 'static> Debug for SharedAllocator<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedAllocator")
+            .field("alive", &self.alive_count())
+            .finish()
+    }
+}
+
+impl<T: 'static> SharedAllocator<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            allocator: Arc::new(Mutex::new(Allocator::new())),
+        }
+    }
+
+    /// Moves object in the pool and returns the first strong handle to it.
+    #[cfg_attr(feature = "alloc-tracking", track_caller)]
+    pub fn spawn(&self, payload: T) -> StrongHandle<T> {
+        let handle = self.allocator.lock().spawn(payload);
+        StrongHandle {
+            owner: Arc::new(Owner {
+                handle,
+                allocator: self.allocator.clone(),
+            }),
+        }
+    }
+
+    /// Returns the number of "alive" objects in the pool.
+    #[inline]
+    pub fn alive_count(&self) -> u32 {
+        self.allocator.lock().alive_count()
+    }
+
+    /// Locks the pool and gives read access to it, for example to iterate over all objects.
+    #[inline]
+    pub fn with<R>(&self, func: impl FnOnce(&Allocator<T>) -> R) -> R {
+        func(&self.allocator.lock())
+    }
+}
+
+/// Shared by all strong handles to an object, frees the object when dropped.
+struct Owner<T: 'static> {
+    handle: Handle<T>,
+    allocator: Arc<Mutex<Allocator<T>>>,
+}
+
+impl<T: 'static> Drop for Owner<T> {
+    fn drop(&mut self) {
+        let payload = self.allocator.lock().free(self.handle);
+        // The lock is released already, so the object can drop strong handles of the same
+        // allocator.
+        drop(payload);
+    }
+}
+
+/// A handle that keeps an object of a [`SharedAllocator`] alive, see the [module docs](self).
+pub struct StrongHandle<T: 'static> {
+    owner: Arc<Owner<T>>,
+}
+
+impl<T: 'static> Clone for StrongHandle<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+impl<T: 'static> PartialEq for StrongHandle<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.owner, &other.owner)
+    }
+}
+
+impl<T: 'static> Eq for StrongHandle<T> {}
+
+impl<T: 'static> Hash for StrongHandle<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.owner.handle.hash(state)
+    }
+}
+
+impl<T: 'static> Debug for StrongHandle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "StrongHandle({:?})", self.owner.handle)
+    }
+}
+
+impl<T: 'static> StrongHandle<T> {
+    /// Returns the plain handle of the object. It does not keep the object alive.
+    #[inline]
+    pub fn handle(&self) -> Handle<T> {
+        self.owner.handle
+    }
+
+    /// Returns the number of strong handles to the object.
+    #[inline]
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.owner)
+    }
+
+    /// Creates a weak handle to the object.
+    #[inline]
+    pub fn downgrade(&self) -> WeakHandle<T> {
+        WeakHandle {
+            handle: self.owner.handle,
+            owner: Arc::downgrade(&self.owner),
+        }
+    }
+
+    /// Locks the pool and borrows the object. The pool stays locked until the guard is dropped.
+    #[inline]
+    pub fn lock(&self) -> MappedMutexGuard<'_, T> {
+        MutexGuard::map(self.owner.allocator.lock(), |allocator| {
+            allocator.borrow_mut(self.owner.handle)
+        })
+    }
+}
+
+/// A handle that does not keep an object of a [`SharedAllocator`] alive, see the
+/// [module docs](self).
+pub struct WeakHandle<T: 'static> {
+    handle: Handle<T>,
+    owner: Weak<Owner<T>>,
+}
+
+impl<T: 'static> Clone for WeakHandle<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle,
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+impl<T: 'static> Default for WeakHandle<T> {
+    /// Creates a weak handle that never upgrades.
+    #[inline]
+    fn default() -> Self {
+        Self {
+            handle: Handle::NONE,
+            owner: Weak::new(),
+        }
+    }
+}
+
+impl<T: 'static> PartialEq for WeakHandle<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.owner, &other.owner)
+    }
+}
+
+impl<T: 'static> Eq for WeakHandle<T> {}
+
+impl<T: 'static> Debug for WeakHandle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "WeakHandle({:?})", self.handle)
+    }
+}
+
+impl<T: 'static> WeakHandle<T> {
+    /// Returns the plain handle of the object, it may be invalid already.
+    #[inline]
+    pub fn handle(&self) -> Handle<T> {
+        self.handle
+    }
+
+    /// Returns a strong handle if the object is still alive. Every object has its own owner, so
+    /// the handle is never upgraded to another object that took the record after the original
+    /// object was freed.
+    #[inline]
+    pub fn upgrade(&self) -> Option<StrongHandle<T>> {
+        self.owner.upgrade().map(|owner| StrongHandle { owner })
+    }
+
+    /// Returns `true` if the object is still alive.
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        self.owner.strong_count() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Material {
+        name: String,
+        base: Option<StrongHandle<Material>>,
+    }
+
+    #[test]
+    fn strong_handles_free_objects_when_dropped() {
+        let allocator = SharedAllocator::new();
+        let base = allocator.spawn(Material {
+            name: "Base".to_string(),
+            base: None,
+        });
+        let derived = allocator.spawn(Material {
+            name: "Derived".to_string(),
+            base: Some(base.clone()),
+        });
+        let weak_base = base.downgrade();
+        let old_handle = base.handle();
+
+        drop(base);
+        assert!(weak_base.is_alive());
+        assert_eq!(derived.lock().base.as_ref().unwrap().handle(), old_handle);
+        assert_eq!(weak_base.upgrade().unwrap().lock().name, "Base");
+        assert_eq!(derived.strong_count(), 1);
+
+        // Freeing the derived material releases the base one as well.
+        let weak_derived = derived.downgrade();
+        drop(derived);
+        assert!(weak_derived.upgrade().is_none());
+        assert!(weak_base.upgrade().is_none());
+        assert_eq!(allocator.alive_count(), 0);
+
+        // The record is reused with a new generation, the old weak handle stays dead.
+        let reused = allocator.spawn(Material::default());
+        assert_eq!(reused.handle().index(), old_handle.index());
+        assert_ne!(reused.handle(), old_handle);
+        assert!(weak_base.upgrade().is_none());
+        assert!(allocator.with(|allocator| allocator.is_valid_handle(reused.handle())));
+        assert!(WeakHandle::<Material>::default().upgrade().is_none());
+    }
+}
@@ -0,0 +1,511 @@
+//! Structure-of-arrays storage for hot data.
+//!
+//! [`Allocator`](super::Allocator) keeps every object together with its generation in a record
+//! (array of structures), which is convenient for big objects, but wastes cache and prevents
+//! vectorization when an update touches only a few small fields of every object. [`SoaAllocator`]
+//! stores every field of a row in its own column instead. Columns are densely packed (there are
+//! no holes left by freed objects) and start at [`SOA_COLUMN_ALIGN`]-aligned addresses, so they
+//! can be processed directly with aligned SIMD loads and stores (`velcro-core` `vsimd` routines).
+//!
+//! Objects are addressed with the usual generational [`Handle`]s. Rows are tuples of `Copy`
+//! types, the second type parameter can be used to give handles of the allocator a distinct type.
+//!
+//! ```
+//! use velcro_rtti::memory::SoaAllocator;
+//!
+//! struct Body;
+//!
+//! let mut bodies = SoaAllocator::<([f32; 3], [f32; 3]), Body>::new();
+//! let body = bodies.spawn(([0.0, 10.0, 0.0], [1.0, 0.0, 0.0]));
+//!
+//! let (positions, velocities) = bodies.columns_mut();
+//! for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+//!     for (p, v) in position.iter_mut().zip(velocity) {
+//!         *p += v * 0.5;
+//!     }
+//! }
+//!
+//! assert_eq!(*bodies.borrow(body).0, [0.5, 10.0, 0.0]);
+//! ```
+//!
+//! Freeing an object moves the last row of every column in its place, so the order of rows is not
+//! preserved. Use [`SoaAllocator::handles`] to find out which object a row belongs to.
+
+use crate::memory::{Handle, INVALID_GENERATION};
+use std::{
+    alloc::Layout,
+    fmt::{self, Debug, Formatter},
+    mem,
+    ptr::NonNull,
+    slice,
+};
+
+/// Alignment of the first element of every column of a [`SoaAllocator`].
+pub const SOA_COLUMN_ALIGN: usize = 16;
+
+/// Dense index of a free record.
+const NIL: u32 = u32::MAX;
+
+/// A growable array of `Copy` values that starts at a [`SOA_COLUMN_ALIGN`]-aligned address.
+pub struct SoaColumn<C: Copy> {
+    ptr: NonNull<C>,
+    len: usize,
+    capacity: usize,
+}
+
+// SAFETY: The column owns its values, just like `Vec<C>`.
+unsafe impl<C: Copy + Send> Send for SoaColumn<C> {}
+unsafe impl<C: Copy + Sync> Sync for SoaColumn<C> {}
+
+impl<C: Copy> Default for SoaColumn<C> {
+    fn default() -> Self {
+        Self {
+            ptr: Self::dangling(),
+            len: 0,
+            capacity: if mem::size_of::<C>() == 0 { usize::MAX } else { 0 },
+        }
+    }
+}
+
+impl<C: Copy + Debug> Debug for SoaColumn<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl<C: Copy> SoaColumn<C> {
+    const ALIGN: usize = if mem::align_of::<C>() > SOA_COLUMN_ALIGN {
+        mem::align_of::<C>()
+    } else {
+        SOA_COLUMN_ALIGN
+    };
+
+    fn dangling() -> NonNull<C> {
+        // SAFETY: The alignment is never zero. Same as `NonNull::dangling`, but aligned to the
+        // column alignment.
+        unsafe { NonNull::new_unchecked(Self::ALIGN as *mut C) }
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::array::<C>(capacity)
+            .and_then(|layout| layout.align_to(Self::ALIGN))
+            .expect("column is too big")
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[C] {
+        // SAFETY: The first `len` values are initialized.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [C] {
+        // SAFETY: The first `len` values are initialized.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn grow(&mut self) {
+        // Zero-sized values take no memory, such columns start with `usize::MAX` capacity, so they
+        // run out of it only when the length overflows.
+        assert!(mem::size_of::<C>() != 0, "column length overflowed usize");
+
+        // Start with a few SIMD registers worth of values, values bigger than that start with one.
+        let min_capacity = (4 * SOA_COLUMN_ALIGN / mem::size_of::<C>()).max(1);
+        let capacity = (self.capacity * 2).max(min_capacity);
+        let new_layout = Self::layout(capacity);
+        // SAFETY: The layout has non-zero size (the capacity and the size of values are non-zero),
+        // the old block was allocated with the old layout.
+        let ptr = unsafe {
+            if self.capacity == 0 {
+                std::alloc::alloc(new_layout)
+            } else {
+                std::alloc::realloc(
+                    self.ptr.as_ptr().cast(),
+                    Self::layout(self.capacity),
+                    new_layout.size(),
+                )
+            }
+        };
+        match NonNull::new(ptr) {
+            Some(ptr) => self.ptr = ptr.cast(),
+            None => std::alloc::handle_alloc_error(new_layout),
+        }
+        self.capacity = capacity;
+    }
+
+    fn push(&mut self, value: C) {
+        if self.len == self.capacity {
+            self.grow();
+        }
+        // SAFETY: The index is inside of the allocated block.
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    fn swap_remove(&mut self, index: usize) -> C {
+        let value = self.as_slice()[index];
+        self.len -= 1;
+        // SAFETY: Both indices are inside of the allocated block, the last value is initialized.
+        unsafe {
+            let last = self.ptr.as_ptr().add(self.len).read();
+            self.ptr.as_ptr().add(index).write(last);
+        }
+        value
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<C: Copy> Drop for SoaColumn<C> {
+    fn drop(&mut self) {
+        if mem::size_of::<C>() != 0 && self.capacity != 0 {
+            // SAFETY: The block was allocated with the same layout, values don't need to be
+            // dropped.
+            unsafe { std::alloc::dealloc(self.ptr.as_ptr().cast(), Self::layout(self.capacity)) };
+        }
+    }
+}
+
+/// A row of a [`SoaAllocator`], every field of it is stored in a separate [`SoaColumn`].
+/// Implemented for tuples of up to eight `Copy` types.
+pub trait SoaRow: Copy + 'static {
+    type Columns: Default;
+    type Refs<'a>;
+    type Muts<'a>;
+    type Slices<'a>;
+    type SlicesMut<'a>;
+
+    fn push(columns: &mut Self::Columns, row: Self);
+
+    fn swap_remove(columns: &mut Self::Columns, index: usize) -> Self;
+
+    fn get(columns: &Self::Columns, index: usize) -> Self::Refs<'_>;
+
+    fn get_mut(columns: &mut Self::Columns, index: usize) -> Self::Muts<'_>;
+
+    fn slices(columns: &Self::Columns) -> Self::Slices<'_>;
+
+    fn slices_mut(columns: &mut Self::Columns) -> Self::SlicesMut<'_>;
+
+    fn clear(columns: &mut Self::Columns);
+}
+
+macro_rules! impl_soa_row {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Copy + 'static),+> SoaRow for ($($name,)+) {
+            type Columns = ($(SoaColumn<$name>,)+);
+            type Refs<'a> = ($(&'a $name,)+);
+            type Muts<'a> = ($(&'a mut $name,)+);
+            type Slices<'a> = ($(&'a [$name],)+);
+            type SlicesMut<'a> = ($(&'a mut [$name],)+);
+
+            fn push(columns: &mut Self::Columns, row: Self) {
+                $(columns.$index.push(row.$index);)+
+            }
+
+            fn swap_remove(columns: &mut Self::Columns, index: usize) -> Self {
+                ($(columns.$index.swap_remove(index),)+)
+            }
+
+            fn get(columns: &Self::Columns, index: usize) -> Self::Refs<'_> {
+                ($(&columns.$index.as_slice()[index],)+)
+            }
+
+            fn get_mut(columns: &mut Self::Columns, index: usize) -> Self::Muts<'_> {
+                ($(&mut columns.$index.as_mut_slice()[index],)+)
+            }
+
+            fn slices(columns: &Self::Columns) -> Self::Slices<'_> {
+                ($(columns.$index.as_slice(),)+)
+            }
+
+            fn slices_mut(columns: &mut Self::Columns) -> Self::SlicesMut<'_> {
+                ($(columns.$index.as_mut_slice(),)+)
+            }
+
+            fn clear(columns: &mut Self::Columns) {
+                $(columns.$index.clear();)+
+            }
+        }
+    };
+}
+
+impl_soa_row!(A 0);
+impl_soa_row!(A 0, B 1);
+impl_soa_row!(A 0, B 1, C 2);
+impl_soa_row!(A 0, B 1, C 2, D 3);
+impl_soa_row!(A 0, B 1, C 2, D 3, E 4);
+impl_soa_row!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_soa_row!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_soa_row!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[derive(Debug, Clone, Copy)]
+struct SoaRecord {
+    generation: u32,
+    /// Index of the row in columns, [`NIL`] if the record is free.
+    dense: u32,
+}
+
+/// Pool that stores rows in separate aligned columns, see the [module docs](self).
+pub struct SoaAllocator<R: SoaRow, T = R> {
+    records: Vec<SoaRecord>,
+    free_stack: Vec<u32>,
+    /// Handle of the object of every row.
+    handles: Vec<Handle<T>>,
+    columns: R::Columns,
+}
+
+impl<R: SoaRow, T> Default for SoaAllocator<R, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: SoaRow, T> Debug for SoaAllocator<R, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoaAllocator")
+            .field("alive", &self.alive_count())
+            .field("records", &self.records.len())
+            .finish()
+    }
+}
+
+impl<R: SoaRow, T> SoaAllocator<R, T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            free_stack: Vec::new(),
+            handles: Vec::new(),
+            columns: Default::default(),
+        }
+    }
+
+    /// Appends the row to the columns and returns a handle to it.
+    pub fn spawn(&mut self, row: R) -> Handle<T> {
+        let dense = u32::try_from(self.handles.len()).expect("dense index overflowed u32");
+        assert_ne!(dense, NIL, "SoaAllocator is full!");
+
+        let handle = if let Some(index) = self.free_stack.pop() {
+            let record = &mut self.records[index as usize];
+            record.generation += 1;
+            record.dense = dense;
+            Handle::new(index, record.generation)
+        } else {
+            let index = u32::try_from(self.records.len()).expect("index overflowed u32");
+            let generation = INVALID_GENERATION + 1;
+            self.records.push(SoaRecord { generation, dense });
+            Handle::new(index, generation)
+        };
+
+        R::push(&mut self.columns, row);
+        self.handles.push(handle);
+        #[cfg(feature = "alloc-tracking")]
+        super::tracking::track_spawn::<R>(1);
+        handle
+    }
+
+    /// Returns the index of the row of the object in columns, `None` if the handle is invalid.
+    /// The index changes when other objects are freed.
+    #[inline]
+    pub fn index_of(&self, handle: Handle<T>) -> Option<usize> {
+        let record = self.records.get(handle.index() as usize)?;
+        if record.dense != NIL && record.generation == handle.generation() {
+            Some(record.dense as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Checks if given handle "points" to some object.
+    #[inline]
+    pub fn is_valid_handle(&self, handle: Handle<T>) -> bool {
+        self.index_of(handle).is_some()
+    }
+
+    /// Removes the object from the allocator and returns its row, `None` if the handle is
+    /// invalid. The last row is moved in place of the removed one.
+    pub fn try_free(&mut self, handle: Handle<T>) -> Option<R> {
+        let dense = self.index_of(handle)?;
+        self.records[handle.index() as usize].dense = NIL;
+        self.free_stack.push(handle.index());
+
+        let row = R::swap_remove(&mut self.columns, dense);
+        self.handles.swap_remove(dense);
+        if let Some(moved) = self.handles.get(dense) {
+            self.records[moved.index() as usize].dense = dense as u32;
+        }
+        #[cfg(feature = "alloc-tracking")]
+        super::tracking::track_free::<R>(1);
+        Some(row)
+    }
+
+    /// Removes the object from the allocator and returns its row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is invalid.
+    #[inline]
+    pub fn free(&mut self, handle: Handle<T>) -> R {
+        self.try_free(handle)
+            .unwrap_or_else(|| panic!("Attempt to free object using invalid handle {:?}!", handle))
+    }
+
+    /// Returns references to all fields of the object.
+    #[inline]
+    pub fn try_borrow(&self, handle: Handle<T>) -> Option<R::Refs<'_>> {
+        let dense = self.index_of(handle)?;
+        Some(R::get(&self.columns, dense))
+    }
+
+    /// Returns mutable references to all fields of the object.
+    #[inline]
+    pub fn try_borrow_mut(&mut self, handle: Handle<T>) -> Option<R::Muts<'_>> {
+        let dense = self.index_of(handle)?;
+        Some(R::get_mut(&mut self.columns, dense))
+    }
+
+    /// Returns references to all fields of the object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is invalid.
+    #[inline]
+    pub fn borrow(&self, handle: Handle<T>) -> R::Refs<'_> {
+        self.try_borrow(handle)
+            .unwrap_or_else(|| panic!("Attempt to borrow object using invalid handle {:?}!", handle))
+    }
+
+    /// Returns mutable references to all fields of the object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is invalid.
+    #[inline]
+    pub fn borrow_mut(&mut self, handle: Handle<T>) -> R::Muts<'_> {
+        match self.index_of(handle) {
+            Some(dense) => R::get_mut(&mut self.columns, dense),
+            None => panic!("Attempt to borrow object using invalid handle {:?}!", handle),
+        }
+    }
+
+    /// Returns all columns as slices of the same length, every slice starts at a
+    /// [`SOA_COLUMN_ALIGN`]-aligned address.
+    #[inline]
+    pub fn columns(&self) -> R::Slices<'_> {
+        R::slices(&self.columns)
+    }
+
+    /// Returns all columns as mutable slices of the same length, every slice starts at a
+    /// [`SOA_COLUMN_ALIGN`]-aligned address.
+    #[inline]
+    pub fn columns_mut(&mut self) -> R::SlicesMut<'_> {
+        R::slices_mut(&mut self.columns)
+    }
+
+    /// Returns handles of objects in the order of rows.
+    #[inline]
+    pub fn handles(&self) -> &[Handle<T>] {
+        &self.handles
+    }
+
+    /// Iterates over all objects in the order of rows.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, R::Refs<'_>)> {
+        self.handles
+            .iter()
+            .enumerate()
+            .map(|(dense, handle)| (*handle, R::get(&self.columns, dense)))
+    }
+
+    /// Returns the number of objects in the allocator. This method is `O(1)`.
+    #[inline]
+    pub fn alive_count(&self) -> u32 {
+        self.handles.len() as u32
+    }
+
+    /// Frees all objects, handles to them become invalid.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "alloc-tracking")]
+        super::tracking::track_free::<R>(self.handles.len());
+        for handle in self.handles.drain(..) {
+            self.records[handle.index() as usize].dense = NIL;
+            self.free_stack.push(handle.index());
+        }
+        R::clear(&mut self.columns);
+    }
+}
+
+#[cfg(feature = "alloc-tracking")]
+impl<R: SoaRow, T> Drop for SoaAllocator<R, T> {
+    fn drop(&mut self) {
+        super::tracking::track_free::<R>(self.handles.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soa_allocator_keeps_columns_dense_and_aligned() {
+        let mut allocator = SoaAllocator::<(f32, [f32; 3], u8)>::new();
+        let handles = (0..10)
+            .map(|i| allocator.spawn((i as f32, [i as f32; 3], i as u8)))
+            .collect::<Vec<_>>();
+
+        let (masses, velocities, tags) = allocator.columns();
+        assert_eq!(masses.as_ptr() as usize % SOA_COLUMN_ALIGN, 0);
+        assert_eq!(velocities.as_ptr() as usize % SOA_COLUMN_ALIGN, 0);
+        assert_eq!(tags.as_ptr() as usize % SOA_COLUMN_ALIGN, 0);
+        assert_eq!(masses.len(), 10);
+
+        // The last row takes place of the freed one.
+        assert_eq!(allocator.free(handles[2]), (2.0, [2.0; 3], 2));
+        assert_eq!(allocator.index_of(handles[9]), Some(2));
+        assert_eq!(allocator.handles()[2], handles[9]);
+        assert_eq!(*allocator.borrow(handles[9]).2, 9);
+        assert!(allocator.try_borrow(handles[2]).is_none());
+        assert!(allocator.try_free(handles[2]).is_none());
+
+        let (masses, _, _) = allocator.columns_mut();
+        masses.iter_mut().for_each(|mass| *mass *= 2.0);
+        *allocator.borrow_mut(handles[5]).1 = [0.0; 3];
+        assert_eq!(allocator.borrow(handles[5]), (&10.0, &[0.0; 3], &5));
+
+        // The record is reused with a new generation.
+        let reused = allocator.spawn((1.0, [1.0; 3], 1));
+        assert_eq!(reused.index(), handles[2].index());
+        assert_ne!(reused, handles[2]);
+        assert_eq!(allocator.alive_count(), 10);
+        assert_eq!(allocator.iter().filter(|(_, (_, _, tag))| **tag == 1).count(), 2);
+
+        allocator.clear();
+        assert_eq!(allocator.alive_count(), 0);
+        assert!(!allocator.is_valid_handle(reused));
+
+        // Values bigger than the initial capacity in bytes and zero-sized values.
+        let mut allocator = SoaAllocator::<([u8; 128], ())>::new();
+        let handles = (0..5)
+            .map(|i| allocator.spawn(([i; 128], ())))
+            .collect::<Vec<_>>();
+        let (blocks, units) = allocator.columns();
+        assert_eq!(blocks.as_ptr() as usize % SOA_COLUMN_ALIGN, 0);
+        assert_eq!((blocks.len(), units.len()), (5, 5));
+        assert_eq!(allocator.borrow(handles[3]).0[127], 3);
+        assert_eq!(allocator.free(handles[0]), ([0; 128], ()));
+        assert_eq!(allocator.borrow(handles[4]).0[0], 4);
+    }
+}
@@ -0,0 +1,226 @@
+//! Allocation tracking, enabled with the `alloc-tracking` feature.
+//!
+//! Every [`Allocator`](super::Allocator) remembers where each of its objects was spawned
+//! (`#[track_caller]`), and keeps global per-type statistics: the number of live objects, the
+//! peak number of live objects and the number of objects spawned so far, see [`allocation_stats`].
+//! When an allocator is dropped with live objects, a [`LeakReport`] is passed to the leak handler
+//! (see [`set_leak_handler`]), which prints it to stderr by default.
+//!
+//! Tracking is not free, so it is meant for debug builds and for finding out which subsystem keeps
+//! growing memory.
+
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    panic::Location,
+};
+
+/// Statistics of all allocators of a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationStats {
+    /// Name of the type of the objects.
+    pub type_name: &'static str,
+    /// Size of an object, in bytes.
+    pub size: usize,
+    /// The number of live objects.
+    pub live: usize,
+    /// The biggest number of live objects at the same time.
+    pub peak: usize,
+    /// The number of objects spawned so far.
+    pub total: usize,
+}
+
+impl AllocationStats {
+    /// Returns the amount of memory used by the live objects, in bytes.
+    #[inline]
+    pub fn live_bytes(&self) -> usize {
+        self.live * self.size
+    }
+
+    /// Returns the amount of memory used by the live objects at the peak, in bytes.
+    #[inline]
+    pub fn peak_bytes(&self) -> usize {
+        self.peak * self.size
+    }
+}
+
+/// An object that was alive when its allocator was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeakedObject {
+    /// Index of the record of the object.
+    pub index: u32,
+    /// Generation of the record of the object.
+    pub generation: u32,
+    /// Where the object was spawned, `None` if the object was loaded or the allocator was cloned.
+    pub site: Option<&'static Location<'static>>,
+}
+
+/// Objects that were alive when their allocator was dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakReport {
+    /// Name of the type of the objects.
+    pub type_name: &'static str,
+    /// The leaked objects, in the order of their records.
+    pub objects: Vec<LeakedObject>,
+}
+
+impl Display for LeakReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Allocator<{}> was dropped with {} live object(s):",
+            self.type_name,
+            self.objects.len()
+        )?;
+
+        // Group the objects by the site, so a leak in a loop takes one line.
+        let mut sites = Vec::<(Option<&'static Location<'static>>, usize)>::new();
+        for object in self.objects.iter() {
+            match sites.iter_mut().find(|(site, _)| *site == object.site) {
+                Some((_, count)) => *count += 1,
+                None => sites.push((object.site, 1)),
+            }
+        }
+
+        for (site, count) in sites {
+            match site {
+                Some(site) => writeln!(f, "    {count} spawned at {site}")?,
+                None => writeln!(f, "    {count} spawned at unknown location")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn print_leak_report(report: &LeakReport) {
+    eprint!("{report}");
+}
+
+lazy_static! {
+    static ref STATS: Mutex<HashMap<&'static str, AllocationStats>> = Default::default();
+    static ref LEAK_HANDLER: Mutex<fn(&LeakReport)> = Mutex::new(print_leak_report);
+}
+
+/// Returns statistics of all types that were ever allocated, sorted by the type name.
+pub fn allocation_stats() -> Vec<AllocationStats> {
+    let mut stats = STATS.lock().values().copied().collect::<Vec<_>>();
+    stats.sort_by_key(|stats| stats.type_name);
+    stats
+}
+
+/// Returns statistics of a type, `None` if it was never allocated.
+pub fn allocation_stats_of<T>() -> Option<AllocationStats> {
+    STATS.lock().get(std::any::type_name::<T>()).copied()
+}
+
+/// Sets a function that is called when an allocator is dropped with live objects. The default
+/// handler prints the report to stderr.
+pub fn set_leak_handler(handler: fn(&LeakReport)) {
+    *LEAK_HANDLER.lock() = handler;
+}
+
+/// Registers spawned objects. Allocators call it automatically, it is public for custom
+/// containers.
+pub fn track_spawn<T>(count: usize) {
+    if count == 0 {
+        return;
+    }
+
+    let type_name = std::any::type_name::<T>();
+    let mut stats = STATS.lock();
+    let stats = stats.entry(type_name).or_insert(AllocationStats {
+        type_name,
+        size: std::mem::size_of::<T>(),
+        live: 0,
+        peak: 0,
+        total: 0,
+    });
+    stats.live += count;
+    stats.total += count;
+    stats.peak = stats.peak.max(stats.live);
+}
+
+/// Registers freed objects. Allocators call it automatically, it is public for custom containers.
+pub fn track_free<T>(count: usize) {
+    if count == 0 {
+        return;
+    }
+
+    if let Some(stats) = STATS.lock().get_mut(std::any::type_name::<T>()) {
+        stats.live = stats.live.saturating_sub(count);
+    }
+}
+
+pub(super) fn report_leak(report: &LeakReport) {
+    let handler = *LEAK_HANDLER.lock();
+    handler(report);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Allocator;
+
+    struct Tracked(#[allow(dead_code)] u64);
+
+    lazy_static! {
+        static ref REPORTS: Mutex<Vec<LeakReport>> = Default::default();
+    }
+
+    fn collect_report(report: &LeakReport) {
+        if report.type_name == std::any::type_name::<Tracked>() {
+            REPORTS.lock().push(report.clone());
+        }
+    }
+
+    #[test]
+    fn allocators_track_live_objects_and_leaks() {
+        set_leak_handler(collect_report);
+
+        let mut allocator = Allocator::<Tracked>::new();
+        let handles = (0..3).map(|i| allocator.spawn(Tracked(i))).collect::<Vec<_>>();
+        allocator.free(handles[0]);
+        allocator.free(handles[1]);
+        // Reserved objects are still alive.
+        let (ticket, object) = allocator.take_reserve(handles[2]);
+        assert_eq!(allocation_stats_of::<Tracked>().unwrap().live, 1);
+        allocator.put_back(ticket, object);
+        let (leaked, line) = (allocator.spawn(Tracked(3)), line!());
+
+        let stats = allocation_stats_of::<Tracked>().unwrap();
+        assert_eq!((stats.live, stats.peak, stats.total), (2, 3, 4));
+        assert_eq!(stats.live_bytes(), 16);
+        assert!(allocation_stats()
+            .iter()
+            .any(|stats| stats.type_name == std::any::type_name::<Tracked>()));
+
+        drop(allocator);
+        assert_eq!(allocation_stats_of::<Tracked>().unwrap().live, 0);
+
+        let reports = REPORTS.lock();
+        assert_eq!(reports.len(), 1);
+        let objects = &reports[0].objects;
+        assert_eq!(objects.len(), 2);
+        let object = objects.iter().find(|o| o.index == leaked.index()).unwrap();
+        assert_eq!(object.site.unwrap().line(), line);
+        assert!(object.site.unwrap().file().ends_with("tracking.rs"));
+        assert!(reports[0].to_string().contains("1 spawned at"));
+        drop(reports);
+
+        // Reserved objects stay tracked until their ticket is given back.
+        let mut allocator = Allocator::<Tracked>::new();
+        let handles = (0..3).map(|i| allocator.spawn(Tracked(i))).collect::<Vec<_>>();
+        let (ticket, object) = allocator.take_reserve(handles[1]);
+        allocator.clear();
+        assert_eq!(allocation_stats_of::<Tracked>().unwrap().live, 1);
+        assert_eq!(allocator.put_back(ticket, object), handles[1]);
+        assert_eq!(allocator.alive_count(), 1);
+        assert_eq!(allocator.spawn(Tracked(4)).index(), 0);
+        assert_eq!(allocation_stats_of::<Tracked>().unwrap().live, 2);
+        allocator.clear();
+        assert_eq!(allocation_stats_of::<Tracked>().unwrap().live, 0);
+        assert_eq!(allocator.total_count(), 0);
+    }
+}
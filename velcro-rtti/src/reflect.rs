@@ -1,12 +1,25 @@
 //! Runtime reflection
 
 mod std_impls;
-
-
-pub use velcro_derive::Reflect;
+mod leaf;
+mod method;
+mod patch;
+mod serde_impls;
+
+
+pub use velcro_derive::{reflect_methods, Reflect};
+pub use method::{CallError, MethodArgs, MethodInfo, MethodReceiver, ParamInfo, ReflectMethods};
+pub use patch::{apply_patch, diff, DiffError, PatchError, ReflectChange};
+pub(crate) use leaf::{clone_leaf, concrete_type_id};
+pub(crate) use patch::{field_path, key_string};
+pub use serde_impls::{
+    ReflectDeserializer, ReflectSerializer, TypedReflectDeserializer, TypedReflectSerializer,
+};
 
 
 
+use crate::type_registry::TypeRegistry;
+use crate::variable::ReflectInheritableVariable;
 use std::{
     any::{Any, TypeId},
     fmt::{self, Debug, Display, Formatter},
@@ -15,7 +28,10 @@ use std::{
 
 pub mod prelude {
     pub use super::{
-        FieldInfo, Reflect, ReflectArray, ReflectList, ReflectHashMap, 
+        FieldConstraints, FieldInfo, Reflect, ReflectArray, ReflectList, ReflectHashMap,
+        ReflectEnum, ReflectSerializer, ReflectDeserializer, SetFieldError, SetVariantError,
+        reflect_methods, CallError, MethodArgs, MethodInfo, MethodReceiver, ParamInfo,
+        ReflectMethods, default_value,
     };
 }
 
@@ -85,10 +101,10 @@ pub struct FieldInfo<'a, 'b> {
     /// A minimal value of the property. Works only with numeric properties!
     pub min_value: Option<f64>,
 
-    /// A minimal value of the property. Works only with numeric properties!
+    /// A maximal value of the property. Works only with numeric properties!
     pub max_value: Option<f64>,
 
-    /// A minimal value of the property. Works only with numeric properties!
+    /// Increment/decrement step of the property. Works only with numeric properties!
     pub step: Option<f64>,
 
     /// Maximum amount of decimal places for a numeric property.
@@ -108,8 +124,145 @@ impl<'a, 'b> FieldInfo<'a, 'b> {
             }),
         }
     }
+
+    /// Returns constraints that [`Reflect::set_field`] enforces for the property.
+    pub fn constraints(&self) -> FieldConstraints {
+        FieldConstraints {
+            read_only: self.read_only,
+            immutable_collection: self.immutable_collection,
+            min_value: self.min_value,
+            max_value: self.max_value,
+        }
+    }
+}
+
+/// Constraints of a field value, set with `#[reflect(read_only, immutable_collection)]` and
+/// `#[reflect(min_value = .., max_value = ..)]`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FieldConstraints {
+    /// The field can't be changed with [`Reflect::set_field`].
+    pub read_only: bool,
+
+    /// The length of a collection field can't be changed with [`Reflect::set_field`] or when the
+    /// field is deserialized, only its items can.
+    pub immutable_collection: bool,
+
+    /// A minimal value of a numeric field.
+    pub min_value: Option<f64>,
+
+    /// A maximal value of a numeric field.
+    pub max_value: Option<f64>,
+}
+
+impl FieldConstraints {
+    /// Returns `true` if the value is in the allowed range. Non-numeric values are always in
+    /// range.
+    pub fn in_range(&self, value: &dyn Reflect) -> bool {
+        let mut number = None;
+        value.as_any(&mut |any| number = leaf::numeric_value(any));
+        let Some(number) = number else {
+            return true;
+        };
+
+        self.min_value.map_or(true, |min| number >= min)
+            && self.max_value.map_or(true, |max| number <= max)
+    }
+}
+
+/// Returns the length of an array, a list or a hash map.
+fn collection_len(value: &dyn Reflect) -> Option<usize> {
+    let mut len = None;
+    value.as_array(&mut |array| len = array.map(|array| array.reflect_len()));
+    if len.is_none() {
+        value.as_hash_map(&mut |map| len = map.map(|map| map.reflect_len()));
+    }
+    len
 }
 
+/// An error that can occur in [`Reflect::set_field`]. The rejected value is given back and can be
+/// taken with [`SetFieldError::into_value`].
+#[derive(Debug)]
+pub enum SetFieldError {
+    /// There is no field with the given name.
+    NoSuchField {
+        name: String,
+        value: Box<dyn Reflect>,
+    },
+
+    /// The field is marked with `#[reflect(read_only)]`.
+    ReadOnly {
+        name: String,
+        value: Box<dyn Reflect>,
+    },
+
+    /// The field is marked with `#[reflect(immutable_collection)]` and the value has a different
+    /// length.
+    ImmutableCollection {
+        name: String,
+        value: Box<dyn Reflect>,
+    },
+
+    /// The value is outside of the `min_value..=max_value` range of the field.
+    OutOfRange {
+        name: String,
+        value: Box<dyn Reflect>,
+        min_value: Option<f64>,
+        max_value: Option<f64>,
+    },
+
+    /// The value has a type different from the type of the field.
+    TypeMismatch {
+        name: String,
+        value: Box<dyn Reflect>,
+    },
+}
+
+impl SetFieldError {
+    /// Returns the rejected value.
+    pub fn into_value(self) -> Box<dyn Reflect> {
+        match self {
+            SetFieldError::NoSuchField { value, .. }
+            | SetFieldError::ReadOnly { value, .. }
+            | SetFieldError::ImmutableCollection { value, .. }
+            | SetFieldError::OutOfRange { value, .. }
+            | SetFieldError::TypeMismatch { value, .. } => value,
+        }
+    }
+}
+
+impl Display for SetFieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SetFieldError::NoSuchField { name, .. } => write!(f, "no field {name}"),
+            SetFieldError::ReadOnly { name, .. } => write!(f, "field {name} is read-only"),
+            SetFieldError::ImmutableCollection { name, .. } => {
+                write!(f, "the length of field {name} can't be changed")
+            }
+            SetFieldError::OutOfRange {
+                name,
+                value,
+                min_value,
+                max_value,
+            } => {
+                write!(f, "value {value:?} of field {name} is out of range ")?;
+                match (min_value, max_value) {
+                    (Some(min), Some(max)) => write!(f, "{min}..={max}"),
+                    (Some(min), None) => write!(f, "{min}.."),
+                    (None, Some(max)) => write!(f, "..={max}"),
+                    (None, None) => write!(f, ".."),
+                }
+            }
+            SetFieldError::TypeMismatch { name, value } => write!(
+                f,
+                "field {name} can't be assigned a value of type {}",
+                value.type_name()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SetFieldError {}
+
 impl<'a, 'b> fmt::Debug for FieldInfo<'a, 'b> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("PropertyInfo")
@@ -167,11 +320,20 @@ impl<T: Reflect> ReflectBase for T {
 /// # Type attributes
 /// - `#[reflect(hide_all)]`: Hide all fields, just like `Any`
 /// - `#[reflect(bounds)]`: Add type boundary for `Reflect` impl
+/// - `#[reflect(no_clone)]`: Do not implement [`Reflect::clone_box`] with `Clone`
+/// - `#[reflect(no_eq)]`: Do not implement [`Reflect::reflect_eq`] with `PartialEq`
+/// - `#[reflect(methods)]`: Forward [`Reflect::call_method`] to the [`ReflectMethods`] impl
+///   generated by `#[reflect_methods]`
 ///
 /// # Field attributes
 /// - `#[reflect(deref)]`: Delegate the field access with deref
 /// - `#[reflect(field = <method call>)]`
 /// - `#[reflect(field_mut = <method call>)]`
+/// - `#[reflect(setter = <method name>)]`: Use the method in [`Reflect::set_field`]
+/// - `#[reflect(read_only)]`, `#[reflect(min_value = .., max_value = ..)]`: Constraints enforced
+///   by [`Reflect::set_field`], see [`FieldConstraints`]
+/// - `#[reflect(display_name = .., description = .., step = .., precision = ..)]`: Editor hints,
+///   available through [`Reflect::field_info`]
 ///
 /// # Additional Trait Bounds
 ///
@@ -218,25 +380,94 @@ pub trait Reflect: ReflectBase {
     where
         Self: Sized;
 
-     /// Calls user method specified with `#[reflect(setter = ..)]` or falls back to
-    /// [`Reflect::field_mut`]
+    /// Calls user method specified with `#[reflect(setter = ..)]` or falls back to
+    /// [`Reflect::field_mut`]. The value is checked with [`Reflect::check_field_value`] first, so
+    /// read-only fields, resized immutable collections and out-of-range values are rejected.
+    /// Passes the previous value of the field to `func` on success.
     #[allow(clippy::type_complexity)]
     fn set_field(
         &mut self,
         field: &str,
         value: Box<dyn Reflect>,
-        func: &mut dyn FnMut(Result<Box<dyn Reflect>, Box<dyn Reflect>>),
+        func: &mut dyn FnMut(Result<Box<dyn Reflect>, SetFieldError>),
     ) {
+        let value = match self.check_field_value(field, value) {
+            Ok(value) => value,
+            Err(err) => return func(Err(err)),
+        };
+
         let mut opt_value = Some(value);
-        self.field_mut(field, &mut move |field| {
+        self.field_mut(field, &mut move |f| {
             let value = opt_value.take().unwrap();
-            match field {
-                Some(f) => func(f.set(value)),
-                None => func(Err(value)),
+            match f {
+                Some(f) => func(f.set(value).map_err(|value| SetFieldError::TypeMismatch {
+                    name: field.to_string(),
+                    value,
+                })),
+                None => func(Err(SetFieldError::NoSuchField {
+                    name: field.to_string(),
+                    value,
+                })),
             };
         });
     }
 
+    /// Passes [`FieldInfo`] of the field with the given name to `func`, if there is such field.
+    fn field_info(&self, name: &str, func: &mut dyn FnMut(Option<&FieldInfo>)) {
+        let mut func = Some(func);
+        self.fields_info(&mut |fields| {
+            if let Some(func) = func.take() {
+                func(fields.iter().find(|field| field.name == name))
+            }
+        });
+        if let Some(func) = func {
+            func(None)
+        }
+    }
+
+    /// Checks the value against [`FieldConstraints`] of the field, the value is given back on
+    /// success. Fields that are not reported by [`Reflect::fields_info`] have no constraints.
+    fn check_field_value(
+        &self,
+        name: &str,
+        value: Box<dyn Reflect>,
+    ) -> Result<Box<dyn Reflect>, SetFieldError> {
+        let mut constraints = None;
+        self.field_info(name, &mut |field| {
+            constraints = field.map(|field| field.constraints())
+        });
+        let Some(constraints) = constraints else {
+            return Ok(value);
+        };
+
+        let resized = constraints.immutable_collection && {
+            let mut len = None;
+            self.field(name, &mut |field| len = field.and_then(collection_len));
+            len != collection_len(&*value)
+        };
+
+        if constraints.read_only {
+            Err(SetFieldError::ReadOnly {
+                name: name.to_string(),
+                value,
+            })
+        } else if resized {
+            Err(SetFieldError::ImmutableCollection {
+                name: name.to_string(),
+                value,
+            })
+        } else if !constraints.in_range(&*value) {
+            Err(SetFieldError::OutOfRange {
+                name: name.to_string(),
+                value,
+                min_value: constraints.min_value,
+                max_value: constraints.max_value,
+            })
+        } else {
+            Ok(value)
+        }
+    }
+
     fn fields(&self, func: &mut dyn FnMut(&[&dyn Reflect])) {
         func(&[])
     }
@@ -284,6 +515,85 @@ pub trait Reflect: ReflectBase {
     fn as_hash_map_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectHashMap>)) {
         func(None)
     }
+
+    fn as_enum(&self, func: &mut dyn FnMut(Option<&dyn ReflectEnum>)) {
+        func(None)
+    }
+
+    fn as_enum_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectEnum>)) {
+        func(None)
+    }
+
+    /// Returns the value as an inheritable variable, if it is one.
+    fn as_inheritable_variable(
+        &self,
+        func: &mut dyn FnMut(Option<&dyn ReflectInheritableVariable>),
+    ) {
+        func(None)
+    }
+
+    fn as_inheritable_variable_mut(
+        &mut self,
+        func: &mut dyn FnMut(Option<&mut dyn ReflectInheritableVariable>),
+    ) {
+        func(None)
+    }
+
+    /// Returns methods marked with `#[reflect(method)]`, see [`ReflectMethods`].
+    fn methods_info(&self) -> &'static [MethodInfo] {
+        &[]
+    }
+
+    /// Calls a method marked with `#[reflect(method)]`. The arguments must match the parameters
+    /// of the method exactly, the returned value is boxed (`()` for methods that return nothing).
+    fn call_method(
+        &mut self,
+        name: &str,
+        #[allow(unused_variables)] args: Vec<Box<dyn Reflect>>,
+    ) -> Result<Box<dyn Reflect>, CallError> {
+        Err(CallError::NoSuchMethod {
+            name: name.to_string(),
+        })
+    }
+
+    /// The same as [`Reflect::call_method`], but fails for methods that take `&mut self`.
+    fn call_method_ref(
+        &self,
+        name: &str,
+        #[allow(unused_variables)] args: Vec<Box<dyn Reflect>>,
+    ) -> Result<Box<dyn Reflect>, CallError> {
+        Err(CallError::NoSuchMethod {
+            name: name.to_string(),
+        })
+    }
+
+    /// Creates a deep copy of the value. Returns `None` if the type does not support cloning.
+    ///
+    /// `#[derive(Reflect)]` implements this method for non-generic types that implement `Clone`,
+    /// use `#[reflect(no_clone)]` to opt out.
+    fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+        None
+    }
+
+    /// Creates a default value of the type. Returns `None` if the type has no default value.
+    ///
+    /// `#[derive(Reflect)]` implements this method for non-generic types that implement `Default`,
+    /// generic structs are created field by field with [`default_value`].
+    fn default_box() -> Option<Box<dyn Reflect>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Compares the value with another value. Values of different types are never equal. Returns
+    /// `None` if the type does not support comparison.
+    ///
+    /// `#[derive(Reflect)]` implements this method for non-generic types that implement
+    /// `PartialEq`, use `#[reflect(no_eq)]` to opt out.
+    fn reflect_eq(&self, #[allow(unused_variables)] other: &dyn Reflect) -> Option<bool> {
+        None
+    }
 }
 
 /// [`Reflect`] sub trait for working with slices.
@@ -295,6 +605,8 @@ pub trait ReflectArray: Reflect {
     
 /// [`Reflect`] sub trait for working with `Vec`-like types
 pub trait ReflectList: ReflectArray {
+    /// Returns type id of the items, so new items can be created for an empty list.
+    fn reflect_item_type_id(&self) -> TypeId;
     fn reflect_push(&mut self, value: Box<dyn Reflect>) -> Result<(), Box<dyn Reflect>>;
     fn reflect_pop(&mut self) -> Option<Box<dyn Reflect>>;
     fn reflect_remove(&mut self, index: usize) -> Option<Box<dyn Reflect>>;
@@ -306,6 +618,10 @@ pub trait ReflectList: ReflectArray {
 }
 
 pub trait ReflectHashMap: Reflect {
+    /// Returns type id of the keys, so new entries can be created for an empty map.
+    fn reflect_key_type_id(&self) -> TypeId;
+    /// Returns type id of the values, so new entries can be created for an empty map.
+    fn reflect_value_type_id(&self) -> TypeId;
     fn reflect_insert(
         &mut self,
         key: Box<dyn Reflect>,
@@ -325,6 +641,64 @@ pub trait ReflectHashMap: Reflect {
     fn reflect_remove(&mut self, key: &dyn Reflect, func: &mut dyn FnMut(Option<Box<dyn Reflect>>));
 }
 
+/// [`Reflect`] sub trait for working with enums. Implemented by `#[derive(Reflect)]` for every
+/// enum, so inspectors can show a list of variants and switch between them.
+pub trait ReflectEnum: Reflect {
+    /// Returns the name of the current variant.
+    fn variant_name(&self) -> &'static str;
+
+    /// Returns names of all variants in declaration order.
+    fn variant_names(&self) -> &'static [&'static str];
+
+    /// Switches the value to the variant with the given name. Fields of the new variant are
+    /// initialized with their `Default` values. Does nothing if the value already is of the
+    /// requested variant.
+    ///
+    /// `#[derive(Reflect)]` can only detect `Default` for fields of concrete types. Fields of a
+    /// generic type (such as `Some(T)` of `Option<T>`) are created with [`default_value`] instead,
+    /// and switching fails with [`SetVariantError::NoDefaultValue`] if it returns `None`.
+    fn set_variant(&mut self, name: &str) -> Result<(), SetVariantError>;
+}
+
+/// Creates a default value of a leaf type (a primitive, a string, a path), of a type with
+/// [`Reflect::default_box`] or of a type registered in the global [`TypeRegistry`]. Used by
+/// [`ReflectEnum::set_variant`] for fields of generic types.
+pub fn default_value<T: Reflect>() -> Option<T> {
+    leaf::new_leaf(TypeId::of::<T>())
+        .or_else(T::default_box)
+        .or_else(|| TypeRegistry::global().create_by_type_id(TypeId::of::<T>()))
+        .and_then(|value| value.take().ok())
+}
+
+/// An error that can occur in [`ReflectEnum::set_variant`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetVariantError {
+    /// The enum has no variant with the given name.
+    UnknownVariant(String),
+
+    /// A field of the new variant has a type that does not implement `Default`.
+    NoDefaultValue {
+        /// Name of the variant.
+        variant: &'static str,
+        /// Name of the field (or its index for tuple variants).
+        field: &'static str,
+    },
+}
+
+impl Display for SetVariantError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SetVariantError::UnknownVariant(name) => write!(f, "unknown variant {name}"),
+            SetVariantError::NoDefaultValue { variant, field } => write!(
+                f,
+                "field {field} of variant {variant} has no default value"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SetVariantError {}
+
 /// An error returned from a failed path string query.
 #[derive(Debug, PartialEq, Eq)]
 pub enum ReflectPathError<'a> {
@@ -887,6 +1261,56 @@ macro_rules! delegate_reflect {
         fn as_list_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectList>)) {
             self.deref_mut().as_list_mut(func)
         }
+
+        fn as_enum(&self, func: &mut dyn FnMut(Option<&dyn ReflectEnum>)) {
+            self.deref().as_enum(func)
+        }
+
+        fn as_enum_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectEnum>)) {
+            self.deref_mut().as_enum_mut(func)
+        }
+
+        fn as_inheritable_variable(
+            &self,
+            func: &mut dyn FnMut(Option<&dyn ReflectInheritableVariable>),
+        ) {
+            self.deref().as_inheritable_variable(func)
+        }
+
+        fn as_inheritable_variable_mut(
+            &mut self,
+            func: &mut dyn FnMut(Option<&mut dyn ReflectInheritableVariable>),
+        ) {
+            self.deref_mut().as_inheritable_variable_mut(func)
+        }
+
+        fn methods_info(&self) -> &'static [MethodInfo] {
+            self.deref().methods_info()
+        }
+
+        fn call_method(
+            &mut self,
+            name: &str,
+            args: Vec<Box<dyn Reflect>>,
+        ) -> Result<Box<dyn Reflect>, CallError> {
+            self.deref_mut().call_method(name, args)
+        }
+
+        fn call_method_ref(
+            &self,
+            name: &str,
+            args: Vec<Box<dyn Reflect>>,
+        ) -> Result<Box<dyn Reflect>, CallError> {
+            self.deref().call_method_ref(name, args)
+        }
+
+        fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+            self.deref().clone_box()
+        }
+
+        fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+            self.deref().reflect_eq(other)
+        }
     };
 }
 
@@ -964,3 +1388,326 @@ macro_rules! newtype_reflect {
     };
 }*/
 
+
+#[cfg(test)]
+mod tests {
+    use super::prelude::*;
+
+    #[derive(Reflect, Debug, Default, Clone, PartialEq)]
+    struct Clip {
+        name: String,
+        keys: Vec<f32>,
+    }
+
+    #[derive(Reflect, Debug, Clone, PartialEq)]
+    #[reflect(no_clone, no_eq)]
+    struct Hidden {
+        value: u32,
+    }
+
+    #[derive(Reflect, Debug)]
+    struct NotCloneable {
+        value: u32,
+    }
+
+    #[derive(Reflect, Debug, Clone, PartialEq)]
+    struct Generic<T> {
+        value: T,
+    }
+
+    #[derive(Reflect, Debug, PartialEq)]
+    enum Shape {
+        Circle { radius: f32 },
+        Rect(f32, f32),
+        Empty,
+        Custom(Hidden),
+    }
+
+    #[derive(Reflect, Debug, Default)]
+    struct Light {
+        #[reflect(min_value = 0.0, max_value = 10.0, step = 0.5, precision = 2)]
+        #[reflect(display_name = "Light Intensity", description = "Brightness.")]
+        intensity: f32,
+        #[reflect(min_value = 1.0, setter = "set_samples")]
+        samples: u32,
+        #[reflect(read_only)]
+        id: u64,
+        #[reflect(immutable_collection)]
+        channels: Vec<u8>,
+    }
+
+    impl Light {
+        fn set_samples(&mut self, samples: u32) -> u32 {
+            std::mem::replace(&mut self.samples, samples)
+        }
+    }
+
+    #[derive(Reflect, Debug, Default)]
+    #[reflect(methods)]
+    struct Door {
+        open: bool,
+        code: u32,
+    }
+
+    #[reflect_methods]
+    impl Door {
+        /// Opens the door if the code is correct.
+        #[reflect(method)]
+        fn unlock(&mut self, code: u32) -> bool {
+            self.open = code == self.code;
+            self.open
+        }
+
+        #[reflect(method, name = "is_open")]
+        fn opened(&self) -> bool {
+            self.open
+        }
+
+        #[reflect(method)]
+        fn with_code(code: u32, open: bool) -> Door {
+            Door { open, code }
+        }
+
+        #[allow(dead_code)]
+        fn not_reflected(&self) {}
+    }
+
+    fn set(light: &mut Light, name: &str, value: Box<dyn Reflect>) -> Result<(), SetFieldError> {
+        let mut result = None;
+        light.set_field(name, value, &mut |r| result = Some(r.map(|_| ())));
+        result.unwrap()
+    }
+
+    #[test]
+    fn clone_box_and_reflect_eq() {
+        let clip = Clip {
+            name: "Walk".to_string(),
+            keys: vec![0.0, 0.5, 1.0],
+        };
+        let erased: Box<dyn Reflect> = Box::new(clip.clone());
+
+        let copy = erased.clone_box().unwrap();
+        assert_eq!(copy.reflect_eq(&clip), Some(true));
+        assert_eq!(copy.reflect_eq(&123u32), Some(false));
+        assert_eq!(copy.take::<Clip>().unwrap(), clip);
+
+        let mut other = clip.clone();
+        other.keys[1] = 0.75;
+        assert_eq!(erased.reflect_eq(&other), Some(false));
+
+        let items = [1u8, 2, 3];
+        assert_eq!(items.clone_box().unwrap().reflect_eq(&items), Some(true));
+        assert_eq!(vec![clip.clone()].reflect_eq(&vec![other]), Some(false));
+
+        assert!(Hidden { value: 1 }.clone_box().is_none());
+        assert!(Hidden { value: 1 }.reflect_eq(&Hidden { value: 1 }).is_none());
+        assert!(NotCloneable { value: 1 }.clone_box().is_none());
+        assert!(vec![NotCloneable { value: 1 }].clone_box().is_none());
+
+        // Generic types are cloned and compared field by field.
+        let generic = Generic {
+            value: Some(vec![1u32, 2]),
+        };
+        let copy = generic.clone_box().unwrap();
+        assert_eq!(copy.reflect_eq(&generic), Some(true));
+        assert_eq!(copy.take::<Generic<Option<Vec<u32>>>>().unwrap(), generic);
+        assert_eq!(generic.reflect_eq(&Generic::<Option<Vec<u32>>> { value: None }), Some(false));
+        assert!(Generic { value: NotCloneable { value: 1 } }
+            .clone_box()
+            .is_none());
+
+        let map = [("a".to_string(), (1u8, 2.5f32))]
+            .into_iter()
+            .collect::<std::collections::HashMap<_, _>>();
+        let copy = map.clone_box().unwrap();
+        assert_eq!(copy.reflect_eq(&map), Some(true));
+        assert_eq!(copy.take::<std::collections::HashMap<String, (u8, f32)>>().unwrap(), map);
+        assert_eq!((1u8, 2.5f32).reflect_eq(&(1u8, 3.0f32)), Some(false));
+    }
+
+    #[test]
+    fn reflect_enum_switches_variants() {
+        let mut shape = Shape::Circle { radius: 2.0 };
+        assert_eq!(shape.variant_name(), "Circle");
+        assert_eq!(shape.variant_names(), &["Circle", "Rect", "Empty", "Custom"]);
+
+        let erased: &mut dyn Reflect = &mut shape;
+        let mut result = None;
+        erased.as_enum_mut(&mut |value| result = value.map(|value| value.set_variant("Rect")));
+        assert_eq!(result, Some(Ok(())));
+        assert_eq!(shape, Shape::Rect(0.0, 0.0));
+
+        shape = Shape::Circle { radius: 2.0 };
+        assert_eq!(shape.set_variant("Circle"), Ok(()));
+        assert_eq!(shape, Shape::Circle { radius: 2.0 });
+        assert_eq!(shape.set_variant("Empty"), Ok(()));
+        assert_eq!(shape, Shape::Empty);
+
+        assert_eq!(
+            shape.set_variant("Triangle"),
+            Err(SetVariantError::UnknownVariant("Triangle".to_string()))
+        );
+        assert_eq!(
+            shape.set_variant("Custom"),
+            Err(SetVariantError::NoDefaultValue {
+                variant: "Custom",
+                field: "0"
+            })
+        );
+        assert_eq!(shape, Shape::Empty);
+
+        // Fields of generic types are created with `default_value`.
+        let mut option = None::<f32>;
+        assert_eq!(option.variant_names(), &["Some", "None"]);
+        assert_eq!(option.set_variant("Some"), Ok(()));
+        assert_eq!(option, Some(0.0));
+        assert_eq!(option.set_variant("None"), Ok(()));
+        assert_eq!(option, None);
+        let mut option = None::<Vec<u32>>;
+        assert_eq!(option.set_variant("Some"), Ok(()));
+        assert_eq!(option, Some(Vec::new()));
+        let mut option = None::<Clip>;
+        assert_eq!(option.set_variant("Some"), Ok(()));
+        assert_eq!(option, Some(Clip::default()));
+        let mut option = None::<Generic<Clip>>;
+        assert_eq!(option.set_variant("Some"), Ok(()));
+        assert_eq!(option.map(|generic| generic.value), Some(Clip::default()));
+        let mut option = None::<Hidden>;
+        assert_eq!(
+            option.set_variant("Some"),
+            Err(SetVariantError::NoDefaultValue {
+                variant: "Some",
+                field: "0"
+            })
+        );
+
+        let mut has_enum = false;
+        Clip {
+            name: String::new(),
+            keys: Vec::new(),
+        }
+        .as_enum(&mut |value| has_enum = value.is_some());
+        assert!(!has_enum);
+    }
+
+    #[test]
+    fn set_field_enforces_constraints() {
+        let mut light = Light::default();
+
+        light.field_info("intensity", &mut |info| {
+            let info = info.unwrap();
+            assert_eq!(info.display_name, "Light Intensity");
+            assert_eq!(info.description, "Brightness.");
+            assert_eq!((info.step, info.precision), (Some(0.5), Some(2)));
+            assert_eq!(
+                info.constraints(),
+                FieldConstraints {
+                    read_only: false,
+                    immutable_collection: false,
+                    min_value: Some(0.0),
+                    max_value: Some(10.0),
+                }
+            );
+        });
+        light.field_info("missing", &mut |info| assert!(info.is_none()));
+
+        set(&mut light, "intensity", Box::new(2.5f32)).unwrap();
+        set(&mut light, "samples", Box::new(4u32)).unwrap();
+        assert_eq!((light.intensity, light.samples), (2.5, 4));
+
+        let err = set(&mut light, "intensity", Box::new(11.0f32)).unwrap_err();
+        assert!(matches!(
+            err,
+            SetFieldError::OutOfRange {
+                min_value: Some(0.0),
+                max_value: Some(10.0),
+                ..
+            }
+        ));
+        assert_eq!(err.into_value().take::<f32>().unwrap(), 11.0);
+        assert!(matches!(
+            set(&mut light, "samples", Box::new(0u32)),
+            Err(SetFieldError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            set(&mut light, "id", Box::new(5u64)),
+            Err(SetFieldError::ReadOnly { .. })
+        ));
+        assert!(matches!(
+            set(&mut light, "intensity", Box::new(1u8)),
+            Err(SetFieldError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            set(&mut light, "color", Box::new(1u8)),
+            Err(SetFieldError::NoSuchField { .. })
+        ));
+        assert_eq!((light.intensity, light.samples, light.id), (2.5, 4, 0));
+
+        light.channels = vec![1, 2];
+        set(&mut light, "channels", Box::new(vec![3u8, 4])).unwrap();
+        assert!(matches!(
+            set(&mut light, "channels", Box::new(vec![5u8])),
+            Err(SetFieldError::ImmutableCollection { .. })
+        ));
+        assert_eq!(light.channels, [3, 4]);
+    }
+
+    #[test]
+    fn call_reflected_methods() {
+        let mut door = Door {
+            open: false,
+            code: 42,
+        };
+        let object: &mut dyn Reflect = &mut door;
+
+        let methods = object.methods_info();
+        assert_eq!(
+            methods.iter().map(|m| m.name).collect::<Vec<_>>(),
+            ["unlock", "is_open", "with_code"]
+        );
+        assert_eq!(methods[0].doc, " Opens the door if the code is correct.");
+        assert_eq!(methods[0].receiver, MethodReceiver::Mut);
+        assert_eq!(methods[0].params, &[ParamInfo { name: "code", type_name: "u32" }]);
+        assert_eq!(methods[2].receiver, MethodReceiver::None);
+        assert_eq!(methods[2].return_type, "Door");
+
+        let opened = object.call_method("unlock", vec![Box::new(42u32)]).unwrap();
+        assert!(opened.take::<bool>().unwrap());
+        let opened = object.call_method_ref("is_open", vec![]).unwrap();
+        assert!(opened.take::<bool>().unwrap());
+
+        let door = object
+            .call_method_ref("with_code", vec![Box::new(7u32), Box::new(false)])
+            .unwrap()
+            .take::<Door>()
+            .unwrap();
+        assert_eq!((door.code, door.open), (7, false));
+
+        assert_eq!(
+            object.call_method_ref("unlock", vec![Box::new(42u32)]).unwrap_err(),
+            CallError::MutableReceiver { method: "unlock" }
+        );
+        assert_eq!(
+            object.call_method("unlock", vec![]).unwrap_err(),
+            CallError::ArgCount {
+                method: "unlock",
+                expected: 1,
+                actual: 0
+            }
+        );
+        assert!(matches!(
+            object.call_method("unlock", vec![Box::new(42i64)]),
+            Err(CallError::ArgType { index: 0, .. })
+        ));
+        assert!(matches!(
+            object.call_method("not_reflected", vec![]),
+            Err(CallError::NoSuchMethod { .. })
+        ));
+        assert!(Clip {
+            name: String::new(),
+            keys: Vec::new()
+        }
+        .methods_info()
+        .is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+//! Helpers for "leaf" values - primitives, strings and other plain values that have no reflected
+//! fields but can be created, copied and compared without knowing their type at compile time.
+
+use crate::reflect::prelude::Reflect;
+use crate::sstorage::ImmutableString;
+use crate::UUID;
+
+use std::{
+    any::{Any, TypeId},
+    path::PathBuf,
+};
+
+macro_rules! define_leaves {
+    ( $( $ty:ty => $default:expr ),* $(,)? ) => {
+        /// Returns `true` if the type is a leaf type.
+        pub(crate) fn is_leaf(type_id: TypeId) -> bool {
+            $( type_id == TypeId::of::<$ty>() )||*
+        }
+
+        /// Creates a default value of a leaf type.
+        pub(crate) fn new_leaf(type_id: TypeId) -> Option<Box<dyn Reflect>> {
+            $(
+                if type_id == TypeId::of::<$ty>() {
+                    let value: $ty = $default;
+                    return Some(Box::new(value));
+                }
+            )*
+            None
+        }
+
+        /// Copies a leaf value.
+        pub(crate) fn clone_leaf(value: &dyn Any) -> Option<Box<dyn Reflect>> {
+            $(
+                if let Some(value) = value.downcast_ref::<$ty>() {
+                    return Some(Box::new(value.clone()));
+                }
+            )*
+            None
+        }
+
+        fn leaf_eq_impl(a: &dyn Any, b: &dyn Any) -> Option<bool> {
+            $(
+                if let (Some(a), Some(b)) = (a.downcast_ref::<$ty>(), b.downcast_ref::<$ty>()) {
+                    return Some(a == b);
+                }
+            )*
+            None
+        }
+    };
+}
+
+define_leaves! {
+    bool => false,
+    char => '\0',
+    u8 => 0, u16 => 0, u32 => 0, u64 => 0, usize => 0,
+    i8 => 0, i16 => 0, i32 => 0, i64 => 0, isize => 0,
+    f32 => 0.0, f64 => 0.0,
+    String => String::new(),
+    ImmutableString => ImmutableString::default(),
+    PathBuf => PathBuf::new(),
+    UUID => UUID::create_null(),
+}
+
+/// Compares two leaf values. Floating point numbers are compared by their bits, so a NaN is equal
+/// to itself. Returns `None` if the values are not leaves of the same type.
+pub(crate) fn leaf_eq(a: &dyn Any, b: &dyn Any) -> Option<bool> {
+    if let (Some(a), Some(b)) = (a.downcast_ref::<f32>(), b.downcast_ref::<f32>()) {
+        return Some(a.to_bits() == b.to_bits());
+    }
+    if let (Some(a), Some(b)) = (a.downcast_ref::<f64>(), b.downcast_ref::<f64>()) {
+        return Some(a.to_bits() == b.to_bits());
+    }
+    leaf_eq_impl(a, b)
+}
+
+/// Returns type id of the actual value (of the inner value for wrappers such as `Box<T>`).
+pub(crate) fn concrete_type_id(value: &dyn Reflect) -> TypeId {
+    let mut type_id = TypeId::of::<()>();
+    value.as_any(&mut |any| type_id = any.type_id());
+    type_id
+}
+
+/// Converts a numeric value to `f64`. Returns `None` for non-numeric values.
+pub(crate) fn numeric_value(value: &dyn Any) -> Option<f64> {
+    macro_rules! try_numeric {
+        ($($ty:ty),*) => {
+            $(
+                if let Some(value) = value.downcast_ref::<$ty>() {
+                    return Some(*value as f64);
+                }
+            )*
+        };
+    }
+
+    try_numeric!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+    None
+}
@@ -0,0 +1,205 @@
+//! Reflected methods.
+//!
+//! Methods marked with `#[reflect(method)]` inside an impl block annotated with
+//! `#[reflect_methods]` can be listed and called by name on a `dyn Reflect`. Arguments and return
+//! values are passed as `Box<dyn Reflect>`, arguments are checked for count and type before the
+//! call. The type itself has to opt in with `#[reflect(methods)]`, so `#[derive(Reflect)]` forwards
+//! [`Reflect::call_method`] and friends to the generated [`ReflectMethods`] impl.
+//!
+//! ```
+//! # use velcro_rtti::reflect::prelude::*;
+//! #[derive(Reflect, Debug, Default)]
+//! #[reflect(methods)]
+//! struct Counter {
+//!     value: i32,
+//! }
+//!
+//! #[reflect_methods]
+//! impl Counter {
+//!     /// Adds the amount to the counter and returns the new value.
+//!     #[reflect(method)]
+//!     fn add(&mut self, amount: i32) -> i32 {
+//!         self.value += amount;
+//!         self.value
+//!     }
+//! }
+//!
+//! let mut counter = Counter::default();
+//! let object: &mut dyn Reflect = &mut counter;
+//! assert_eq!(object.methods_info()[0].name, "add");
+//!
+//! let result = object.call_method("add", vec![Box::new(5i32)]).unwrap();
+//! assert_eq!(result.take::<i32>().unwrap(), 5);
+//! assert!(object.call_method("add", vec![Box::new(5.0f32)]).is_err());
+//! ```
+
+use crate::reflect::prelude::Reflect;
+
+use std::fmt::{self, Display, Formatter};
+
+/// How a reflected method takes `self`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodReceiver {
+    /// An associated function without `self`.
+    None,
+    /// `&self`
+    Ref,
+    /// `&mut self`
+    Mut,
+}
+
+/// A parameter of a reflected method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamInfo {
+    /// Name of the parameter.
+    pub name: &'static str,
+
+    /// Type of the parameter as written in the source code.
+    pub type_name: &'static str,
+}
+
+/// Description of a reflected method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodInfo {
+    /// Name of the method, the same as used in [`Reflect::call_method`].
+    pub name: &'static str,
+
+    /// Doc comment content.
+    pub doc: &'static str,
+
+    /// How the method takes `self`.
+    pub receiver: MethodReceiver,
+
+    /// Parameters of the method, excluding `self`.
+    pub params: &'static [ParamInfo],
+
+    /// Return type as written in the source code, `()` if the method returns nothing.
+    pub return_type: &'static str,
+}
+
+/// An error that can occur during a reflected method call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    /// There is no method with the given name.
+    NoSuchMethod { name: String },
+
+    /// The method takes `&mut self`, but was called with [`Reflect::call_method_ref`].
+    MutableReceiver { method: &'static str },
+
+    /// Wrong amount of arguments.
+    ArgCount {
+        method: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// An argument has a type different from the type of the parameter.
+    ArgType {
+        method: &'static str,
+        index: usize,
+        expected: &'static str,
+        actual: &'static str,
+    },
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::NoSuchMethod { name } => write!(f, "no method {name}"),
+            CallError::MutableReceiver { method } => {
+                write!(f, "method {method} requires mutable access")
+            }
+            CallError::ArgCount {
+                method,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "method {method} takes {expected} argument(s), but {actual} were given"
+            ),
+            CallError::ArgType {
+                method,
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "argument {index} of method {method} must be {expected}, but {actual} was given"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+/// Methods of a type, implemented by `#[reflect_methods]`.
+pub trait ReflectMethods {
+    /// Returns all reflected methods of the type, in declaration order.
+    fn methods() -> &'static [MethodInfo]
+    where
+        Self: Sized;
+
+    /// Calls a method with any receiver.
+    fn call_method(
+        &mut self,
+        name: &str,
+        args: Vec<Box<dyn Reflect>>,
+    ) -> Result<Box<dyn Reflect>, CallError>;
+
+    /// Calls a method that does not need mutable access.
+    fn call_method_ref(
+        &self,
+        name: &str,
+        args: Vec<Box<dyn Reflect>>,
+    ) -> Result<Box<dyn Reflect>, CallError>;
+}
+
+/// Arguments of a method call, used by the code generated with `#[reflect_methods]`.
+pub struct MethodArgs {
+    method: &'static str,
+    index: usize,
+    args: std::vec::IntoIter<Box<dyn Reflect>>,
+}
+
+impl MethodArgs {
+    /// Checks the amount of the arguments.
+    pub fn new(
+        method: &'static str,
+        expected: usize,
+        args: Vec<Box<dyn Reflect>>,
+    ) -> Result<Self, CallError> {
+        if args.len() != expected {
+            return Err(CallError::ArgCount {
+                method,
+                expected,
+                actual: args.len(),
+            });
+        }
+
+        Ok(Self {
+            method,
+            index: 0,
+            args: args.into_iter(),
+        })
+    }
+
+    /// Takes the next argument.
+    pub fn take_next<T: Reflect>(&mut self) -> Result<T, CallError> {
+        let index = self.index;
+        self.index += 1;
+
+        let arg = self.args.next().ok_or(CallError::ArgCount {
+            method: self.method,
+            expected: index + 1,
+            actual: index,
+        })?;
+        let actual = (*arg).type_name();
+
+        arg.take::<T>().map_err(|_| CallError::ArgType {
+            method: self.method,
+            index,
+            expected: std::any::type_name::<T>(),
+            actual,
+        })
+    }
+}
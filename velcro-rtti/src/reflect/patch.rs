@@ -0,0 +1,521 @@
+//! Reflective diff and patch.
+//!
+//! [`diff`] compares two values of the same type and produces a list of [`ReflectChange`]s - pairs
+//! of a path (in the syntax understood by [`ResolvePath`]) and a new value for that path.
+//! [`apply_patch`] replays such changes on another value using [`ResolvePath::resolve_path_mut`]
+//! and [`Reflect::set`]. This is the basis for undo/redo, prefab overrides and delta replication.
+//!
+//! Changes are produced at the deepest possible level: struct fields, array items and hash map
+//! values (with string keys) are compared recursively, and only leaf values that differ are
+//! emitted. If the structure of a value has changed (an enum variant or the length of a list is
+//! different, a hash map has other keys, etc.), the value has to be replaced as a whole, which is
+//! only possible for leaf values and values that support [`Reflect::clone_box`]. Values without
+//! reflected fields are compared with [`Reflect::reflect_eq`]. If a value can't be copied or
+//! compared, [`DiffError::Unsupported`] is returned.
+//!
+//! ```
+//! # use velcro_rtti::reflect::{prelude::*, apply_patch, diff};
+//! #[derive(Reflect, Debug, Clone, PartialEq)]
+//! struct Transform {
+//!     position: [f32; 3],
+//!     scale: f32,
+//! }
+//!
+//! let old = Transform { position: [0.0; 3], scale: 1.0 };
+//! let new = Transform { position: [0.0, 2.0, 0.0], scale: 1.0 };
+//!
+//! let changes = diff(&old, &new).unwrap();
+//! assert_eq!(changes.len(), 1);
+//! assert_eq!(changes[0].path, "position[1]");
+//!
+//! let mut copy = old.clone();
+//! apply_patch(&mut copy, changes).unwrap();
+//! assert_eq!(copy, new);
+//! ```
+
+use crate::reflect::{
+    leaf::{clone_leaf, concrete_type_id, is_leaf, leaf_eq},
+    prelude::{Reflect, ReflectArray, ReflectHashMap},
+    ResolvePath,
+};
+use crate::sstorage::ImmutableString;
+
+use std::fmt::{self, Display, Formatter};
+
+/// A single change of a value at the given path.
+#[derive(Debug)]
+pub struct ReflectChange {
+    /// Path to the changed value, empty path means the root value.
+    pub path: String,
+
+    /// New value.
+    pub value: Box<dyn Reflect>,
+}
+
+/// An error that may occur during [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffError {
+    /// Values at the path have different types.
+    TypeMismatch {
+        /// Path to the values.
+        path: String,
+        /// Type of the old value.
+        old: &'static str,
+        /// Type of the new value.
+        new: &'static str,
+    },
+    /// The value at the path has to be replaced as a whole, but it can't be copied, or it has no
+    /// reflected fields and can't be compared with [`Reflect::reflect_eq`].
+    Unsupported {
+        /// Path to the value.
+        path: String,
+        /// Type of the value.
+        type_name: &'static str,
+    },
+}
+
+impl Display for DiffError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::TypeMismatch { path, old, new } => {
+                write!(f, "type mismatch at `{path}`: {old} vs {new}")
+            }
+            DiffError::Unsupported { path, type_name } => {
+                write!(f, "unable to copy or compare a value of {type_name} at `{path}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+/// An error that may occur during [`apply_patch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The path of a change can't be resolved.
+    InvalidPath {
+        /// Path of the change.
+        path: String,
+        /// Description of the resolution error.
+        reason: String,
+    },
+    /// The value of a change has a different type than the value at the path.
+    TypeMismatch {
+        /// Path of the change.
+        path: String,
+        /// Type of the value at the path.
+        expected: &'static str,
+        /// Type of the value of the change.
+        actual: &'static str,
+    },
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::InvalidPath { path, reason } => {
+                write!(f, "invalid path `{path}`: {reason}")
+            }
+            PatchError::TypeMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(f, "unable to set {actual} at `{path}` of type {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Compares two values and returns a list of changes that turns `old` into `new`.
+pub fn diff(old: &dyn Reflect, new: &dyn Reflect) -> Result<Vec<ReflectChange>, DiffError> {
+    let mut changes = Vec::new();
+    diff_recursive(String::new(), old, new, &mut changes)?;
+    Ok(changes)
+}
+
+/// Applies changes produced by [`diff`] to the value. Changes are applied in order, the patch stops
+/// at the first failed change (the changes before it stay applied).
+pub fn apply_patch(
+    target: &mut dyn Reflect,
+    changes: Vec<ReflectChange>,
+) -> Result<(), PatchError> {
+    for ReflectChange { path, value } in changes {
+        apply_change(target, &path, value)?;
+    }
+    Ok(())
+}
+
+fn apply_change(
+    target: &mut dyn Reflect,
+    path: &str,
+    value: Box<dyn Reflect>,
+) -> Result<(), PatchError> {
+    let set = |target: &mut dyn Reflect, value: Box<dyn Reflect>| {
+        let expected = target.type_name();
+        target
+            .set(value)
+            .map(|_| ())
+            .map_err(|value| PatchError::TypeMismatch {
+                path: path.to_string(),
+                expected,
+                actual: value.type_name(),
+            })
+    };
+
+    if path.is_empty() {
+        return set(target, value);
+    }
+
+    let mut value = Some(value);
+    let mut result = None;
+    target.resolve_path_mut(path, &mut |resolved| {
+        result = Some(match resolved {
+            Ok(field) => set(field, value.take().unwrap()),
+            Err(err) => Err(PatchError::InvalidPath {
+                path: path.to_string(),
+                reason: err.to_string(),
+            }),
+        });
+    });
+    result.unwrap_or_else(|| {
+        Err(PatchError::InvalidPath {
+            path: path.to_string(),
+            reason: "the path was not resolved".to_string(),
+        })
+    })
+}
+
+pub(crate) fn field_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+fn replace(
+    path: String,
+    new: &dyn Reflect,
+    changes: &mut Vec<ReflectChange>,
+) -> Result<(), DiffError> {
+    let mut value = None;
+    new.as_any(&mut |any| value = clone_leaf(any));
+    match value.or_else(|| new.clone_box()) {
+        Some(value) => {
+            changes.push(ReflectChange { path, value });
+            Ok(())
+        }
+        None => Err(DiffError::Unsupported {
+            path,
+            type_name: new.type_name(),
+        }),
+    }
+}
+
+fn diff_recursive(
+    path: String,
+    old: &dyn Reflect,
+    new: &dyn Reflect,
+    changes: &mut Vec<ReflectChange>,
+) -> Result<(), DiffError> {
+    let type_id = concrete_type_id(old);
+    if type_id != concrete_type_id(new) {
+        return Err(DiffError::TypeMismatch {
+            path,
+            old: old.type_name(),
+            new: new.type_name(),
+        });
+    }
+
+    if is_leaf(type_id) {
+        let mut equal = false;
+        old.as_any(&mut |a| new.as_any(&mut |b| equal = leaf_eq(a, b).unwrap_or(false)));
+        return if equal {
+            Ok(())
+        } else {
+            replace(path, new, changes)
+        };
+    }
+
+    let mut result = None;
+    old.as_array(&mut |old_array| {
+        if let Some(old_array) = old_array {
+            new.as_array(&mut |new_array| {
+                if let Some(new_array) = new_array {
+                    result = Some(diff_arrays(&path, old_array, new_array, changes));
+                }
+            })
+        }
+    });
+    if let Some(result) = result {
+        return result;
+    }
+
+    old.as_hash_map(&mut |old_map| {
+        if let Some(old_map) = old_map {
+            new.as_hash_map(&mut |new_map| {
+                if let Some(new_map) = new_map {
+                    result = Some(diff_hash_maps(&path, old_map, new_map, changes));
+                }
+            })
+        }
+    });
+    if let Some(result) = result {
+        return result;
+    }
+
+    let mut same_layout = false;
+    let mut has_fields = false;
+    old.fields_info(&mut |old_fields| {
+        new.fields_info(&mut |new_fields| {
+            has_fields = !old_fields.is_empty() || !new_fields.is_empty();
+            same_layout = old_fields.len() == new_fields.len()
+                && old_fields
+                    .iter()
+                    .zip(new_fields)
+                    .all(|(a, b)| a.name == b.name);
+            if same_layout && has_fields {
+                result = Some(old_fields.iter().zip(new_fields).try_for_each(|(a, b)| {
+                    diff_recursive(
+                        field_path(&path, a.name),
+                        a.reflect_value,
+                        b.reflect_value,
+                        changes,
+                    )
+                }));
+            }
+        })
+    });
+
+    match result {
+        Some(result) => result,
+        // Opaque values (without fields) can only be compared with `reflect_eq`.
+        None if !has_fields => match old.reflect_eq(new) {
+            Some(true) => Ok(()),
+            Some(false) => replace(path, new, changes),
+            None => Err(DiffError::Unsupported {
+                path,
+                type_name: new.type_name(),
+            }),
+        },
+        None => replace(path, new, changes),
+    }
+}
+
+fn diff_arrays(
+    path: &str,
+    old: &dyn ReflectArray,
+    new: &dyn ReflectArray,
+    changes: &mut Vec<ReflectChange>,
+) -> Result<(), DiffError> {
+    if old.reflect_len() != new.reflect_len() {
+        return replace(path.to_string(), new, changes);
+    }
+
+    for i in 0..old.reflect_len() {
+        if let (Some(a), Some(b)) = (old.reflect_index(i), new.reflect_index(i)) {
+            diff_recursive(format!("{path}[{i}]"), a, b, changes)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns a string representation of a hash map key, only string keys can be used in paths.
+pub(crate) fn key_string(key: &dyn Reflect) -> Option<String> {
+    let mut string = None;
+    key.as_any(&mut |any| {
+        string = any.downcast_ref::<String>().cloned().or_else(|| {
+            any.downcast_ref::<ImmutableString>()
+                .map(|s| s.to_mutable())
+        })
+    });
+    string
+}
+
+fn diff_hash_maps(
+    path: &str,
+    old: &dyn ReflectHashMap,
+    new: &dyn ReflectHashMap,
+    changes: &mut Vec<ReflectChange>,
+) -> Result<(), DiffError> {
+    let mut keys = Vec::new();
+    let mut same_keys = old.reflect_len() == new.reflect_len();
+    for i in 0..new.reflect_len() {
+        let Some((key, _)) = new.reflect_get_at(i) else {
+            continue;
+        };
+        let mut exists = false;
+        old.reflect_get(key, &mut |value| exists = value.is_some());
+        match key_string(key) {
+            Some(string) if exists && !string.contains(']') => keys.push((string, i)),
+            _ => same_keys = false,
+        }
+    }
+
+    if !same_keys {
+        return replace(path.to_string(), new, changes);
+    }
+
+    // Hash map iteration order is unspecified, sort the keys to make the diff deterministic.
+    keys.sort();
+    for (string, i) in keys {
+        let Some((key, new_value)) = new.reflect_get_at(i) else {
+            continue;
+        };
+        let mut result = Ok(());
+        old.reflect_get(key, &mut |old_value| {
+            if let Some(old_value) = old_value {
+                result = diff_recursive(format!("{path}[{string}]"), old_value, new_value, changes);
+            }
+        });
+        result?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::prelude::*;
+    use std::{collections::HashMap, time::Duration};
+
+    #[derive(Reflect, Debug, Clone, PartialEq)]
+    struct Material {
+        name: String,
+        color: [u8; 4],
+        properties: HashMap<String, f32>,
+    }
+
+    #[derive(Reflect, Debug, Clone, PartialEq)]
+    struct Model {
+        visible: bool,
+        lods: Vec<f32>,
+        material: Material,
+    }
+
+    #[derive(Reflect, Debug)]
+    #[reflect(hide_all)]
+    struct Opaque;
+
+    fn model() -> Model {
+        Model {
+            visible: true,
+            lods: vec![10.0, 50.0],
+            material: Material {
+                name: "Stone".to_string(),
+                color: [255, 255, 255, 255],
+                properties: [
+                    ("roughness".to_string(), 0.5),
+                    ("metallic".to_string(), 0.0),
+                ]
+                .into_iter()
+                .collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn diff_and_patch_round_trip() {
+        let old = model();
+        let mut new = model();
+        new.visible = false;
+        new.lods[1] = 75.0;
+        new.material.color[2] = 0;
+        *new.material.properties.get_mut("metallic").unwrap() = 1.0;
+        new.material
+            .properties
+            .insert("roughness".to_string(), f32::NAN);
+
+        let changes = diff(&old, &new).unwrap();
+        let paths = changes.iter().map(|c| c.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "visible",
+                "lods[1]",
+                "material.color[2]",
+                "material.properties[metallic]",
+                "material.properties[roughness]",
+            ]
+        );
+
+        let mut patched = old.clone();
+        apply_patch(&mut patched, changes).unwrap();
+        assert!(patched.material.properties["roughness"].is_nan());
+        patched
+            .material
+            .properties
+            .insert("roughness".to_string(), 0.0);
+        new.material.properties.insert("roughness".to_string(), 0.0);
+        assert_eq!(patched, new);
+
+        assert!(diff(&new, &new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn structural_changes_and_bad_patches() {
+        let old = model();
+        let mut new = model();
+        new.lods.push(100.0);
+        let changes = diff(&old, &new).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "lods");
+        let mut patched = old.clone();
+        apply_patch(&mut patched, changes).unwrap();
+        assert_eq!(patched, new);
+
+        // Maps with other keys and enums with other variants are replaced as a whole.
+        let mut new = model();
+        new.material.properties.insert("emission".to_string(), 1.0);
+        let changes = diff(&old, &new).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "material.properties");
+        let mut patched = old.clone();
+        apply_patch(&mut patched, changes).unwrap();
+        assert_eq!(patched, new);
+
+        let changes = diff(&Some(1u32), &None::<u32>).unwrap();
+        let mut patched = Some(1u32);
+        apply_patch(&mut patched, changes).unwrap();
+        assert_eq!(patched, None);
+        assert_eq!(diff(&Some(1u32), &Some(2u32)).unwrap().len(), 1);
+        assert!(diff(&None::<u32>, &None::<u32>).unwrap().is_empty());
+        // Opaque values are compared with `reflect_eq`, values that can't be compared are not
+        // guessed to be equal or different.
+        let changes = diff(&Duration::from_secs(1), &Duration::from_secs(2)).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(diff(&Duration::from_secs(1), &Duration::from_secs(1))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            diff(&Opaque, &Opaque).unwrap_err(),
+            DiffError::Unsupported {
+                path: String::new(),
+                type_name: std::any::type_name::<Opaque>(),
+            }
+        );
+
+        let mut target = model();
+        assert!(matches!(
+            apply_patch(
+                &mut target,
+                vec![ReflectChange {
+                    path: "material.shininess".to_string(),
+                    value: Box::new(1.0f32),
+                }]
+            ),
+            Err(PatchError::InvalidPath { .. })
+        ));
+        assert!(matches!(
+            apply_patch(
+                &mut target,
+                vec![ReflectChange {
+                    path: "visible".to_string(),
+                    value: Box::new(1u32),
+                }]
+            ),
+            Err(PatchError::TypeMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,1129 @@
+//! Serde bridge for [`Reflect`] types.
+//!
+//! [`ReflectSerializer`] walks any `dyn Reflect` and feeds it to a serde serializer, while
+//! [`ReflectDeserializer`] fills an existing `dyn Reflect` from a serde deserializer, so any type
+//! with `#[derive(Reflect)]` can be stored in JSON, TOML, bincode, etc. without hand-written impls.
+//!
+//! The mapping is the following:
+//!
+//! - Primitives, strings, [`ImmutableString`], [`PathBuf`] and [`UUID`] are written as serde
+//!   primitives (UUID as a string in `{xxxxxxxx-...}` form).
+//! - Lists ([`Reflect::as_list`]) are written as sequences, fixed-size arrays
+//!   ([`Reflect::as_array`]) as tuples, so formats like bincode don't store their length.
+//! - Hash maps ([`Reflect::as_hash_map`]) are written as maps.
+//! - Enums ([`Reflect::as_enum`]) are externally tagged: `{"Variant": {"field": ...}}`, tuple
+//!   fields are named by their index. Only the fields of the active variant are written.
+//! - Everything else is written as a map of the fields reported by [`Reflect::fields_info`].
+//!
+//! Deserialization works in-place: the target value defines the layout of the data, except for
+//! enums, which are switched to the variant found in the data with [`ReflectEnum::set_variant`]
+//! (fields of the new variant are created with [`Reflect::default_box`], so `None` can be loaded
+//! as `Some` of a struct) and then filled like structs.
+//! Struct fields missing in the data are left untouched, leaf fields are assigned with
+//! [`Reflect::set_field`] (so user-defined setters and value ranges are respected, read-only fields
+//! are still restored). Lists are resized to the length of the incoming sequence, hash map entries
+//! are inserted or updated. New items of lists and hash maps can only be created for leaf types and
+//! types registered in the global [`TypeRegistry`], items of other types must already be present in
+//! the target value.
+//!
+//! Values whose type is not known in advance (such as `Box<dyn Reflect>`) can be written with
+//! [`TypedReflectSerializer`], which stores the type UUID next to the value, and restored with
+//! [`TypedReflectDeserializer`].
+//!
+//! ```
+//! # use velcro_rtti::reflect::prelude::*;
+//! use serde::de::DeserializeSeed;
+//!
+//! #[derive(Reflect, Debug, Default, PartialEq)]
+//! struct Settings {
+//!     volume: f32,
+//!     name: String,
+//!     history: Vec<u32>,
+//! }
+//!
+//! let settings = Settings {
+//!     volume: 0.5,
+//!     name: "Player".to_string(),
+//!     history: vec![1, 2, 3],
+//! };
+//!
+//! let json = serde_json::to_string(&ReflectSerializer(&settings)).unwrap();
+//!
+//! let mut loaded = Settings::default();
+//! ReflectDeserializer(&mut loaded)
+//!     .deserialize(&mut serde_json::Deserializer::from_str(&json))
+//!     .unwrap();
+//! assert_eq!(settings, loaded);
+//! ```
+
+use crate::reflect::{
+    leaf::{concrete_type_id, is_leaf, new_leaf},
+    prelude::{
+        Reflect, ReflectArray, ReflectEnum, ReflectHashMap, ReflectList, SetFieldError,
+        SetVariantError,
+    },
+};
+use crate::sstorage::ImmutableString;
+use crate::type_registry::TypeRegistry;
+use crate::UUID;
+
+use serde::{
+    de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple, Serializer},
+    Deserialize, Serialize,
+};
+use std::{
+    any::{Any, TypeId},
+    fmt,
+    path::PathBuf,
+};
+
+/// Serializes a `dyn Reflect` with any serde serializer.
+pub struct ReflectSerializer<'a>(pub &'a dyn Reflect);
+
+/// Deserializes data into an existing `dyn Reflect` using any serde deserializer.
+pub struct ReflectDeserializer<'a>(pub &'a mut dyn Reflect);
+
+/// Serializes a `dyn Reflect` together with its type UUID, so it can be restored with
+/// [`TypedReflectDeserializer`] without knowing its type in advance. The type must be registered
+/// in the registry.
+pub struct TypedReflectSerializer<'a> {
+    /// A value to serialize.
+    pub value: &'a dyn Reflect,
+    /// A registry to take the type UUID from.
+    pub registry: &'a TypeRegistry,
+}
+
+/// Creates a new `Box<dyn Reflect>` from the data written by [`TypedReflectSerializer`].
+pub struct TypedReflectDeserializer<'a> {
+    /// A registry to create the value with.
+    pub registry: &'a TypeRegistry,
+}
+
+fn serialize_leaf<S: Serializer>(
+    any: &dyn Any,
+    serializer: S,
+) -> Result<Result<S::Ok, S::Error>, S> {
+    macro_rules! plain {
+        ( $( $ty:ty ),* ) => {
+            $(
+                if let Some(value) = any.downcast_ref::<$ty>() {
+                    return Ok(value.serialize(serializer));
+                }
+            )*
+        };
+    }
+
+    plain!(
+        bool, char, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, String, PathBuf
+    );
+
+    if let Some(value) = any.downcast_ref::<ImmutableString>() {
+        Ok(serializer.serialize_str(value.as_str()))
+    } else if let Some(value) = any.downcast_ref::<UUID>() {
+        Ok(serializer.serialize_str(&value.to_string(true, true)))
+    } else {
+        Err(serializer)
+    }
+}
+
+fn deserialize_leaf<'de, D: Deserializer<'de>>(
+    type_id: TypeId,
+    deserializer: D,
+) -> Result<Box<dyn Reflect>, D::Error> {
+    macro_rules! plain {
+        ( $( $ty:ty ),* ) => {
+            $(
+                if type_id == TypeId::of::<$ty>() {
+                    return <$ty>::deserialize(deserializer).map(|v| Box::new(v) as Box<dyn Reflect>);
+                }
+            )*
+        };
+    }
+
+    plain!(
+        bool, char, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, String, PathBuf
+    );
+
+    if type_id == TypeId::of::<ImmutableString>() {
+        String::deserialize(deserializer)
+            .map(|s| Box::new(ImmutableString::new(s)) as Box<dyn Reflect>)
+    } else if type_id == TypeId::of::<UUID>() {
+        let s = String::deserialize(deserializer)?;
+        UUID::parse(&s)
+            .map(|uuid| Box::new(uuid) as Box<dyn Reflect>)
+            .ok_or_else(|| de::Error::custom(format!("invalid UUID {}", s)))
+    } else {
+        Err(de::Error::custom("not a leaf type"))
+    }
+}
+
+/// Creates a default value of a leaf type or of a type from the global [`TypeRegistry`].
+fn new_item(type_id: TypeId) -> Option<Box<dyn Reflect>> {
+    new_leaf(type_id).or_else(|| TypeRegistry::global().create_by_type_id(type_id))
+}
+
+fn with_array<R>(value: &dyn Reflect, func: impl FnOnce(&dyn ReflectArray) -> R) -> Option<R> {
+    let mut func = Some(func);
+    let mut result = None;
+    value.as_array(&mut |array| {
+        if let (Some(array), Some(func)) = (array, func.take()) {
+            result = Some(func(array));
+        }
+    });
+    result
+}
+
+fn with_hash_map<R>(value: &dyn Reflect, func: impl FnOnce(&dyn ReflectHashMap) -> R) -> Option<R> {
+    let mut func = Some(func);
+    let mut result = None;
+    value.as_hash_map(&mut |map| {
+        if let (Some(map), Some(func)) = (map, func.take()) {
+            result = Some(func(map));
+        }
+    });
+    result
+}
+
+fn with_enum<R>(value: &dyn Reflect, func: impl FnOnce(&dyn ReflectEnum) -> R) -> Option<R> {
+    let mut func = Some(func);
+    let mut result = None;
+    value.as_enum(&mut |value| {
+        if let (Some(value), Some(func)) = (value, func.take()) {
+            result = Some(func(value));
+        }
+    });
+    result
+}
+
+fn with_array_mut<R>(
+    value: &mut dyn Reflect,
+    func: impl FnOnce(&mut dyn ReflectArray) -> R,
+) -> Option<R> {
+    let mut func = Some(func);
+    let mut result = None;
+    value.as_array_mut(&mut |array| {
+        if let (Some(array), Some(func)) = (array, func.take()) {
+            result = Some(func(array));
+        }
+    });
+    result
+}
+
+fn with_list_mut<R>(
+    value: &mut dyn Reflect,
+    func: impl FnOnce(&mut dyn ReflectList) -> R,
+) -> Option<R> {
+    let mut func = Some(func);
+    let mut result = None;
+    value.as_list_mut(&mut |list| {
+        if let (Some(list), Some(func)) = (list, func.take()) {
+            result = Some(func(list));
+        }
+    });
+    result
+}
+
+fn with_enum_mut<R>(
+    value: &mut dyn Reflect,
+    func: impl FnOnce(&mut dyn ReflectEnum) -> R,
+) -> Option<R> {
+    let mut func = Some(func);
+    let mut result = None;
+    value.as_enum_mut(&mut |value| {
+        if let (Some(value), Some(func)) = (value, func.take()) {
+            result = Some(func(value));
+        }
+    });
+    result
+}
+
+fn with_hash_map_mut<R>(
+    value: &mut dyn Reflect,
+    func: impl FnOnce(&mut dyn ReflectHashMap) -> R,
+) -> Option<R> {
+    let mut func = Some(func);
+    let mut result = None;
+    value.as_hash_map_mut(&mut |map| {
+        if let (Some(map), Some(func)) = (map, func.take()) {
+            result = Some(func(map));
+        }
+    });
+    result
+}
+
+impl Serialize for ReflectSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = self.0;
+
+        let mut serializer = Some(serializer);
+        let mut leaf = None;
+        value.as_any(&mut |any| {
+            if let Some(s) = serializer.take() {
+                match serialize_leaf(any, s) {
+                    Ok(result) => leaf = Some(result),
+                    Err(s) => serializer = Some(s),
+                }
+            }
+        });
+        if let Some(result) = leaf {
+            return result;
+        }
+        let Some(serializer) = serializer else {
+            return Err(ser::Error::custom(format!(
+                "{} did not provide its value",
+                value.type_name()
+            )));
+        };
+
+        let mut is_list = false;
+        value.as_list(&mut |list| is_list = list.is_some());
+        let mut serializer = Some(serializer);
+        if let Some(result) = with_array(value, |array| {
+            let serializer = serializer.take().unwrap();
+            let items = (0..array.reflect_len()).filter_map(|i| array.reflect_index(i));
+            if is_list {
+                let mut seq = serializer.serialize_seq(Some(array.reflect_len()))?;
+                for item in items {
+                    seq.serialize_element(&ReflectSerializer(item))?;
+                }
+                seq.end()
+            } else {
+                let mut tuple = serializer.serialize_tuple(array.reflect_len())?;
+                for item in items {
+                    tuple.serialize_element(&ReflectSerializer(item))?;
+                }
+                tuple.end()
+            }
+        }) {
+            return result;
+        }
+
+        if let Some(result) = with_hash_map(value, |hash_map| {
+            let mut map = serializer
+                .take()
+                .unwrap()
+                .serialize_map(Some(hash_map.reflect_len()))?;
+            for i in 0..hash_map.reflect_len() {
+                if let Some((key, value)) = hash_map.reflect_get_at(i) {
+                    map.serialize_entry(&ReflectSerializer(key), &ReflectSerializer(value))?;
+                }
+            }
+            map.end()
+        }) {
+            return result;
+        }
+
+        if let Some(variant) = with_enum(value, |value| value.variant_name()) {
+            let mut map = serializer.take().unwrap().serialize_map(Some(1))?;
+            map.serialize_entry(variant, &FieldsSerializer { value, variant })?;
+            return map.end();
+        }
+
+        FieldsSerializer { value, variant: "" }.serialize(serializer.take().unwrap())
+    }
+}
+
+/// Writes fields of a struct or of the active enum variant (`variant` is not empty), the
+/// `Variant@` prefix of enum fields is stripped.
+struct FieldsSerializer<'a> {
+    value: &'a dyn Reflect,
+    variant: &'static str,
+}
+
+impl Serialize for FieldsSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = self.value;
+        let mut serializer = Some(serializer);
+        let mut result = None;
+        value.fields_info(&mut |fields| {
+            if let Some(serializer) = serializer.take() {
+                result = Some((|| {
+                    let mut map = serializer.serialize_map(Some(fields.len()))?;
+                    for field in fields {
+                        let name = field
+                            .name
+                            .strip_prefix(self.variant)
+                            .and_then(|name| name.strip_prefix('@'))
+                            .unwrap_or(field.name);
+                        map.serialize_entry(name, &ReflectSerializer(field.reflect_value))?;
+                    }
+                    map.end()
+                })());
+            }
+        });
+        result.unwrap_or_else(|| {
+            Err(ser::Error::custom(format!(
+                "{} did not provide its fields",
+                value.type_name()
+            )))
+        })
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ReflectDeserializer<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let target = self.0;
+        let type_name = target.type_name();
+
+        let type_id = concrete_type_id(target);
+        if is_leaf(type_id) {
+            let value = deserialize_leaf(type_id, deserializer)?;
+            return target.set(value).map(|_| ()).map_err(|_| {
+                de::Error::custom(format!("unable to assign a value to {}", type_name))
+            });
+        }
+
+        let mut deserializer = Some(deserializer);
+        if let Some(result) = with_list_mut(target, |list| {
+            deserializer
+                .take()
+                .unwrap()
+                .deserialize_seq(ListVisitor(list))
+        }) {
+            return result;
+        }
+
+        if let Some(result) = with_array_mut(target, |array| {
+            let len = array.reflect_len();
+            deserializer
+                .take()
+                .unwrap()
+                .deserialize_tuple(len, ArrayVisitor(array))
+        }) {
+            return result;
+        }
+
+        if let Some(result) = with_hash_map_mut(target, |map| {
+            deserializer
+                .take()
+                .unwrap()
+                .deserialize_map(HashMapVisitor(map))
+        }) {
+            return result;
+        }
+
+        if with_enum_mut(target, |_| ()).is_some() {
+            return deserializer.take().unwrap().deserialize_map(EnumVisitor(target));
+        }
+
+        deserializer.take().unwrap().deserialize_map(StructVisitor {
+            target,
+            variant: None,
+        })
+    }
+}
+
+struct ArrayVisitor<'a>(&'a mut dyn ReflectArray);
+
+impl<'de> Visitor<'de> for ArrayVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an array of {} items", self.0.reflect_len())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let len = self.0.reflect_len();
+        for i in 0..len {
+            let item = self.0.reflect_index_mut(i).unwrap();
+            if seq.next_element_seed(ReflectDeserializer(item))?.is_none() {
+                return Err(de::Error::invalid_length(i, &self));
+            }
+        }
+        if seq.next_element::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(len + 1, &self));
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes a field marked with `#[reflect(immutable_collection)]`, items of a list are loaded
+/// in place and data of a different length is rejected. Arrays have a fixed length anyway and are
+/// loaded as usual.
+struct FixedLengthSeed<'a>(&'a mut dyn Reflect);
+
+impl<'de> DeserializeSeed<'de> for FixedLengthSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let target = self.0;
+        let mut is_list = false;
+        target.as_list(&mut |list| is_list = list.is_some());
+        if !is_list {
+            return ReflectDeserializer(target).deserialize(deserializer);
+        }
+
+        let mut deserializer = Some(deserializer);
+        match with_array_mut(target, |array| {
+            deserializer
+                .take()
+                .unwrap()
+                .deserialize_seq(ArrayVisitor(array))
+        }) {
+            Some(result) => result,
+            None => ReflectDeserializer(target).deserialize(deserializer.take().unwrap()),
+        }
+    }
+}
+
+struct ListVisitor<'a>(&'a mut dyn ReflectList);
+
+impl ListVisitor<'_> {
+    /// Appends a default item to the list, works only for lists of leaf or registered types.
+    fn push_default(&mut self) -> bool {
+        new_item(self.0.reflect_item_type_id())
+            .is_some_and(|item| self.0.reflect_push(item).is_ok())
+    }
+}
+
+impl<'de> Visitor<'de> for ListVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let mut count = 0;
+        loop {
+            if count == self.0.reflect_len() {
+                // Peeking is not possible with `SeqAccess`, so the default item is pushed first
+                // and removed if the sequence has ended.
+                if !self.push_default() {
+                    return match seq.next_element::<IgnoredAny>()? {
+                        Some(_) => Err(de::Error::custom(format!(
+                            "unable to create a new item for {}",
+                            self.0.type_name()
+                        ))),
+                        None => Ok(()),
+                    };
+                }
+                let item = self.0.reflect_index_mut(count).unwrap();
+                if seq.next_element_seed(ReflectDeserializer(item))?.is_none() {
+                    self.0.reflect_pop();
+                    return Ok(());
+                }
+            } else {
+                let item = self.0.reflect_index_mut(count).unwrap();
+                if seq.next_element_seed(ReflectDeserializer(item))?.is_none() {
+                    while self.0.reflect_len() > count {
+                        self.0.reflect_pop();
+                    }
+                    return Ok(());
+                }
+            }
+            count += 1;
+        }
+    }
+}
+
+struct HashMapVisitor<'a>(&'a mut dyn ReflectHashMap);
+
+impl<'de> Visitor<'de> for HashMapVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<(), A::Error> {
+        let (key_type, value_type) = (self.0.reflect_key_type_id(), self.0.reflect_value_type_id());
+        loop {
+            let Some(mut key) = new_leaf(key_type) else {
+                return match access.next_key::<IgnoredAny>()? {
+                    Some(_) => Err(de::Error::custom(format!(
+                        "keys of {} must be primitive values",
+                        self.0.type_name()
+                    ))),
+                    None => Ok(()),
+                };
+            };
+            if access
+                .next_key_seed(ReflectDeserializer(&mut *key))?
+                .is_none()
+            {
+                return Ok(());
+            }
+
+            let mut access = Some(&mut access);
+            let mut result = None;
+            self.0.reflect_get_mut(&*key, &mut |value| {
+                if let Some(value) = value {
+                    result = Some(
+                        access
+                            .take()
+                            .unwrap()
+                            .next_value_seed(ReflectDeserializer(value)),
+                    );
+                }
+            });
+            match result {
+                Some(result) => result?,
+                None => {
+                    let Some(mut value) = new_item(value_type) else {
+                        return Err(de::Error::custom(format!(
+                            "unable to create a new value for {}",
+                            self.0.type_name()
+                        )));
+                    };
+                    access
+                        .take()
+                        .unwrap()
+                        .next_value_seed(ReflectDeserializer(&mut *value))?;
+                    self.0.reflect_insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+struct EnumVisitor<'a>(&'a mut dyn Reflect);
+
+impl<'de> Visitor<'de> for EnumVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a variant of {}", self.0.type_name())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<(), A::Error> {
+        let type_name = self.0.type_name();
+
+        let Some(variant) = access.next_key::<String>()? else {
+            return Err(de::Error::custom(format!("missing variant of {}", type_name)));
+        };
+
+        let variant = with_enum_mut(self.0, |value| {
+            value.set_variant(&variant)?;
+            Ok(value.variant_name())
+        })
+        .unwrap_or(Err(SetVariantError::UnknownVariant(variant)))
+        .map_err(|err| de::Error::custom(format!("{} of {}", err, type_name)))?;
+
+        access.next_value_seed(VariantSeed {
+            target: self.0,
+            variant,
+        })?;
+
+        if access.next_key::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::custom(format!(
+                "expected a single variant of {}",
+                type_name
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+struct VariantSeed<'a> {
+    target: &'a mut dyn Reflect,
+    variant: &'static str,
+}
+
+impl<'de> DeserializeSeed<'de> for VariantSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(StructVisitor {
+            target: self.target,
+            variant: Some(self.variant),
+        })
+    }
+}
+
+/// Fills fields of a struct or of the active enum variant (`variant` is set), names of enum fields
+/// are given without the `Variant@` prefix.
+struct StructVisitor<'a> {
+    target: &'a mut dyn Reflect,
+    variant: Option<&'static str>,
+}
+
+impl<'de> Visitor<'de> for StructVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "fields of {}", self.target.type_name())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<(), A::Error> {
+        let StructVisitor { target, variant } = self;
+        let type_name = target.type_name();
+
+        while let Some(name) = access.next_key::<String>()? {
+            // Fields renamed with `#[reflect(name = ...)]` have no variant prefix.
+            let name = match variant {
+                Some(variant) => {
+                    let prefixed = format!("{}@{}", variant, name);
+                    let mut exists = false;
+                    target.field(&prefixed, &mut |field| exists = field.is_some());
+                    if exists {
+                        prefixed
+                    } else {
+                        name
+                    }
+                }
+                None => name,
+            };
+            let mut field_type = None;
+            target
+                .field(&name, &mut |field| field_type = field.map(concrete_type_id));
+            let Some(field_type) = field_type else {
+                return Err(de::Error::custom(format!(
+                    "unknown field `{}` of {}",
+                    name, type_name
+                )));
+            };
+
+            if is_leaf(field_type) {
+                let value = access.next_value_seed(LeafSeed(field_type))?;
+                let mut result = None;
+                target.set_field(&name, value, &mut |r| result = Some(r.map(|_| ())));
+                // Read-only fields can't be edited, but still have to be restored.
+                let result = match result {
+                    Some(Err(SetFieldError::ReadOnly { value, .. })) => {
+                        let mut value = Some(value);
+                        let mut restored = None;
+                        target.field_mut(&name, &mut |field| {
+                            if let (Some(field), Some(value)) = (field, value.take()) {
+                                restored = Some(field.set(value).map(|_| ()).map_err(|value| {
+                                    SetFieldError::TypeMismatch {
+                                        name: name.clone(),
+                                        value,
+                                    }
+                                }));
+                            }
+                        });
+                        restored
+                    }
+                    result => result,
+                };
+                match result {
+                    Some(Ok(())) => (),
+                    Some(Err(err)) => {
+                        return Err(de::Error::custom(format!("{} of {}", err, type_name)))
+                    }
+                    None => {
+                        return Err(de::Error::custom(format!(
+                            "unable to set field `{}` of {}",
+                            name, type_name
+                        )))
+                    }
+                }
+            } else {
+                let mut immutable_collection = false;
+                target.field_info(&name, &mut |field| {
+                    immutable_collection = field.is_some_and(|field| field.immutable_collection)
+                });
+                let mut access = Some(&mut access);
+                let mut result = None;
+                target.field_mut(&name, &mut |field| {
+                    if let Some(field) = field {
+                        let access = access.take().unwrap();
+                        result = Some(if immutable_collection {
+                            access.next_value_seed(FixedLengthSeed(field))
+                        } else {
+                            access.next_value_seed(ReflectDeserializer(field))
+                        });
+                    }
+                });
+                result.unwrap_or_else(|| {
+                    Err(de::Error::custom(format!(
+                        "unknown field `{}` of {}",
+                        name, type_name
+                    )))
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const TYPED_FIELDS: &[&str] = &["type", "value"];
+
+impl Serialize for TypedReflectSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(type_uuid) = self.registry.type_uuid_of(concrete_type_id(self.value)) else {
+            return Err(ser::Error::custom(format!(
+                "{} is not registered",
+                self.value.type_name()
+            )));
+        };
+
+        let mut state = serializer.serialize_struct("TypedReflect", TYPED_FIELDS.len())?;
+        state.serialize_field("type", &type_uuid.to_string(true, true))?;
+        state.serialize_field("value", &ReflectSerializer(self.value))?;
+        state.end()
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for TypedReflectDeserializer<'_> {
+    type Value = Box<dyn Reflect>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("TypedReflect", TYPED_FIELDS, TypedVisitor(self.registry))
+    }
+}
+
+struct TypedVisitor<'a>(&'a TypeRegistry);
+
+impl TypedVisitor<'_> {
+    fn create<E: de::Error>(&self, type_uuid: &str) -> Result<Box<dyn Reflect>, E> {
+        let uuid = UUID::parse(type_uuid)
+            .ok_or_else(|| E::custom(format!("invalid type UUID {}", type_uuid)))?;
+        self.0
+            .create_by_uuid(&uuid)
+            .ok_or_else(|| E::custom(format!("type {} is not registered", type_uuid)))
+    }
+}
+
+impl<'de> Visitor<'de> for TypedVisitor<'_> {
+    type Value = Box<dyn Reflect>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a type uuid and a value")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let type_uuid = seq
+            .next_element::<String>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut value = self.create(&type_uuid)?;
+        seq.next_element_seed(ReflectDeserializer(&mut *value))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(value)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        match access.next_key::<String>()?.as_deref() {
+            Some("type") => (),
+            _ => return Err(de::Error::missing_field("type")),
+        }
+        let mut value = self.create(&access.next_value::<String>()?)?;
+        match access.next_key::<String>()?.as_deref() {
+            Some("value") => access.next_value_seed(ReflectDeserializer(&mut *value))?,
+            _ => return Err(de::Error::missing_field("value")),
+        }
+        Ok(value)
+    }
+}
+
+struct LeafSeed(TypeId);
+
+impl<'de> DeserializeSeed<'de> for LeafSeed {
+    type Value = Box<dyn Reflect>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserialize_leaf(self.0, deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::prelude::*;
+    use bincode::Options;
+    use std::collections::HashMap;
+
+    #[derive(Reflect, Debug, Default, PartialEq)]
+    struct Stats {
+        health: f32,
+        alive: bool,
+    }
+
+    #[derive(Reflect, Debug, PartialEq)]
+    struct Player {
+        name: String,
+        id: UUID,
+        stats: Stats,
+        inventory: Vec<u32>,
+        slots: [i16; 3],
+        tags: HashMap<String, i64>,
+        pet: Option<Box<Stats>>,
+    }
+
+    fn player() -> Player {
+        Player {
+            name: "Velcro".to_string(),
+            id: UUID::create_name("player"),
+            stats: Stats {
+                health: 42.5,
+                alive: true,
+            },
+            inventory: vec![1, 2, 3],
+            slots: [-1, 0, 7],
+            tags: [("level".to_string(), 3), ("score".to_string(), -20)]
+                .into_iter()
+                .collect(),
+            pet: None,
+        }
+    }
+
+    #[derive(Reflect, Debug, PartialEq)]
+    enum Weapon {
+        Sword { damage: f32, name: String },
+        Bow(u32),
+        Fists,
+    }
+
+    #[derive(Reflect, Debug, Default)]
+    struct Volume {
+        #[reflect(min_value = 0.0, max_value = 1.0)]
+        level: f32,
+        #[reflect(read_only)]
+        version: u32,
+        #[reflect(immutable_collection)]
+        balance: Vec<f32>,
+    }
+
+    #[derive(Reflect, Debug, Default, PartialEq)]
+    struct Owner {
+        pet: Option<Stats>,
+        history: Option<Vec<u32>>,
+    }
+
+    #[derive(Reflect, Debug, Default, PartialEq)]
+    struct Transform {
+        pos: [f32; 3],
+        n: u32,
+    }
+
+    fn from_json(target: &mut dyn Reflect, json: &str) -> Result<(), serde_json::Error> {
+        ReflectDeserializer(target).deserialize(&mut serde_json::Deserializer::from_str(json))
+    }
+
+    fn bincode_options() -> impl bincode::Options {
+        bincode::DefaultOptions::new().with_fixint_encoding()
+    }
+
+    fn from_bincode(target: &mut dyn Reflect, bytes: &[u8]) -> bincode::Result<()> {
+        let mut deserializer = bincode::Deserializer::from_slice(bytes, bincode_options());
+        ReflectDeserializer(target).deserialize(&mut deserializer)
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let source = player();
+        let json = serde_json::to_string(&ReflectSerializer(&source)).unwrap();
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["stats"]["health"], 42.5);
+        assert_eq!(value["slots"], serde_json::json!([-1, 0, 7]));
+        assert_eq!(value["tags"]["score"], -20);
+
+        let mut loaded = Player {
+            name: String::new(),
+            id: UUID::create_null(),
+            stats: Stats::default(),
+            inventory: Vec::new(),
+            slots: [0; 3],
+            tags: HashMap::new(),
+            pet: None,
+        };
+        from_json(&mut loaded, &json).unwrap();
+        assert_eq!(source, loaded);
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let source = Transform {
+            pos: [1.0, 2.5, -3.0],
+            n: 7,
+        };
+        // Fixed-size arrays are written without their length.
+        let bytes = bincode_options()
+            .serialize(&ReflectSerializer(&source.pos))
+            .unwrap();
+        assert_eq!(bytes.len(), 3 * 4);
+        let bytes = bincode_options()
+            .serialize(&ReflectSerializer(&source))
+            .unwrap();
+        let mut loaded = Transform::default();
+        from_bincode(&mut loaded, &bytes).unwrap();
+        assert_eq!(source, loaded);
+
+        let source = player();
+        let bytes = bincode_options()
+            .serialize(&ReflectSerializer(&source))
+            .unwrap();
+        let mut loaded = player();
+        loaded.inventory.clear();
+        loaded.slots = [0; 3];
+        loaded.tags.clear();
+        loaded.stats = Stats::default();
+        from_bincode(&mut loaded, &bytes).unwrap();
+        assert_eq!(source, loaded);
+
+        let sword = Weapon::Sword {
+            damage: 7.5,
+            name: "Blade".to_string(),
+        };
+        let bytes = bincode_options()
+            .serialize(&ReflectSerializer(&sword))
+            .unwrap();
+        let mut weapon = Weapon::Fists;
+        from_bincode(&mut weapon, &bytes).unwrap();
+        assert_eq!(weapon, sword);
+    }
+
+    #[test]
+    fn enums_are_externally_tagged() {
+        let sword = Weapon::Sword {
+            damage: 7.5,
+            name: "Blade".to_string(),
+        };
+        let json = serde_json::to_string(&ReflectSerializer(&sword)).unwrap();
+        assert_eq!(json, r#"{"Sword":{"damage":7.5,"name":"Blade"}}"#);
+
+        let mut weapon = Weapon::Fists;
+        from_json(&mut weapon, &json).unwrap();
+        assert_eq!(weapon, sword);
+
+        from_json(&mut weapon, r#"{"Bow":{"0":12}}"#).unwrap();
+        assert_eq!(weapon, Weapon::Bow(12));
+        assert_eq!(
+            serde_json::to_string(&ReflectSerializer(&weapon)).unwrap(),
+            r#"{"Bow":{"0":12}}"#
+        );
+
+        from_json(&mut weapon, r#"{"Fists":{}}"#).unwrap();
+        assert_eq!(weapon, Weapon::Fists);
+
+        assert!(from_json(&mut weapon, r#"{"Spear":{}}"#).is_err());
+        assert!(from_json(&mut weapon, r#"{"Bow":{"0":1},"Fists":{}}"#).is_err());
+    }
+
+    #[test]
+    fn options_switch_from_none_to_some() {
+        let mut owner = Owner::default();
+        from_json(
+            &mut owner,
+            r#"{"pet": {"Some": {"0": {"health": 3.0}}}, "history": {"Some": {"0": [4, 5]}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            owner.pet,
+            Some(Stats {
+                health: 3.0,
+                alive: false,
+            })
+        );
+        assert_eq!(owner.history, Some(vec![4, 5]));
+
+        let bytes = bincode_options()
+            .serialize(&ReflectSerializer(&owner))
+            .unwrap();
+        let mut loaded = Owner::default();
+        from_bincode(&mut loaded, &bytes).unwrap();
+        assert_eq!(owner, loaded);
+
+        from_json(&mut owner, r#"{"pet": {"None": {}}}"#).unwrap();
+        assert_eq!(owner.pet, None);
+    }
+
+    #[test]
+    fn lists_are_resized_and_maps_are_merged() {
+        let mut target = player();
+        from_json(
+            &mut target,
+            r#"{"inventory": [9], "tags": {"level": 4, "bonus": 1}, "stats": {"alive": false}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(target.inventory, vec![9]);
+        assert_eq!(target.tags.len(), 3);
+        assert_eq!(target.tags["level"], 4);
+        assert_eq!(target.tags["bonus"], 1);
+        assert!(!target.stats.alive);
+        assert_eq!(target.stats.health, 42.5);
+    }
+
+    #[test]
+    fn read_only_fields_are_loaded_and_ranges_checked() {
+        let mut volume = Volume::default();
+        from_json(&mut volume, r#"{"level": 0.5, "version": 3}"#).unwrap();
+        assert_eq!((volume.level, volume.version), (0.5, 3));
+
+        let err = from_json(&mut volume, r#"{"level": 1.5}"#).unwrap_err();
+        assert!(err.to_string().contains("out of range"));
+        assert_eq!(volume.level, 0.5);
+
+        volume.balance = vec![0.5, 0.5];
+        from_json(&mut volume, r#"{"balance": [0.25, 0.75]}"#).unwrap();
+        assert_eq!(volume.balance, [0.25, 0.75]);
+        assert!(from_json(&mut volume, r#"{"balance": [1.0]}"#).is_err());
+        assert!(from_json(&mut volume, r#"{"balance": [1.0, 1.0, 1.0]}"#).is_err());
+        assert_eq!(volume.balance.len(), 2);
+    }
+
+    #[test]
+    fn reports_unknown_fields_and_bad_lengths() {
+        let mut target = player();
+        assert!(from_json(&mut target, r#"{"mana": 5}"#).is_err());
+        assert!(from_json(&mut target, r#"{"slots": [1, 2]}"#).is_err());
+        assert!(from_json(&mut target, r#"{"stats": {"health": "high"}}"#).is_err());
+        assert!(from_json(&mut target, r#"{"id": "{not-a-uuid}"}"#).is_err());
+    }
+
+    #[derive(Reflect, Debug, Default, PartialEq)]
+    struct Marker {
+        label: String,
+        weight: f64,
+    }
+
+    crate::uuid_provider!(Marker = "{5d1e0f4a-93b7-4c2e-8a61-7f3c9d2b8e45}");
+
+    #[test]
+    fn registered_types_are_created() {
+        TypeRegistry::global().register::<Marker>().unwrap();
+
+        // Items of an empty list are created by its item type.
+        let mut markers = Vec::<Marker>::new();
+        from_json(
+            &mut markers,
+            r#"[{"label": "a", "weight": 1.0}, {"label": "b", "weight": 2.5}]"#,
+        )
+        .unwrap();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[1].label, "b");
+
+        let source: Box<dyn Reflect> = Box::new(Marker {
+            label: "typed".to_string(),
+            weight: 0.25,
+        });
+        let json = serde_json::to_string(&TypedReflectSerializer {
+            value: &*source,
+            registry: TypeRegistry::global(),
+        })
+        .unwrap();
+        let loaded = TypedReflectDeserializer {
+            registry: TypeRegistry::global(),
+        }
+        .deserialize(&mut serde_json::Deserializer::from_str(&json))
+        .unwrap();
+        assert_eq!(
+            loaded.take::<Marker>().unwrap(),
+            Marker {
+                label: "typed".to_string(),
+                weight: 0.25,
+            }
+        );
+
+        let typed = |json: &str| {
+            TypedReflectDeserializer {
+                registry: TypeRegistry::global(),
+            }
+            .deserialize(&mut serde_json::Deserializer::from_str(json))
+            .map(|_| ())
+        };
+        let err = typed(r#"{"type": "{5d1e0f4a}", "value": {}}"#).unwrap_err();
+        assert!(err.to_string().contains("invalid type UUID"));
+        let err = typed(r#"{"type": "{00000000-0000-0000-0000-000000000001}", "value": {}}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("is not registered"));
+    }
+}
@@ -1,11 +1,15 @@
-use crate::reflect::prelude::{Reflect, ReflectArray, ReflectList, ReflectHashMap, FieldInfo};
+use crate::reflect::prelude::{
+    CallError, FieldInfo, MethodInfo, Reflect, ReflectArray, ReflectEnum, ReflectHashMap,
+    ReflectList, SetFieldError, SetVariantError, default_value,
+};
 use crate::UUID;
+use crate::variable::ReflectInheritableVariable;
 use crate::{blank_reflect,delegate_reflect};
 use crate::sstorage::ImmutableString;
 use velcro_derive::impl_reflect;
 
 use std::{
-    any::Any,
+    any::{Any, TypeId},
     fmt::Debug,
     collections::HashMap,
     hash::{BuildHasher, Hash},
@@ -16,17 +20,74 @@ use std::{
     sync::Arc,
 };
 
+/// Compares a value with a type-erased value, values of different types are not equal.
+fn eq_downcast<T: PartialEq + 'static>(this: &T, other: &dyn Reflect) -> bool {
+    let mut equal = false;
+    other.as_any(&mut |any| equal = any.downcast_ref::<T>().is_some_and(|other| this == other));
+    equal
+}
+
+/// Clones a value with [`Reflect::clone_box`].
+fn clone_value<T: Reflect>(value: &T) -> Option<T> {
+    value.clone_box()?.take::<T>().ok()
+}
+
+/// Clones items of an array one by one with [`Reflect::clone_box`].
+fn clone_items<T: Reflect>(items: &[T]) -> Option<Vec<T>> {
+    items.iter().map(clone_value).collect()
+}
+
+/// Compares pairs of values one by one with [`Reflect::reflect_eq`], stops at the first pair that
+/// is not equal or can't be compared.
+fn values_eq<'a>(pairs: impl IntoIterator<Item = (&'a dyn Reflect, &'a dyn Reflect)>) -> Option<bool> {
+    for (a, b) in pairs {
+        match a.reflect_eq(b) {
+            Some(true) => (),
+            not_equal => return not_equal,
+        }
+    }
+    Some(true)
+}
+
+/// Compares items of two arrays one by one with [`Reflect::reflect_eq`].
+fn items_eq(this: &dyn ReflectArray, other: &dyn Reflect) -> Option<bool> {
+    let mut result = Some(false);
+    other.as_array(&mut |other| {
+        if let Some(other) = other {
+            if this.type_name() != other.type_name() || this.reflect_len() != other.reflect_len() {
+                return;
+            }
+            let pairs = (0..this.reflect_len())
+                .map(|i| this.reflect_index(i).zip(other.reflect_index(i)))
+                .collect::<Option<Vec<_>>>();
+            if let Some(pairs) = pairs {
+                result = values_eq(pairs);
+            }
+        }
+    });
+    result
+}
+
 macro_rules! impl_blank_reflect {
     ( $( $ty:ty ),* $(,)? ) => {
         $(
             impl Reflect for $ty {
                 blank_reflect!();
+
+                fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+                    Some(Box::new(self.clone()))
+                }
+
+                fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+                    Some(eq_downcast(self, other))
+                }
             }
         )*
     }
 }
 
 impl_blank_reflect! {
+    bool, char,
     f32, f64,
     usize, u8, u16, u32, u64,
     isize, i8, i16, i32, i64,
@@ -39,28 +100,53 @@ impl_blank_reflect! {
 macro_rules! impl_reflect_tuple {
     (
         $(
-            ( $($t:ident,)* );
+            ( $($t:ident $i:tt,)* );
         )*
     ) => {
         $(
             impl< $($t: Reflect),* > Reflect for ( $($t,)* ) {
                 blank_reflect!();
+
+                fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+                    Some(Box::new(( $( clone_value(&self.$i)?, )* )))
+                }
+
+                fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+                    let mut result = Some(false);
+                    other.as_any(&mut |any| {
+                        if let Some(other) = any.downcast_ref::<Self>() {
+                            result = values_eq([
+                                $( (&self.$i as &dyn Reflect, &other.$i as &dyn Reflect), )*
+                            ]);
+                        }
+                    });
+                    result
+                }
             }
         )*
     }
 }
 
 impl_reflect_tuple! {
-    (T0,);
-    (T0, T1, );
-    (T0, T1, T2, );
-    (T0, T1, T2, T3,);
-    (T0, T1, T2, T3, T4,);
+    (T0 0,);
+    (T0 0, T1 1,);
+    (T0 0, T1 1, T2 2,);
+    (T0 0, T1 1, T2 2, T3 3,);
+    (T0 0, T1 1, T2 2, T3 3, T4 4,);
 }
 
 impl<const N: usize, T: Reflect> Reflect for [T; N] {
     blank_reflect!();
 
+    fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+        let items: [T; N] = clone_items(self)?.try_into().ok()?;
+        Some(Box::new(items))
+    }
+
+    fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+        items_eq(self, other)
+    }
+
     fn as_array(&self, func: &mut dyn FnMut(Option<&dyn ReflectArray>)) {
         func(Some(self))
     }
@@ -92,9 +178,36 @@ impl<const N: usize, T: Reflect> ReflectArray for [T; N] {
     }
 }
 
-impl_reflect! {
-    #[reflect(ReflectList, ReflectArray)]
-    pub struct Vec<T: Reflect + 'static>;
+impl<T: Reflect + 'static> Reflect for Vec<T> {
+    blank_reflect!();
+
+    fn as_array(&self, func: &mut dyn FnMut(Option<&dyn ReflectArray>)) {
+        func(Some(self))
+    }
+
+    fn as_array_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectArray>)) {
+        func(Some(self))
+    }
+
+    fn as_list(&self, func: &mut dyn FnMut(Option<&dyn ReflectList>)) {
+        func(Some(self))
+    }
+
+    fn as_list_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectList>)) {
+        func(Some(self))
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+        Some(Box::new(clone_items(self)?))
+    }
+
+    fn default_box() -> Option<Box<dyn Reflect>> {
+        Some(Box::new(Vec::<T>::new()))
+    }
+
+    fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+        items_eq(self, other)
+    }
 }
 
 impl<T: Reflect + 'static> ReflectArray for Vec<T> {
@@ -113,6 +226,10 @@ impl<T: Reflect + 'static> ReflectArray for Vec<T> {
 
 /// REMARK: `Reflect` is implemented for `Vec<T>` where `T: Reflect` only.
 impl<T: Reflect + 'static> ReflectList for Vec<T> {
+    fn reflect_item_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
     fn reflect_push(&mut self, value: Box<dyn Reflect>) -> Result<(), Box<dyn Reflect>> {
         self.push(*value.downcast::<T>()?);
         Ok(())
@@ -148,10 +265,18 @@ impl<K, V, S> Reflect for HashMap<K, V, S>
 where
     K: Reflect + Debug + Eq + Hash + 'static,
     V: Reflect + Debug + 'static,
-    S: BuildHasher + 'static,
+    S: BuildHasher + Clone + 'static,
 {
     blank_reflect!();
 
+    fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+        let mut map = HashMap::with_capacity_and_hasher(self.len(), self.hasher().clone());
+        for (key, value) in self.iter() {
+            map.insert(clone_value(key)?, clone_value(value)?);
+        }
+        Some(Box::new(map))
+    }
+
     fn as_hash_map(&self, func: &mut dyn FnMut(Option<&dyn ReflectHashMap>)) {
         func(Some(self))
     }
@@ -159,14 +284,46 @@ where
     fn as_hash_map_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectHashMap>)) {
         func(Some(self))
     }
+
+    fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+        let mut result = Some(false);
+        other.as_any(&mut |any| {
+            let Some(other) = any.downcast_ref::<Self>() else {
+                return;
+            };
+            if self.len() != other.len() {
+                return;
+            }
+            for (key, value) in self.iter() {
+                match other.get(key).map(|other| value.reflect_eq(other)) {
+                    Some(Some(true)) => (),
+                    Some(None) => {
+                        result = None;
+                        return;
+                    }
+                    _ => return,
+                }
+            }
+            result = Some(true);
+        });
+        result
+    }
 }
 
 impl<K, V, S> ReflectHashMap for HashMap<K, V, S>
 where
     K: Reflect + Debug + Eq + Hash + 'static,
     V: Reflect + Debug + 'static,
-    S: BuildHasher + 'static,
+    S: BuildHasher + Clone + 'static,
 {
+    fn reflect_key_type_id(&self) -> TypeId {
+        TypeId::of::<K>()
+    }
+
+    fn reflect_value_type_id(&self) -> TypeId {
+        TypeId::of::<V>()
+    }
+
     fn reflect_insert(
         &mut self,
         key: Box<dyn Reflect>,
@@ -248,6 +405,14 @@ where
 
 impl Reflect for () {
     blank_reflect!();
+
+    fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+        Some(Box::new(()))
+    }
+
+    fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+        Some(eq_downcast(self, other))
+    }
 }
 
 impl_reflect! { pub struct UUID; }
@@ -335,7 +500,7 @@ macro_rules! impl_reflect_inner_mutability {
             &mut $self,
             field: &str,
             value: Box<dyn Reflect>,
-            func: &mut dyn FnMut(Result<Box<dyn Reflect>, Box<dyn Reflect>>),
+            func: &mut dyn FnMut(Result<Box<dyn Reflect>, SetFieldError>),
         ) {
             let mut guard = $acquire_lock_guard;
             guard.set_field(field, value, func)
@@ -381,7 +546,7 @@ macro_rules! impl_reflect_inner_mutability {
             guard.as_list_mut(func)
         }
 
-        /*fn as_inheritable_variable(
+        fn as_inheritable_variable(
             &$self,
             func: &mut dyn FnMut(Option<&dyn ReflectInheritableVariable>),
         ) {
@@ -395,7 +560,7 @@ macro_rules! impl_reflect_inner_mutability {
         ) {
             let mut guard = $acquire_lock_guard;
             guard.as_inheritable_variable_mut(func)
-        }*/
+        }
 
         fn as_hash_map(&$self, func: &mut dyn FnMut(Option<&dyn ReflectHashMap>)) {
             let guard = $acquire_lock_guard;
@@ -406,6 +571,39 @@ macro_rules! impl_reflect_inner_mutability {
             let mut guard = $acquire_lock_guard;
             guard.as_hash_map_mut(func)
         }
+
+        fn methods_info(&$self) -> &'static [MethodInfo] {
+            let guard = $acquire_lock_guard;
+            guard.methods_info()
+        }
+
+        fn call_method(
+            &mut $self,
+            name: &str,
+            args: Vec<Box<dyn Reflect>>,
+        ) -> Result<Box<dyn Reflect>, CallError> {
+            let mut guard = $acquire_lock_guard;
+            guard.call_method(name, args)
+        }
+
+        fn call_method_ref(
+            &$self,
+            name: &str,
+            args: Vec<Box<dyn Reflect>>,
+        ) -> Result<Box<dyn Reflect>, CallError> {
+            let guard = $acquire_lock_guard;
+            guard.call_method_ref(name, args)
+        }
+
+        fn as_enum(&$self, func: &mut dyn FnMut(Option<&dyn ReflectEnum>)) {
+            let guard = $acquire_lock_guard;
+            guard.as_enum(func)
+        }
+
+        fn as_enum_mut(&mut $self, func: &mut dyn FnMut(Option<&mut dyn ReflectEnum>)) {
+            let mut guard = $acquire_lock_guard;
+            guard.as_enum_mut(func)
+        }
     };
 }
 
@@ -1,28 +1,45 @@
-//! Visitor is a tree-based serializer/deserializer.
+//! ReflectContext is a tree-based serializer/deserializer.
 //!
 //! # Overview
 //!
-//! Visitor uses tree to create structured storage of data. Basic unit is a *node* - it is a container
+//! ReflectContext uses tree to create structured storage of data. Basic unit is a *node* - it is a container
 //! for data fields. Each node has name, handle to parent, set of handles to children nodes and some
 //! container for data fields. Data field is tuple of name and value, value can be any of simple Rust
 //! types and some of basic structures of the crate. Main criteria of what could be the field and what
 //! not is the ability to be represented as set of bytes without any aliasing issues.
+//!
+//! # Binary format
+//!
+//! [ReflectContext::save_binary] writes [ReflectContext::MAGIC], followed by [ReflectContext::VERSION]
+//! (little-endian `u32`) and then the node tree in depth-first order. Every node is stored as its name,
+//! its fields and the number of its children, which are written right after it.
+//!
+//! # Text format
+//!
+//! [ReflectContext::save_text] writes the same tree in a human-readable form that can be reviewed
+//! and diffed, see the [text] module for details.
+
+pub mod text;
 
 pub use velcro_derive::Context;
 
 pub mod prelude {
     //! Types to use `#[derive(Context)]`
-    pub use super::{Context, ContextError, ReflectResult};
+    pub use super::{Context, ContextError, ReflectContext, ReflectResult};
 }
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
-use std::io::{Read, Write};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::num;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
 use std::string::FromUtf8Error;
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum FieldKind {
     Bool(bool),
     U8(u8),
@@ -102,6 +119,7 @@ impl Pod for f64 {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Field {
     // 字段名字
     name: String,
@@ -126,8 +144,10 @@ pub enum ContextError {
     RegionDoesNotExist(String),
     /// 访问者试图离开当前节点，但不知何故它没有当前节点.[这绝对不应该发生]
     NoActiveNode,
-    /// 编码缺少[Visitor::MAGIC]字节数据
+    /// 编码缺少[ReflectContext::MAGIC]字节数据
     NotSupportedFormat,
+    /// 数据的格式版本高于当前支持的[ReflectContext::VERSION]
+    UnsupportedVersion(u32),
     /// 某些字节序列不是 UTF8 格式.
     InvalidName,
     /// 访问者数据可以是自引用的，例如当数据包含对单个共享值的多个 [Rc] 引用时. 这会导致访问者存储数据一次,
@@ -141,6 +161,12 @@ pub enum ContextError {
     UnexpectedRcNullIndex,
     /// 尝试访问互斥体时发生错误.
     PoisonedMutex,
+    /// 读写数据时发生的 IO 错误.
+    Io(std::io::Error),
+    /// 文本格式数据在指定行存在语法错误.
+    TextSyntax { line: usize, message: String },
+    /// 区域的嵌套层数超过了 [ReflectContext::MAX_DEPTH].
+    TooDeep,
 }
 
 impl Error for ContextError {}
@@ -157,12 +183,18 @@ impl Display for ContextError {
             Self::RegionDoesNotExist(name) => write!(f, "region does not exists {}", name),
             Self::NoActiveNode => write!(f, "no active node"),
             Self::NotSupportedFormat => write!(f, "not supported format"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
             Self::InvalidName => write!(f, "invalid name"),
             Self::TypeMismatch => write!(f, "type mismatch"),
             Self::RefCellAlreadyMutableBorrowed => write!(f, "ref cell already mutable borrowed"),
             Self::User(msg) => write!(f, "user defined error: {}", msg),
             Self::UnexpectedRcNullIndex => write!(f, "unexpected rc null index"),
             Self::PoisonedMutex => write!(f, "attempt to lock poisoned mutex"),
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::TextSyntax { line, message } => {
+                write!(f, "syntax error at line {}: {}", line, message)
+            }
+            Self::TooDeep => write!(f, "regions are nested too deep"),
         }
     }
 }
@@ -185,6 +217,12 @@ impl<T> From<std::sync::PoisonError<std::sync::RwLockWriteGuard<'_, T>>> for Con
     }
 }
 
+impl From<std::io::Error> for ContextError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
 impl From<FromUtf8Error> for ContextError {
     fn from(_: FromUtf8Error) -> Self {
         Self::InvalidName
@@ -201,7 +239,7 @@ impl From<String> for ContextError {
 /// such as [ReflectContext::save_binary]. It has no value unless an error occurred.
 pub type ReflectResult = Result<(), ContextError>;
 
-use crate::memory::Handle;
+use crate::memory::{Allocator, Handle};
 use velcro_utils::UUID;
 
 trait ElementaryField {
@@ -267,7 +305,7 @@ impl Field {
                 file.write_u8(*data)?;
             }
             FieldKind::I8(data) => {
-                file.write_i8(2)?;
+                file.write_u8(2)?;
                 file.write_i8(*data)?;
             }
             FieldKind::U16(data) => {
@@ -316,21 +354,42 @@ impl Field {
     }
 
     fn load(file: &mut dyn Read) -> Result<Field, ContextError> {
-        let name_len = file.read_u3::<LittleEndian>()? as usize;
-        let mut raw_name = vec![Default::default(); name_len];
-        file.read_exact(raw_name.as_mut_slice())?;
+        let raw_name = read_bytes(file)?;
         let id = file.read_u8()?;
         Ok(Field::new(
             String::from_utf8(raw_name)?.as_str(),
             match id {
                 1 => FieldKind::U8(file.read_u8()?),
                 2 => FieldKind::I8(file.read_i8()?),
-                _ => {}
+                3 => FieldKind::U16(file.read_u16::<LittleEndian>()?),
+                4 => FieldKind::I16(file.read_i16::<LittleEndian>()?),
+                5 => FieldKind::U32(file.read_u32::<LittleEndian>()?),
+                6 => FieldKind::I32(file.read_i32::<LittleEndian>()?),
+                7 => FieldKind::U64(file.read_u64::<LittleEndian>()?),
+                8 => FieldKind::I64(file.read_i64::<LittleEndian>()?),
+                9 => FieldKind::F32(file.read_f32::<LittleEndian>()?),
+                10 => FieldKind::F64(file.read_f64::<LittleEndian>()?),
+                14 => FieldKind::BinaryBlob(read_bytes(file)?),
+                15 => FieldKind::Bool(file.read_u8()? != 0),
+                _ => return Err(ContextError::UnknownFieldType(id)),
             },
         ))
     }
 }
 
+/// Reads a length-prefixed sequence of bytes. The length comes from the stream, so the buffer
+/// grows with the data that is actually read instead of being allocated upfront.
+fn read_bytes(src: &mut dyn Read) -> Result<Vec<u8>, ContextError> {
+    let len = src.read_u32::<LittleEndian>()?;
+    let mut data = Vec::new();
+    src.take(u64::from(len)).read_to_end(&mut data)?;
+    if data.len() != len as usize {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(data)
+}
+
+#[derive(Default, Debug)]
 pub struct ContextNode {
     name: String,
     fields: Vec<Field>,
@@ -349,16 +408,656 @@ impl ContextNode {
     }
 }
 
+/// A RegionGuard is a [ReflectContext] that automatically leaves the current region when it is dropped.
+#[must_use = "the guard leaves the region as soon as it is dropped"]
+pub struct RegionGuard<'a>(&'a mut ReflectContext);
+
+impl<'a> Deref for RegionGuard<'a> {
+    type Target = ReflectContext;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a> DerefMut for RegionGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0
+    }
+}
+
+impl<'a> Drop for RegionGuard<'a> {
+    fn drop(&mut self) {
+        // If we acquired RegionGuard instance, then it is safe to assert that
+        // `leave_region` was successful.
+        self.0.leave_region().unwrap();
+    }
+}
+
+/// A tree of named regions and fields that is filled by [Context::context] when writing
+/// and consumed by it when reading.
 pub struct ReflectContext {
+    nodes: Allocator<ContextNode>,
+    reading: bool,
+    current_node: Handle<ContextNode>,
+    root: Handle<ContextNode>,
     pub flags: ContextFlags,
 }
 
+/// Trait of types that can be read from or written to a [ReflectContext].
+pub trait Context {
+    /// Read or write this value, depending on whether [ReflectContext::is_reading()] is true or false.
+    ///
+    /// `name` is the name of the field or region the value is stored under. A value that is made of
+    /// several parts should enter a region with [ReflectContext::enter_region] and store each part in it.
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult;
+}
+
+impl Default for ReflectContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReflectContext {
+    /// Sequence of bytes that is written at the beginning of every binary stream.
+    pub const MAGIC: &'static [u8; 4] = b"VCTX";
+
+    /// Version of the binary format, written right after [Self::MAGIC].
+    pub const VERSION: u32 = 1;
+
+    /// Maximum nesting of regions accepted when a tree is loaded, deeper data is rejected with
+    /// [ContextError::TooDeep] instead of overflowing the stack.
+    pub const MAX_DEPTH: usize = 256;
+
+    /// Creates new context in write mode with a single root node.
+    pub fn new() -> Self {
+        let mut nodes = Allocator::new();
+        let root = nodes.spawn(ContextNode::new("__ROOT__", Handle::NONE));
+        Self {
+            nodes,
+            reading: false,
+            current_node: root,
+            root,
+            flags: ContextFlags::NONE,
+        }
+    }
+
+    /// Returns true if the context was loaded from a stream and values should be read from it.
+    #[inline]
+    pub fn is_reading(&self) -> bool {
+        self.reading
+    }
+
+    fn current_node(&mut self) -> &mut ContextNode {
+        self.nodes.borrow_mut(self.current_node)
+    }
+
+    fn find_field(&mut self, name: &str) -> Option<&mut Field> {
+        self.current_node()
+            .fields
+            .iter_mut()
+            .find(|field| field.name == name)
+    }
+
+    /// Enters a child region of the current region. In write mode the region is created (it is an
+    /// error to create it twice), in read mode it must exist.
+    pub fn enter_region(&mut self, name: &str) -> Result<RegionGuard<'_>, ContextError> {
+        let node = self.nodes.borrow(self.current_node);
+        if self.reading {
+            let region = node
+                .children
+                .iter()
+                .copied()
+                .find(|child| self.nodes.borrow(*child).name == name);
+
+            match region {
+                Some(region) => {
+                    self.current_node = region;
+                    Ok(RegionGuard(self))
+                }
+                None => Err(ContextError::RegionDoesNotExist(
+                    self.build_breadcrumb(" > ") + " > " + name,
+                )),
+            }
+        } else {
+            // Make sure that node does not exists already.
+            if node
+                .children
+                .iter()
+                .any(|child| self.nodes.borrow(*child).name == name)
+            {
+                return Err(ContextError::RegionAlreadyExists(name.to_owned()));
+            }
+
+            let node_handle = self.nodes.spawn(ContextNode::new(name, self.current_node));
+            self.nodes
+                .borrow_mut(self.current_node)
+                .children
+                .push(node_handle);
+            self.current_node = node_handle;
+
+            Ok(RegionGuard(self))
+        }
+    }
+
+    /// Returns the name of the current region, or `None` if there is no current node.
+    pub fn current_region(&self) -> Option<&str> {
+        self.nodes
+            .try_borrow(self.current_node)
+            .map(|n| n.name.as_str())
+    }
+
+    fn leave_region(&mut self) -> ReflectResult {
+        self.current_node = self.nodes.borrow(self.current_node).parent;
+        if self.current_node.is_none() {
+            Err(ContextError::NoActiveNode)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn build_breadcrumb(&self, separator: &str) -> String {
+        let mut rev = String::new();
+        let mut handle = self.current_node;
+        while let Some(node) = self.nodes.try_borrow(handle) {
+            if !rev.is_empty() {
+                rev.insert_str(0, separator);
+            }
+            rev.insert_str(0, node.name.as_str());
+            handle = node.parent;
+        }
+        rev
+    }
+
+    /// Reads or writes raw bytes as a single [FieldKind::BinaryBlob] field of the current node.
+    pub fn context_binary_blob(&mut self, name: &str, data: &mut Vec<u8>) -> ReflectResult {
+        if self.reading {
+            if let Some(field) = self.find_field(name) {
+                match &field.kind {
+                    FieldKind::BinaryBlob(blob) => {
+                        data.clone_from(blob);
+                        Ok(())
+                    }
+                    _ => Err(ContextError::FieldTypeDoesNotMatch),
+                }
+            } else {
+                Err(ContextError::FieldDoesNotExist(name.to_owned()))
+            }
+        } else if self.find_field(name).is_some() {
+            Err(ContextError::FieldAlreadyExists(name.to_owned()))
+        } else {
+            let node = self.current_node();
+            node.fields
+                .push(Field::new(name, FieldKind::BinaryBlob(data.clone())));
+            Ok(())
+        }
+    }
+
+    /// Writes the whole tree to `dest` in the binary format. See the module docs for the layout.
+    pub fn save_binary(&self, dest: &mut dyn Write) -> ReflectResult {
+        dest.write_all(Self::MAGIC)?;
+        dest.write_u32::<LittleEndian>(Self::VERSION)?;
+        self.save_node_binary(self.root, dest)
+    }
+
+    fn save_node_binary(&self, handle: Handle<ContextNode>, dest: &mut dyn Write) -> ReflectResult {
+        let node = self.nodes.borrow(handle);
+        let name = node.name.as_bytes();
+        dest.write_u32::<LittleEndian>(name.len() as u32)?;
+        dest.write_all(name)?;
+        dest.write_u32::<LittleEndian>(node.fields.len() as u32)?;
+        for field in node.fields.iter() {
+            Field::save(field, dest)?;
+        }
+        dest.write_u32::<LittleEndian>(node.children.len() as u32)?;
+        for child in node.children.iter() {
+            self.save_node_binary(*child, dest)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole tree to a file at `path`. See [Self::save_binary].
+    pub fn save_binary_to_file<P: AsRef<Path>>(&self, path: P) -> ReflectResult {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save_binary(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the whole tree to a new vector of bytes. See [Self::save_binary].
+    pub fn save_binary_to_vec(&self) -> Result<Vec<u8>, ContextError> {
+        let mut bytes = Vec::new();
+        self.save_binary(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Reads a tree previously written by [Self::save_binary]. The returned context is in read mode
+    /// and positioned at the root, so the same [Context::context] calls that produced the data can
+    /// be used to restore it.
+    pub fn load_binary(src: &mut dyn Read) -> Result<Self, ContextError> {
+        let mut magic = [0u8; 4];
+        src.read_exact(&mut magic)?;
+        if &magic != Self::MAGIC {
+            return Err(ContextError::NotSupportedFormat);
+        }
+
+        let version = src.read_u32::<LittleEndian>()?;
+        if version > Self::VERSION {
+            return Err(ContextError::UnsupportedVersion(version));
+        }
+
+        let mut reflect_context = Self {
+            nodes: Allocator::new(),
+            reading: true,
+            current_node: Handle::NONE,
+            root: Handle::NONE,
+            flags: ContextFlags::NONE,
+        };
+        reflect_context.root = reflect_context.load_node_binary(src, 0)?;
+        reflect_context.current_node = reflect_context.root;
+        Ok(reflect_context)
+    }
+
+    fn load_node_binary(
+        &mut self,
+        src: &mut dyn Read,
+        depth: usize,
+    ) -> Result<Handle<ContextNode>, ContextError> {
+        if depth > Self::MAX_DEPTH {
+            return Err(ContextError::TooDeep);
+        }
+
+        let raw_name = read_bytes(src)?;
+
+        let mut node = ContextNode {
+            name: String::from_utf8(raw_name)?,
+            ..ContextNode::default()
+        };
+
+        let field_count = src.read_u32::<LittleEndian>()? as usize;
+        for _ in 0..field_count {
+            node.fields.push(Field::load(src)?);
+        }
+
+        let child_count = src.read_u32::<LittleEndian>()? as usize;
+        for _ in 0..child_count {
+            node.children.push(self.load_node_binary(src, depth + 1)?);
+        }
+
+        let children = node.children.clone();
+        let handle = self.nodes.spawn(node);
+        for child in children {
+            self.nodes.borrow_mut(child).parent = handle;
+        }
+
+        Ok(handle)
+    }
+
+    /// Reads a tree from a file at `path`. See [Self::load_binary].
+    pub fn load_binary_from_file<P: AsRef<Path>>(path: P) -> Result<Self, ContextError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::load_binary(&mut reader)
+    }
+
+    /// Reads a tree from a slice of bytes. See [Self::load_binary].
+    pub fn load_from_memory(mut data: &[u8]) -> Result<Self, ContextError> {
+        Self::load_binary(&mut data)
+    }
+}
+
+macro_rules! impl_context_as_field {
+    ($type_name:ty, $kind:ident) => {
+        impl Context for $type_name {
+            fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+                if reflect_context.reading {
+                    if let Some(field) = reflect_context.find_field(name) {
+                        match field.kind {
+                            FieldKind::$kind(data) => {
+                                *self = data;
+                                Ok(())
+                            }
+                            _ => Err(ContextError::FieldTypeDoesNotMatch),
+                        }
+                    } else {
+                        Err(ContextError::FieldDoesNotExist(name.to_owned()))
+                    }
+                } else if reflect_context.find_field(name).is_some() {
+                    Err(ContextError::FieldAlreadyExists(name.to_owned()))
+                } else {
+                    let node = reflect_context.current_node();
+                    node.fields.push(Field::new(name, FieldKind::$kind(*self)));
+                    Ok(())
+                }
+            }
+        }
+    };
+}
+
+impl_context_as_field!(u64, U64);
+impl_context_as_field!(i64, I64);
+impl_context_as_field!(u32, U32);
+impl_context_as_field!(i32, I32);
+impl_context_as_field!(u16, U16);
+impl_context_as_field!(i16, I16);
+impl_context_as_field!(u8, U8);
+impl_context_as_field!(i8, I8);
+impl_context_as_field!(f32, F32);
+impl_context_as_field!(f64, F64);
+impl_context_as_field!(bool, Bool);
+
+impl Context for usize {
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut this = *self as u64;
+        this.context(name, reflect_context)?;
+        if reflect_context.reading {
+            *self = this as usize;
+        }
+        Ok(())
+    }
+}
+
+impl Context for isize {
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut this = *self as i64;
+        this.context(name, reflect_context)?;
+        if reflect_context.reading {
+            *self = this as isize;
+        }
+        Ok(())
+    }
+}
+
+impl Context for String {
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut data = if reflect_context.reading {
+            Vec::new()
+        } else {
+            self.as_bytes().to_vec()
+        };
+        reflect_context.context_binary_blob(name, &mut data)?;
+        if reflect_context.reading {
+            *self = String::from_utf8(data)?;
+        }
+        Ok(())
+    }
+}
+
+impl Context for UUID {
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut string = if reflect_context.reading {
+            String::new()
+        } else {
+            self.to_string(true, true)
+        };
+        string.context(name, reflect_context)?;
+        if reflect_context.reading {
+            *self = UUID::parse(&string)
+                .ok_or_else(|| ContextError::User(format!("invalid UUID {}", string)))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Context> Context for Box<T> {
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        self.deref_mut().context(name, reflect_context)
+    }
+}
+
+impl<T> Context for Option<T>
+where
+    T: Default + Context,
+{
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut region = reflect_context.enter_region(name)?;
+
+        let mut is_some = u8::from(self.is_some());
+        is_some.context("IsSome", &mut region)?;
+
+        if is_some != 0 {
+            if region.reading {
+                let mut value = T::default();
+                value.context("Data", &mut region)?;
+                *self = Some(value);
+            } else if let Some(value) = self {
+                value.context("Data", &mut region)?;
+            }
+        } else if region.reading {
+            *self = None;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Context for Vec<T>
+where
+    T: Default + Context + 'static,
+{
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut region = reflect_context.enter_region(name)?;
+
+        let mut len = self.len() as u32;
+        len.context("Length", &mut region)?;
+
+        if region.reading {
+            self.clear();
+            for index in 0..len {
+                let region_name = format!("Item{}", index);
+                let mut region = region.enter_region(region_name.as_str())?;
+                let mut object = T::default();
+                object.context("ItemData", &mut region)?;
+                self.push(object);
+            }
+        } else {
+            for (index, item) in self.iter_mut().enumerate() {
+                let region_name = format!("Item{}", index);
+                let mut region = region.enter_region(region_name.as_str())?;
+                item.context("ItemData", &mut region)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Context, const N: usize> Context for [T; N] {
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        let mut region = reflect_context.enter_region(name)?;
+
+        let mut len = N as u32;
+        len.context("Length", &mut region)?;
+
+        if region.reading && len as usize != N {
+            return Err(ContextError::User(format!(
+                "Not enough data in array {}: expected {}, got {}",
+                name, N, len
+            )));
+        }
+
+        for (index, item) in self.iter_mut().enumerate() {
+            let region_name = format!("Item{}", index);
+            let mut region = region.enter_region(region_name.as_str())?;
+            item.context("ItemData", &mut region)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    //use super::*;
+    use super::*;
+
+    #[derive(Default, Debug, PartialEq, Context)]
+    struct Settings {
+        volume: f32,
+        fullscreen: bool,
+        name: String,
+    }
+
+    #[derive(Default, Debug, PartialEq, Context)]
+    enum Difficulty {
+        #[default]
+        Easy,
+        Custom { damage: f64, lives: u8 },
+    }
+
+    #[derive(Default, Debug, PartialEq, Context)]
+    struct SaveGame {
+        level: u32,
+        seed: u64,
+        offset: i16,
+        tiny: i8,
+        flags: u8,
+        wide: i64,
+        delta: u16,
+        score: i32,
+        settings: Settings,
+        difficulty: Difficulty,
+        checkpoints: Vec<Settings>,
+        boss: Option<Box<Settings>>,
+        blob: Vec<u8>,
+        position: [f32; 3],
+    }
+
+    fn save_game() -> SaveGame {
+        SaveGame {
+            level: 7,
+            seed: u64::MAX - 3,
+            offset: -1234,
+            tiny: -5,
+            flags: 0b1010,
+            wide: i64::MIN + 1,
+            delta: 60000,
+            score: -42,
+            settings: Settings {
+                volume: 0.75,
+                fullscreen: true,
+                name: "Player One".to_string(),
+            },
+            difficulty: Difficulty::Custom {
+                damage: 1.5,
+                lives: 3,
+            },
+            checkpoints: vec![
+                Settings::default(),
+                Settings {
+                    volume: 0.1,
+                    fullscreen: false,
+                    name: "Checkpoint".to_string(),
+                },
+            ],
+            boss: Some(Box::new(Settings {
+                volume: 1.0,
+                fullscreen: true,
+                name: "Boss".to_string(),
+            })),
+            blob: vec![0, 1, 2, 255],
+            position: [1.0, -2.5, 3.25],
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut original = save_game();
+        let mut reflect_context = ReflectContext::new();
+        original.context("SaveGame", &mut reflect_context).unwrap();
+        let bytes = reflect_context.save_binary_to_vec().unwrap();
+
+        assert_eq!(&bytes[..4], ReflectContext::MAGIC);
+
+        let mut reflect_context = ReflectContext::load_from_memory(&bytes).unwrap();
+        assert!(reflect_context.is_reading());
+        let mut loaded = SaveGame::default();
+        loaded.context("SaveGame", &mut reflect_context).unwrap();
+
+        assert_eq!(original, loaded);
+    }
+
+    #[test]
+    fn rejects_unknown_magic_and_version() {
+        assert!(matches!(
+            ReflectContext::load_from_memory(b"NOPE\x01\x00\x00\x00"),
+            Err(ContextError::NotSupportedFormat)
+        ));
+
+        let mut bytes = ReflectContext::new().save_binary_to_vec().unwrap();
+        bytes[4..8].copy_from_slice(&(ReflectContext::VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            ReflectContext::load_from_memory(&bytes),
+            Err(ContextError::UnsupportedVersion(_))
+        ));
+    }
 
     #[test]
-    fn it_works() {
-        let a: u32 = 1;
+    fn region_and_field_errors() {
+        let mut reflect_context = ReflectContext::new();
+        {
+            let mut region = reflect_context.enter_region("Region").unwrap();
+            let mut value = 1u32;
+            value.context("Value", &mut region).unwrap();
+            assert!(matches!(
+                value.context("Value", &mut region),
+                Err(ContextError::FieldAlreadyExists(_))
+            ));
+        }
+        assert!(matches!(
+            reflect_context.enter_region("Region"),
+            Err(ContextError::RegionAlreadyExists(_))
+        ));
+
+        let bytes = reflect_context.save_binary_to_vec().unwrap();
+        let mut reflect_context = ReflectContext::load_from_memory(&bytes).unwrap();
+        assert!(matches!(
+            reflect_context.enter_region("Missing"),
+            Err(ContextError::RegionDoesNotExist(_))
+        ));
+
+        let mut region = reflect_context.enter_region("Region").unwrap();
+        let mut wrong_type = 0.0f32;
+        assert!(matches!(
+            wrong_type.context("Value", &mut region),
+            Err(ContextError::FieldTypeDoesNotMatch)
+        ));
+        assert!(matches!(
+            wrong_type.context("Other", &mut region),
+            Err(ContextError::FieldDoesNotExist(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_data() {
+        let mut bytes = ReflectContext::new().save_binary_to_vec().unwrap();
+        // A blob that claims to be 4 GiB long must fail on the missing data, not on allocation.
+        bytes.truncate(bytes.len() - 8);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 0, 0, 0, b'B', 14]);
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            ReflectContext::load_from_memory(&bytes),
+            Err(ContextError::Io(_))
+        ));
+
+        let depth = ReflectContext::MAX_DEPTH + 1;
+        let text = format!(
+            "VCTX 1\n\"__ROOT__\" {}{}",
+            "{ \"R\" ".repeat(depth),
+            "}".repeat(depth + 1)
+        );
+        assert!(matches!(
+            ReflectContext::load_text_from_str(&text),
+            Err(ContextError::TooDeep)
+        ));
+
+        let mut invalid = ReflectContext::new();
+        "{f094d7d4-e168}".to_owned().context("Id", &mut invalid).unwrap();
+        let bytes = invalid.save_binary_to_vec().unwrap();
+        let mut invalid = ReflectContext::load_from_memory(&bytes).unwrap();
+        assert!(matches!(
+            UUID::create_null().context("Id", &mut invalid),
+            Err(ContextError::User(_))
+        ));
     }
 }
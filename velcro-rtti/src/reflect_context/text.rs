@@ -0,0 +1,512 @@
+//! Human-readable text format of a [ReflectContext] tree.
+//!
+//! The text format stores exactly the same tree as the binary one, so both can be converted into
+//! each other without losing anything. It looks like this:
+//!
+//! ```text
+//! VCTX 1
+//! "__ROOT__" {
+//!     "Player" {
+//!         "Health": f32 = 97.5
+//!         "Name": str = "Player One"
+//!         "Position" {
+//!             "Length": u32 = 0
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Every region is a quoted name followed by a block in braces, every field is a quoted name, a type
+//! and a value. Indentation is only cosmetic, lines starting with `#` are comments. Floating point
+//! numbers are written with the shortest representation that parses back to the same bits, NaNs are
+//! written with their bit pattern (`NaN(0x7fc00000)`). Binary blobs are written as strings when they
+//! contain valid UTF-8 and as hex digits otherwise.
+
+use crate::memory::{Allocator, Handle};
+use crate::reflect_context::{
+    ContextError, ContextFlags, ContextNode, Field, FieldKind, ReflectContext, ReflectResult,
+};
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    iter::Peekable,
+    path::Path,
+    str::CharIndices,
+};
+
+const INDENT: &str = "    ";
+
+impl ReflectContext {
+    /// Writes the whole tree to `dest` in the text format. See the [text](self) module docs
+    /// for the layout.
+    pub fn save_text(&self, dest: &mut dyn Write) -> ReflectResult {
+        let mut text = String::new();
+        self.write_text(&mut text);
+        dest.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the whole tree to a new string. See [Self::save_text].
+    pub fn save_text_to_string(&self) -> String {
+        let mut text = String::new();
+        self.write_text(&mut text);
+        text
+    }
+
+    /// Writes the whole tree to a file at `path`. See [Self::save_text].
+    pub fn save_text_to_file<P: AsRef<Path>>(&self, path: P) -> ReflectResult {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save_text(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a tree previously written by [Self::save_text]. The returned context is in read mode
+    /// and positioned at the root, just like the one returned by [Self::load_binary].
+    pub fn load_text(src: &mut dyn Read) -> Result<Self, ContextError> {
+        let mut text = String::new();
+        src.read_to_string(&mut text)?;
+        Self::load_text_from_str(&text)
+    }
+
+    /// Reads a tree from a string. See [Self::load_text].
+    pub fn load_text_from_str(text: &str) -> Result<Self, ContextError> {
+        let mut reflect_context = Self {
+            nodes: Allocator::new(),
+            reading: true,
+            current_node: Handle::NONE,
+            root: Handle::NONE,
+            flags: ContextFlags::NONE,
+        };
+
+        let mut parser = Parser::new(text);
+        parser.parse_header()?;
+        parser.skip_whitespace();
+        let name = parser.parse_string()?;
+        reflect_context.root = parser.parse_node(&mut reflect_context, name, Handle::NONE, 0)?;
+        parser.skip_whitespace();
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected text after the root region"));
+        }
+
+        reflect_context.current_node = reflect_context.root;
+        Ok(reflect_context)
+    }
+
+    /// Reads a tree from a file at `path`. See [Self::load_text].
+    pub fn load_text_from_file<P: AsRef<Path>>(path: P) -> Result<Self, ContextError> {
+        Self::load_text_from_str(&std::fs::read_to_string(path)?)
+    }
+
+    fn write_text(&self, text: &mut String) {
+        let _ = writeln!(text, "{} {}", TEXT_MAGIC, Self::VERSION);
+        self.write_node_text(self.root, 0, text);
+    }
+
+    fn write_node_text(&self, handle: Handle<ContextNode>, depth: usize, text: &mut String) {
+        let node = self.nodes.borrow(handle);
+        let indent = INDENT.repeat(depth);
+
+        let _ = write!(text, "{}", indent);
+        write_string(&node.name, text);
+        text.push_str(" {\n");
+
+        for field in node.fields.iter() {
+            let _ = write!(text, "{}{}", indent, INDENT);
+            write_string(&field.name, text);
+            text.push_str(": ");
+            write_field_kind(&field.kind, text);
+            text.push('\n');
+        }
+
+        for child in node.children.iter() {
+            self.write_node_text(*child, depth + 1, text);
+        }
+
+        let _ = writeln!(text, "{}}}", indent);
+    }
+}
+
+const TEXT_MAGIC: &str = "VCTX";
+
+fn write_string(string: &str, text: &mut String) {
+    text.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(text, "\\u{{{:x}}}", c as u32);
+            }
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+}
+
+fn write_field_kind(kind: &FieldKind, text: &mut String) {
+    let _ = match kind {
+        FieldKind::Bool(data) => write!(text, "bool = {}", data),
+        FieldKind::U8(data) => write!(text, "u8 = {}", data),
+        FieldKind::I8(data) => write!(text, "i8 = {}", data),
+        FieldKind::U16(data) => write!(text, "u16 = {}", data),
+        FieldKind::I16(data) => write!(text, "i16 = {}", data),
+        FieldKind::U32(data) => write!(text, "u32 = {}", data),
+        FieldKind::I32(data) => write!(text, "i32 = {}", data),
+        FieldKind::U64(data) => write!(text, "u64 = {}", data),
+        FieldKind::I64(data) => write!(text, "i64 = {}", data),
+        FieldKind::F32(data) => {
+            if data.is_nan() {
+                write!(text, "f32 = NaN({:#010x})", data.to_bits())
+            } else {
+                // Debug formatting prints the shortest string that parses back to the same value.
+                write!(text, "f32 = {:?}", data)
+            }
+        }
+        FieldKind::F64(data) => {
+            if data.is_nan() {
+                write!(text, "f64 = NaN({:#018x})", data.to_bits())
+            } else {
+                write!(text, "f64 = {:?}", data)
+            }
+        }
+        FieldKind::BinaryBlob(data) => match std::str::from_utf8(data) {
+            Ok(string) => {
+                text.push_str("str = ");
+                write_string(string, text);
+                Ok(())
+            }
+            Err(_) => {
+                text.push_str("blob = ");
+                for byte in data {
+                    let _ = write!(text, "{:02x}", byte);
+                }
+                Ok(())
+            }
+        },
+    };
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            chars: text.char_indices().peekable(),
+            line: 1,
+        }
+    }
+
+    fn error(&self, message: &str) -> ContextError {
+        ContextError::TextSyntax {
+            line: self.line,
+            message: message.to_owned(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next().map(|(_, c)| c);
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.bump();
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> ReflectResult {
+        self.skip_whitespace();
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(&format!("expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("expected `{}`, found end of text", expected))),
+        }
+    }
+
+    /// Reads a token that ends at whitespace or at any of the punctuation characters.
+    fn parse_word(&mut self) -> Result<&'a str, ContextError> {
+        self.skip_whitespace();
+        let start = match self.chars.peek() {
+            Some((start, _)) => *start,
+            None => return Err(self.error("unexpected end of text")),
+        };
+        let mut end = start;
+        while let Some((i, c)) = self.chars.peek().copied() {
+            if c.is_whitespace() || matches!(c, '{' | '}' | ':' | '=' | '"' | '#') {
+                break;
+            }
+            end = i + c.len_utf8();
+            self.bump();
+        }
+        if start == end {
+            Err(self.error("expected a value"))
+        } else {
+            Ok(&self.text[start..end])
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ContextError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.bump() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => {
+                        self.expect('{')?;
+                        let mut code = String::new();
+                        loop {
+                            match self.bump() {
+                                Some('}') => break,
+                                Some(c) => code.push(c),
+                                None => return Err(self.error("unterminated escape sequence")),
+                            }
+                        }
+                        let c = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error("invalid unicode escape sequence"))?;
+                        string.push(c);
+                    }
+                    _ => return Err(self.error("invalid escape sequence")),
+                },
+                Some(c) => string.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_header(&mut self) -> ReflectResult {
+        if self.parse_word()? != TEXT_MAGIC {
+            return Err(ContextError::NotSupportedFormat);
+        }
+        let version = self
+            .parse_word()?
+            .parse::<u32>()
+            .map_err(|_| self.error("invalid format version"))?;
+        if version > ReflectContext::VERSION {
+            return Err(ContextError::UnsupportedVersion(version));
+        }
+        Ok(())
+    }
+
+    fn parse_node(
+        &mut self,
+        reflect_context: &mut ReflectContext,
+        name: String,
+        parent: Handle<ContextNode>,
+        depth: usize,
+    ) -> Result<Handle<ContextNode>, ContextError> {
+        if depth > ReflectContext::MAX_DEPTH {
+            return Err(ContextError::TooDeep);
+        }
+        self.expect('{')?;
+
+        let handle = reflect_context.nodes.spawn(ContextNode::new(&name, parent));
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('}') => {
+                    self.bump();
+                    return Ok(handle);
+                }
+                Some('"') => {
+                    let name = self.parse_string()?;
+                    self.skip_whitespace();
+                    if self.peek() == Some(':') {
+                        self.bump();
+                        let kind = self.parse_field_kind()?;
+                        reflect_context
+                            .nodes
+                            .borrow_mut(handle)
+                            .fields
+                            .push(Field::new(&name, kind));
+                    } else {
+                        let child = self.parse_node(reflect_context, name, handle, depth + 1)?;
+                        reflect_context.nodes.borrow_mut(handle).children.push(child);
+                    }
+                }
+                Some(c) => {
+                    return Err(self.error(&format!(
+                        "expected a quoted name or `}}`, found `{}`",
+                        c
+                    )))
+                }
+                None => return Err(self.error("expected `}`, found end of text")),
+            }
+        }
+    }
+
+    fn parse_field_kind(&mut self) -> Result<FieldKind, ContextError> {
+        let ty = self.parse_word()?;
+        self.expect('=')?;
+
+        if ty == "str" {
+            return Ok(FieldKind::BinaryBlob(self.parse_string()?.into_bytes()));
+        }
+
+        let value = self.parse_word()?;
+        let invalid = || self.error(&format!("invalid `{}` value `{}`", ty, value));
+
+        macro_rules! parse {
+            ($kind:ident) => {
+                FieldKind::$kind(value.parse().map_err(|_| invalid())?)
+            };
+        }
+
+        Ok(match ty {
+            "bool" => parse!(Bool),
+            "u8" => parse!(U8),
+            "i8" => parse!(I8),
+            "u16" => parse!(U16),
+            "i16" => parse!(I16),
+            "u32" => parse!(U32),
+            "i32" => parse!(I32),
+            "u64" => parse!(U64),
+            "i64" => parse!(I64),
+            "f32" => FieldKind::F32(match nan_bits(value) {
+                Some(bits) => f32::from_bits(u32::from_str_radix(bits, 16).map_err(|_| invalid())?),
+                None => value.parse().map_err(|_| invalid())?,
+            }),
+            "f64" => FieldKind::F64(match nan_bits(value) {
+                Some(bits) => f64::from_bits(u64::from_str_radix(bits, 16).map_err(|_| invalid())?),
+                None => value.parse().map_err(|_| invalid())?,
+            }),
+            "blob" => {
+                if value.len() % 2 != 0 || !value.is_ascii() {
+                    return Err(invalid());
+                }
+                FieldKind::BinaryBlob(
+                    (0..value.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(&value[i..i + 2], 16))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid())?,
+                )
+            }
+            _ => return Err(self.error(&format!("unknown field type `{}`", ty))),
+        })
+    }
+}
+
+/// Extracts hex digits from `NaN(0x...)`.
+fn nan_bits(value: &str) -> Option<&str> {
+    value.strip_prefix("NaN(0x")?.strip_suffix(')')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reflect_context::prelude::*;
+
+    #[derive(Default, Debug, PartialEq, Context)]
+    struct Level {
+        name: String,
+        gravity: f32,
+        scale: f64,
+        seed: u64,
+        enabled: bool,
+        spawn_points: Vec<[f32; 2]>,
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut original = Level {
+            name: "Level \"1\"\n\tcaves".to_string(),
+            gravity: -9.81,
+            scale: 0.1 + 0.2,
+            seed: u64::MAX,
+            enabled: true,
+            spawn_points: vec![[1.0e-45, -0.0], [f32::MAX, f32::INFINITY]],
+        };
+
+        let mut reflect_context = ReflectContext::new();
+        original.context("Level", &mut reflect_context).unwrap();
+        let text = reflect_context.save_text_to_string();
+        assert!(text.contains("\"Gravity\": f32 = -9.81"));
+
+        let mut reflect_context = ReflectContext::load_text_from_str(&text).unwrap();
+        let mut loaded = Level::default();
+        loaded.context("Level", &mut reflect_context).unwrap();
+
+        assert_eq!(original, loaded);
+        assert_eq!(loaded.scale.to_bits(), (0.1f64 + 0.2).to_bits());
+        assert!(loaded.spawn_points[0][1].is_sign_negative());
+
+        // Text and binary formats store the same tree.
+        let binary = reflect_context.save_binary_to_vec().unwrap();
+        let reflect_context = ReflectContext::load_from_memory(&binary).unwrap();
+        assert_eq!(reflect_context.save_text_to_string(), text);
+    }
+
+    #[test]
+    fn non_utf8_blob_is_written_as_hex() {
+        let mut blob = vec![0xff, 0x00, 0x7f];
+        let mut reflect_context = ReflectContext::new();
+        reflect_context.context_binary_blob("Blob", &mut blob).unwrap();
+        let text = reflect_context.save_text_to_string();
+        assert!(text.contains("\"Blob\": blob = ff007f"));
+
+        let mut reflect_context = ReflectContext::load_text_from_str(&text).unwrap();
+        let mut loaded = Vec::new();
+        reflect_context.context_binary_blob("Blob", &mut loaded).unwrap();
+        assert_eq!(loaded, blob);
+    }
+
+    #[test]
+    fn nan_bits_are_preserved() {
+        let mut value = f32::from_bits(0x7fc0_1234);
+        let mut reflect_context = ReflectContext::new();
+        value.context("Value", &mut reflect_context).unwrap();
+        let text = reflect_context.save_text_to_string();
+
+        let mut reflect_context = ReflectContext::load_text_from_str(&text).unwrap();
+        let mut loaded = 0.0f32;
+        loaded.context("Value", &mut reflect_context).unwrap();
+        assert_eq!(loaded.to_bits(), 0x7fc0_1234);
+    }
+
+    #[test]
+    fn syntax_errors_report_line() {
+        let text = "VCTX 1\n\"__ROOT__\" {\n    \"Value\": u32 = -1\n}\n";
+        match ReflectContext::load_text_from_str(text) {
+            Err(ContextError::TextSyntax { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("expected a syntax error"),
+        }
+
+        assert!(matches!(
+            ReflectContext::load_text_from_str("JSON 1"),
+            Err(ContextError::NotSupportedFormat)
+        ));
+    }
+}
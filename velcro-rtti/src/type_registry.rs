@@ -0,0 +1,324 @@
+//! Global registry of reflectable types.
+//!
+//! The registry maps [`TypeUuidProvider`] UUIDs (and type names) to a factory that creates a
+//! default instance of the type as `Box<dyn Reflect>`. It is used to restore polymorphic values
+//! (see [`crate::reflect::TypedReflectDeserializer`]) and by editor tools to list all creatable
+//! types.
+//!
+//! ```
+//! # use velcro_rtti::{reflect::prelude::*, type_registry::TypeRegistry, type_traits::prelude::*};
+//! #[derive(Reflect, TypeUuidProvider, Default, Debug)]
+//! #[type_uuid(id = "{7b9a1f60-8f0e-4c56-9b2a-2f7f5f0c1d11}")]
+//! struct Light {
+//!     intensity: f32,
+//! }
+//!
+//! let registry = TypeRegistry::new();
+//! registry.register::<Light>().unwrap();
+//!
+//! let light = registry.create_by_uuid(&Light::type_uuid()).unwrap();
+//! assert!(light.is::<Light>());
+//! ```
+
+use crate::{parking_lot::RwLock, reflect::prelude::Reflect, type_traits::TypeUuidProvider, UUID};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+/// A factory that creates a default instance of a registered type.
+pub type TypeFactory = fn() -> Box<dyn Reflect>;
+
+/// Information about a registered type.
+#[derive(Clone, Copy)]
+pub struct TypeDefinition {
+    /// Unique identifier of the type, see [`TypeUuidProvider`].
+    pub type_uuid: UUID,
+
+    /// Rust type id of the type.
+    pub type_id: TypeId,
+
+    /// Full name of the type, the same as returned by [`Reflect::type_name`].
+    pub type_name: &'static str,
+
+    /// Name of the crate that defines the type, see [`Reflect::assembly_name`].
+    pub assembly_name: &'static str,
+
+    /// Creates a default instance of the type.
+    pub factory: TypeFactory,
+}
+
+impl fmt::Debug for TypeDefinition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypeDefinition")
+            .field("type_uuid", &self.type_uuid)
+            .field("type_name", &self.type_name)
+            .field("assembly_name", &self.assembly_name)
+            .finish()
+    }
+}
+
+/// An error that may occur during type registration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeRegistryError {
+    /// The UUID is already used by another type.
+    UuidCollision {
+        /// The UUID that is used twice.
+        type_uuid: UUID,
+        /// Name of the type that was registered first.
+        registered: &'static str,
+        /// Name of the type that was rejected.
+        rejected: &'static str,
+    },
+}
+
+impl Display for TypeRegistryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeRegistryError::UuidCollision {
+                type_uuid,
+                registered,
+                rejected,
+            } => write!(
+                f,
+                "unable to register {rejected}: uuid {} is already used by {registered}",
+                type_uuid.to_string(true, true)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TypeRegistryError {}
+
+#[derive(Default)]
+struct RegistryState {
+    definitions: HashMap<UUID, TypeDefinition>,
+    by_name: HashMap<&'static str, UUID>,
+    by_type_id: HashMap<TypeId, UUID>,
+}
+
+/// Thread-safe storage of [`TypeDefinition`]s. Most of the time the global instance returned by
+/// [`TypeRegistry::global`] should be used, separate instances are useful to limit the set of
+/// types (for example, for a plugin).
+#[derive(Default)]
+pub struct TypeRegistry {
+    state: RwLock<RegistryState>,
+}
+
+lazy_static! {
+    static ref GLOBAL_REGISTRY: TypeRegistry = TypeRegistry::new();
+}
+
+impl TypeRegistry {
+    /// Creates a new empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the global type registry.
+    #[inline]
+    pub fn global() -> &'static TypeRegistry {
+        &GLOBAL_REGISTRY
+    }
+
+    /// Registers a type that can be created with [`Default`]. Registering the same type twice is
+    /// allowed and does nothing.
+    pub fn register<T>(&self) -> Result<(), TypeRegistryError>
+    where
+        T: Reflect + TypeUuidProvider + Default,
+    {
+        self.register_with_factory::<T>(|| Box::new(T::default()))
+    }
+
+    /// Registers a type with a custom factory. The factory must return an instance of `T`.
+    pub fn register_with_factory<T>(&self, factory: TypeFactory) -> Result<(), TypeRegistryError>
+    where
+        T: Reflect + TypeUuidProvider,
+    {
+        self.add(TypeDefinition {
+            type_uuid: T::type_uuid(),
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            assembly_name: T::type_assembly_name(),
+            factory,
+        })
+    }
+
+    fn add(&self, definition: TypeDefinition) -> Result<(), TypeRegistryError> {
+        let mut state = self.state.write();
+
+        if let Some(existing) = state.definitions.get(&definition.type_uuid) {
+            return if existing.type_id == definition.type_id {
+                Ok(())
+            } else {
+                Err(TypeRegistryError::UuidCollision {
+                    type_uuid: definition.type_uuid,
+                    registered: existing.type_name,
+                    rejected: definition.type_name,
+                })
+            };
+        }
+
+        state
+            .by_name
+            .insert(definition.type_name, definition.type_uuid);
+        state
+            .by_type_id
+            .insert(definition.type_id, definition.type_uuid);
+        state.definitions.insert(definition.type_uuid, definition);
+
+        Ok(())
+    }
+
+    /// Removes a type from the registry and returns its definition, if any.
+    pub fn unregister(&self, type_uuid: &UUID) -> Option<TypeDefinition> {
+        let mut state = self.state.write();
+        let definition = state.definitions.remove(type_uuid)?;
+        state.by_name.remove(definition.type_name);
+        state.by_type_id.remove(&definition.type_id);
+        Some(definition)
+    }
+
+    /// Returns a definition of the type with the given UUID.
+    pub fn definition(&self, type_uuid: &UUID) -> Option<TypeDefinition> {
+        self.state.read().definitions.get(type_uuid).copied()
+    }
+
+    /// Returns a definition of the type with the given name (see [`Reflect::type_name`]).
+    pub fn definition_by_name(&self, type_name: &str) -> Option<TypeDefinition> {
+        let state = self.state.read();
+        let type_uuid = state.by_name.get(type_name)?;
+        state.definitions.get(type_uuid).copied()
+    }
+
+    /// Returns a definition of the type with the given type id.
+    pub fn definition_by_type_id(&self, type_id: TypeId) -> Option<TypeDefinition> {
+        let state = self.state.read();
+        let type_uuid = state.by_type_id.get(&type_id)?;
+        state.definitions.get(type_uuid).copied()
+    }
+
+    /// Returns a UUID of the type with the given type id.
+    pub fn type_uuid_of(&self, type_id: TypeId) -> Option<UUID> {
+        self.state.read().by_type_id.get(&type_id).copied()
+    }
+
+    /// Creates a default instance of the type with the given UUID.
+    pub fn create_by_uuid(&self, type_uuid: &UUID) -> Option<Box<dyn Reflect>> {
+        // The lock is released before calling the factory, so factories may use the registry.
+        self.definition(type_uuid)
+            .map(|definition| (definition.factory)())
+    }
+
+    /// Creates a default instance of the type with the given name.
+    pub fn create_by_name(&self, type_name: &str) -> Option<Box<dyn Reflect>> {
+        self.definition_by_name(type_name)
+            .map(|definition| (definition.factory)())
+    }
+
+    /// Creates a default instance of the type with the given type id.
+    pub fn create_by_type_id(&self, type_id: TypeId) -> Option<Box<dyn Reflect>> {
+        self.definition_by_type_id(type_id)
+            .map(|definition| (definition.factory)())
+    }
+
+    /// Returns `true` if a type with the given UUID is registered.
+    pub fn is_registered(&self, type_uuid: &UUID) -> bool {
+        self.state.read().definitions.contains_key(type_uuid)
+    }
+
+    /// Returns definitions of all registered types, sorted by type name.
+    pub fn definitions(&self) -> Vec<TypeDefinition> {
+        let mut definitions = self
+            .state
+            .read()
+            .definitions
+            .values()
+            .copied()
+            .collect::<Vec<_>>();
+        definitions.sort_by_key(|definition| definition.type_name);
+        definitions
+    }
+
+    /// Returns amount of registered types.
+    pub fn len(&self) -> usize {
+        self.state.read().definitions.len()
+    }
+
+    /// Returns `true` if there are no registered types.
+    pub fn is_empty(&self) -> bool {
+        self.state.read().definitions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::prelude::*;
+    use crate::uuid_provider;
+
+    #[derive(Reflect, Default, Debug)]
+    struct Sprite {
+        size: f32,
+    }
+
+    #[derive(Reflect, Default, Debug)]
+    struct Mesh {
+        vertices: Vec<f32>,
+    }
+
+    #[derive(Reflect, Default, Debug)]
+    struct Impostor;
+
+    uuid_provider!(Sprite = "{0c8b2d54-6a43-4e0b-9a3f-8e8f7b6f3d01}");
+    uuid_provider!(Mesh = "{d9b3f0a2-1c8e-4f8d-a8d4-3a0c2b9e6f02}");
+    uuid_provider!(Impostor = "{0c8b2d54-6a43-4e0b-9a3f-8e8f7b6f3d01}");
+
+    #[test]
+    fn register_and_create() {
+        let registry = TypeRegistry::new();
+        registry.register::<Sprite>().unwrap();
+        registry.register::<Mesh>().unwrap();
+        registry.register::<Sprite>().unwrap();
+        assert_eq!(registry.len(), 2);
+
+        let sprite = registry.create_by_uuid(&Sprite::type_uuid()).unwrap();
+        assert!(sprite.is::<Sprite>());
+
+        let mesh = registry
+            .create_by_name(std::any::type_name::<Mesh>())
+            .unwrap();
+        assert!(mesh.is::<Mesh>());
+        assert_eq!(
+            registry.type_uuid_of(TypeId::of::<Mesh>()),
+            Some(Mesh::type_uuid())
+        );
+
+        let names = registry
+            .definitions()
+            .iter()
+            .map(|d| d.type_name)
+            .collect::<Vec<_>>();
+        assert!(names[0].ends_with("Mesh") && names[1].ends_with("Sprite"));
+
+        assert!(registry.unregister(&Mesh::type_uuid()).is_some());
+        assert!(registry
+            .create_by_name(std::any::type_name::<Mesh>())
+            .is_none());
+    }
+
+    #[test]
+    fn uuid_collision_is_rejected() {
+        let registry = TypeRegistry::new();
+        registry.register::<Sprite>().unwrap();
+        assert!(matches!(
+            registry.register::<Impostor>(),
+            Err(TypeRegistryError::UuidCollision { .. })
+        ));
+        assert!(registry
+            .create_by_uuid(&Sprite::type_uuid())
+            .unwrap()
+            .is::<Sprite>());
+    }
+}
@@ -1,14 +1,15 @@
 use crate::{
-    reflect::prelude::*,
-    reflect_context::{prelude::*, VisitorFlags},
+    reflect::{clone_leaf, concrete_type_id, field_path, key_string, prelude::*},
+    reflect_context::{prelude::*, ContextFlags},
 };
 
 use bitflags::{bitflags, Flags};
 use std::{
     any::{Any, TypeId},
     cell::Cell,
-    fmt::Debug,
+    fmt::{self, Debug, Display, Formatter},
     ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 use velcro_derive::Reflect;
@@ -55,11 +56,82 @@ impl Debug for VariableFlags {
 pub enum InheritError {
     /// Types of properties mismatch.
     TypesMismatch {
+        /// Path to the property.
+        path: String,
         /// Type of left property.
         left_type: &'static str,
         /// Type of right property.
         right_type: &'static str,
     },
+    /// The value of the parent property can't be copied, its type supports neither
+    /// [`Reflect::clone_box`] nor is a primitive.
+    NotCloneable {
+        /// Path to the property.
+        path: String,
+        /// Type of the property.
+        type_name: &'static str,
+    },
+}
+
+impl Display for InheritError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InheritError::TypesMismatch {
+                path,
+                left_type,
+                right_type,
+            } => write!(f, "type mismatch at `{path}`: {left_type} vs {right_type}"),
+            InheritError::NotCloneable { path, type_name } => {
+                write!(f, "unable to copy a value of {type_name} at `{path}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InheritError {}
+
+/// A change of an [`InheritableVariable`] passed to its observers.
+#[derive(Debug)]
+pub enum VariableChange<'a, T> {
+    /// The value was replaced with [`InheritableVariable::set_value_and_mark_modified`].
+    Set {
+        /// The previous value.
+        old: &'a T,
+        /// The new value.
+        new: &'a T,
+    },
+    /// The value was changed through reflection ([`Reflect::set`] or [`Reflect::set_field`]).
+    Modified(&'a T),
+    /// A mutable reference to the value was handed out with
+    /// [`InheritableVariable::get_value_mut_and_mark_modified`] or `DerefMut`. Observers are called
+    /// before the reference is returned, so the value can be read only after the modification.
+    MutableAccess,
+    /// The value was taken from a parent object by [`try_inherit`].
+    Inherited(&'a T),
+}
+
+/// An identifier of an observer of an [`InheritableVariable`], used to unsubscribe it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+impl ObserverId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+type ObserverCallback<T> = Box<dyn FnMut(&VariableChange<'_, T>) + Send>;
+
+struct Observer<T> {
+    id: ObserverId,
+    callback: ObserverCallback<T>,
+}
+
+fn notify<T>(observers: &mut [Observer<T>], change: VariableChange<'_, T>) {
+    for observer in observers {
+        (observer.callback)(&change);
+    }
 }
 
 /// 变量的包装器，包含附加标志，表明初始值在运行时已更改.
@@ -79,9 +151,16 @@ pub enum InheritError {
 ///
 /// Access via Deref provides access to inner variable. **DerefMut marks variable as modified** and returns a
 /// mutable reference to inner variable.
+///
+/// # Observers
+///
+/// Functions added with [`InheritableVariable::subscribe`] are called on every change of the value
+/// that marks the variable modified, see [`VariableChange`]. Observers are not cloned together
+/// with the variable.
 pub struct InheritableVariable<T> {
     value: T,
     flags: Cell<VariableFlags>,
+    observers: Vec<Observer<T>>,
 }
 
 impl<T: Debug> Debug for InheritableVariable<T> {
@@ -96,6 +175,7 @@ impl<T: Clone> Clone for InheritableVariable<T> {
         Self {
             value: self.value.clone(),
             flags: self.flags.clone(),
+            observers: Vec::new(),
         }
     }
 }
@@ -123,6 +203,7 @@ impl<T: Default> Default for InheritableVariable<T> {
         Self {
             value: T::default(),
             flags: Cell::new(VariableFlags::MODIFIED),
+            observers: Vec::new(),
         }
     }
 }
@@ -163,6 +244,7 @@ impl<T> InheritableVariable<T> {
         Self {
             value,
             flags: Cell::new(VariableFlags::MODIFIED),
+            observers: Vec::new(),
         }
     }
 
@@ -172,6 +254,7 @@ impl<T> InheritableVariable<T> {
         Self {
             value,
             flags: Cell::new(VariableFlags::NONE),
+            observers: Vec::new(),
         }
     }
 
@@ -181,14 +264,24 @@ impl<T> InheritableVariable<T> {
         Self {
             value,
             flags: Cell::new(flags),
+            observers: Vec::new(),
         }
     }
 
-    /// Replaces value and also raises the [`VariableFlags::MODIFIED`] flag.
+    /// Replaces value and also raises the [`VariableFlags::MODIFIED`] flag. Observers are notified
+    /// with [`VariableChange::Set`].
     #[inline]
     pub fn set_value_and_mark_modified(&mut self, value: T) -> T {
         self.mark_modified_and_need_sync();
-        std::mem::replace(&mut self.value, value)
+        let old = std::mem::replace(&mut self.value, value);
+        notify(
+            &mut self.observers,
+            VariableChange::Set {
+                old: &old,
+                new: &self.value,
+            },
+        );
+        old
     }
 
     /// Replaces value and flags.
@@ -220,10 +313,12 @@ impl<T> InheritableVariable<T> {
     ///
     /// # Important notes.
     ///
-    /// The method raises `modified` flag, no matter if actual modification was made!
+    /// The method raises `modified` flag, no matter if actual modification was made! Observers are
+    /// notified with [`VariableChange::MutableAccess`].
     #[inline]
     pub fn get_value_mut_and_mark_modified(&mut self) -> &mut T {
         self.mark_modified_and_need_sync();
+        notify(&mut self.observers, VariableChange::MutableAccess);
         &mut self.value
     }
 
@@ -251,6 +346,26 @@ impl<T> InheritableVariable<T> {
             .insert(VariableFlags::MODIFIED | VariableFlags::NEED_SYNC);
     }
 
+    /// Adds a function that will be called on every change of the value, see [`VariableChange`].
+    pub fn subscribe<F>(&mut self, callback: F) -> ObserverId
+    where
+        F: FnMut(&VariableChange<'_, T>) + Send + 'static,
+    {
+        let id = ObserverId::next();
+        self.observers.push(Observer {
+            id,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Removes the observer, returns `false` if it wasn't subscribed to the variable.
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        let count = self.observers.len();
+        self.observers.retain(|observer| observer.id != id);
+        self.observers.len() != count
+    }
+
     /// Deconstructs the variable and returns the wrapped value.
     #[inline]
     pub fn take(self) -> T {
@@ -265,6 +380,42 @@ impl<T> InheritableVariable<T> {
     }
 }
 
+impl<T> Context for InheritableVariable<T>
+where
+    T: Context,
+{
+    /// Read or write this value, depending on whether [ReflectContext::is_reading()] is true or false.
+    ///
+    /// # In Write Mode
+    ///
+    /// The value is written as a compound of two fields in a region with the given name:
+    /// `Value` and `Flags`. The value is written only if it was modified or if
+    /// [ContextFlags::SERIALIZE_EVERYTHING] is set, otherwise nothing is written at all, because
+    /// an unmodified value will be taken from a parent object during inheritance.
+    ///
+    /// # In Read Mode
+    ///
+    /// The value is read if the region exists, otherwise the variable is left as is.
+    fn context(&mut self, name: &str, reflect_context: &mut ReflectContext) -> ReflectResult {
+        if reflect_context.is_reading() {
+            if let Ok(mut region) = reflect_context.enter_region(name) {
+                self.value.context("Value", &mut region)?;
+                self.flags.get_mut().0.context("Flags", &mut region)?;
+            }
+        } else if self.is_modified()
+            || reflect_context
+                .flags
+                .contains(ContextFlags::SERIALIZE_EVERYTHING)
+        {
+            let mut region = reflect_context.enter_region(name)?;
+            self.value.context("Value", &mut region)?;
+            self.flags.get_mut().0.context("Flags", &mut region)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<T> Deref for InheritableVariable<T> {
     type Target = T;
 
@@ -277,8 +428,775 @@ impl<T> Deref for InheritableVariable<T> {
 impl<T> DerefMut for InheritableVariable<T> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.mark_modified_and_need_sync();
+        self.get_value_mut_and_mark_modified()
+    }
+}
+
+/// Type-erased access to an [`InheritableVariable`], available through
+/// [`Reflect::as_inheritable_variable`].
+pub trait ReflectInheritableVariable: Reflect + Debug {
+    fn flags(&self) -> VariableFlags;
+
+    fn set_flags(&mut self, flags: VariableFlags);
+
+    fn is_modified(&self) -> bool;
+
+    fn need_sync(&self) -> bool;
+
+    fn mark_modified(&mut self);
+
+    /// Removes the [`VariableFlags::MODIFIED`] flag, so the value will be inherited again.
+    fn reset_modified_flag(&mut self);
+
+    /// Removes the [`VariableFlags::NEED_SYNC`] flag.
+    fn reset_need_sync_flag(&mut self);
+
+    fn inner_value_ref(&self) -> &dyn Reflect;
+
+    fn inner_value_mut(&mut self) -> &mut dyn Reflect;
+
+    /// Replaces the value with a value of a parent without raising the [`VariableFlags::MODIFIED`]
+    /// flag. Returns the previous value, or the given value back if its type is wrong.
+    fn inherit_value(
+        &mut self,
+        value: Box<dyn Reflect>,
+    ) -> Result<Box<dyn Reflect>, Box<dyn Reflect>>;
+}
+
+impl<T: Reflect> ReflectInheritableVariable for InheritableVariable<T> {
+    #[inline]
+    fn flags(&self) -> VariableFlags {
+        self.flags.get()
+    }
+
+    #[inline]
+    fn set_flags(&mut self, flags: VariableFlags) {
+        self.flags.set(flags)
+    }
+
+    #[inline]
+    fn is_modified(&self) -> bool {
+        InheritableVariable::is_modified(self)
+    }
+
+    #[inline]
+    fn need_sync(&self) -> bool {
+        InheritableVariable::need_sync(self)
+    }
+
+    #[inline]
+    fn mark_modified(&mut self) {
+        InheritableVariable::mark_modified(self)
+    }
+
+    #[inline]
+    fn reset_modified_flag(&mut self) {
+        self.flags.get_mut().remove(VariableFlags::MODIFIED)
+    }
+
+    #[inline]
+    fn reset_need_sync_flag(&mut self) {
+        self.flags.get_mut().remove(VariableFlags::NEED_SYNC)
+    }
+
+    #[inline]
+    fn inner_value_ref(&self) -> &dyn Reflect {
+        &self.value
+    }
+
+    #[inline]
+    fn inner_value_mut(&mut self) -> &mut dyn Reflect {
         &mut self.value
     }
+
+    fn inherit_value(
+        &mut self,
+        value: Box<dyn Reflect>,
+    ) -> Result<Box<dyn Reflect>, Box<dyn Reflect>> {
+        let old = self.value.set(value)?;
+        self.flags.get_mut().insert(VariableFlags::NEED_SYNC);
+        notify(&mut self.observers, VariableChange::Inherited(&self.value));
+        Ok(old)
+    }
+}
+
+/// The variable is transparent for reflection: everything is delegated to the wrapped value,
+/// except [`Reflect::as_inheritable_variable`]. Values set with [`Reflect::set`] and
+/// [`Reflect::set_field`] mark the variable modified and notify its observers. Other mutable
+/// accessors (`field_mut`, `as_any_mut`, etc.) are silent, so walking an object with them, for
+/// example to remap handles, doesn't change the flags.
+impl<T: Reflect> Reflect for InheritableVariable<T> {
+    fn source_path() -> &'static str {
+        file!()
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.value.type_name()
+    }
+
+    fn doc(&self) -> &'static str {
+        self.value.doc()
+    }
+
+    fn assembly_name(&self) -> &'static str {
+        self.value.assembly_name()
+    }
+
+    fn type_assembly_name() -> &'static str {
+        T::type_assembly_name()
+    }
+
+    fn fields_info(&self, func: &mut dyn FnMut(&[FieldInfo])) {
+        self.value.fields_info(func)
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        Box::new(self.value).into_any()
+    }
+
+    fn as_any(&self, func: &mut dyn FnMut(&dyn Any)) {
+        self.value.as_any(func)
+    }
+
+    fn as_any_mut(&mut self, func: &mut dyn FnMut(&mut dyn Any)) {
+        self.value.as_any_mut(func)
+    }
+
+    fn as_reflect(&self, func: &mut dyn FnMut(&dyn Reflect)) {
+        self.value.as_reflect(func)
+    }
+
+    fn as_reflect_mut(&mut self, func: &mut dyn FnMut(&mut dyn Reflect)) {
+        self.value.as_reflect_mut(func)
+    }
+
+    fn set(&mut self, value: Box<dyn Reflect>) -> Result<Box<dyn Reflect>, Box<dyn Reflect>> {
+        let old = self.value.set(value)?;
+        self.mark_modified_and_need_sync();
+        notify(&mut self.observers, VariableChange::Modified(&self.value));
+        Ok(old)
+    }
+
+    fn set_field(
+        &mut self,
+        field: &str,
+        value: Box<dyn Reflect>,
+        func: &mut dyn FnMut(Result<Box<dyn Reflect>, SetFieldError>),
+    ) {
+        let mut changed = false;
+        self.value.set_field(field, value, &mut |result| {
+            changed = result.is_ok();
+            func(result)
+        });
+        if changed {
+            self.mark_modified_and_need_sync();
+            notify(&mut self.observers, VariableChange::Modified(&self.value));
+        }
+    }
+
+    fn fields(&self, func: &mut dyn FnMut(&[&dyn Reflect])) {
+        self.value.fields(func)
+    }
+
+    fn fields_mut(&mut self, func: &mut dyn FnMut(&mut [&mut dyn Reflect])) {
+        self.value.fields_mut(func)
+    }
+
+    fn field(&self, name: &str, func: &mut dyn FnMut(Option<&dyn Reflect>)) {
+        self.value.field(name, func)
+    }
+
+    fn field_mut(&mut self, name: &str, func: &mut dyn FnMut(Option<&mut dyn Reflect>)) {
+        self.value.field_mut(name, func)
+    }
+
+    fn as_array(&self, func: &mut dyn FnMut(Option<&dyn ReflectArray>)) {
+        self.value.as_array(func)
+    }
+
+    fn as_array_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectArray>)) {
+        self.value.as_array_mut(func)
+    }
+
+    fn as_list(&self, func: &mut dyn FnMut(Option<&dyn ReflectList>)) {
+        self.value.as_list(func)
+    }
+
+    fn as_list_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectList>)) {
+        self.value.as_list_mut(func)
+    }
+
+    fn as_hash_map(&self, func: &mut dyn FnMut(Option<&dyn ReflectHashMap>)) {
+        self.value.as_hash_map(func)
+    }
+
+    fn as_hash_map_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectHashMap>)) {
+        self.value.as_hash_map_mut(func)
+    }
+
+    fn as_enum(&self, func: &mut dyn FnMut(Option<&dyn ReflectEnum>)) {
+        self.value.as_enum(func)
+    }
+
+    fn as_enum_mut(&mut self, func: &mut dyn FnMut(Option<&mut dyn ReflectEnum>)) {
+        self.value.as_enum_mut(func)
+    }
+
+    fn as_inheritable_variable(
+        &self,
+        func: &mut dyn FnMut(Option<&dyn ReflectInheritableVariable>),
+    ) {
+        func(Some(self))
+    }
+
+    fn as_inheritable_variable_mut(
+        &mut self,
+        func: &mut dyn FnMut(Option<&mut dyn ReflectInheritableVariable>),
+    ) {
+        func(Some(self))
+    }
+
+    fn methods_info(&self) -> &'static [MethodInfo] {
+        self.value.methods_info()
+    }
+
+    fn call_method(
+        &mut self,
+        name: &str,
+        args: Vec<Box<dyn Reflect>>,
+    ) -> Result<Box<dyn Reflect>, CallError> {
+        self.value.call_method(name, args)
+    }
+
+    fn call_method_ref(
+        &self,
+        name: &str,
+        args: Vec<Box<dyn Reflect>>,
+    ) -> Result<Box<dyn Reflect>, CallError> {
+        self.value.call_method_ref(name, args)
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn Reflect>> {
+        self.value.clone_box()
+    }
+
+    fn reflect_eq(&self, other: &dyn Reflect) -> Option<bool> {
+        self.value.reflect_eq(other)
+    }
+}
+
+/// Walks the object tree (struct fields, array items and hash map values with string keys) and
+/// calls the function for every variable that has the [`VariableFlags::NEED_SYNC`] flag, together
+/// with a path to it in the syntax of [`ResolvePath`](crate::reflect::ResolvePath).
+pub fn gather_dirty_variables(
+    object: &dyn Reflect,
+    func: &mut dyn FnMut(&str, &dyn ReflectInheritableVariable),
+) {
+    visit_variables("", object, &mut |path, variable| {
+        if variable.need_sync() {
+            func(path, variable)
+        }
+    })
+}
+
+/// The same as [`gather_dirty_variables`], but passes wrapped values of variables to the function
+/// and removes the [`VariableFlags::NEED_SYNC`] flag from them. Useful to send all changes of an
+/// object once per frame (network replication, UI bindings, etc.).
+pub fn sync_dirty_variables(object: &mut dyn Reflect, func: &mut dyn FnMut(&str, &dyn Reflect)) {
+    visit_variables_mut("", object, &mut |path, variable| {
+        if variable.need_sync() {
+            func(path, variable.inner_value_ref());
+            variable.reset_need_sync_flag();
+        }
+    })
+}
+
+/// Removes the [`VariableFlags::MODIFIED`] flag from every variable in the object tree, so all of
+/// them will be inherited from a parent.
+pub fn mark_inheritable_properties_non_modified(object: &mut dyn Reflect) {
+    visit_variables_mut("", object, &mut |_, variable| {
+        variable.reset_modified_flag()
+    })
 }
 
+/// Copies values of variables of the parent object into respective variables of the child object
+/// that are not [modified](InheritableVariable::is_modified). This is how instances of a prefab
+/// pick up changes of the prefab.
+///
+/// Both objects are walked together: struct fields are matched by name, array items by index and
+/// hash map values by key; map values that exist only in the child are left as is. Lists are
+/// resized to the length of the parent list: extra items are removed and missing items are copied
+/// from the parent, except for lists inside of modified variables, which keep their length. A
+/// non-modified variable takes a copy of the whole parent value, variables nested in the copy
+/// become non-modified, observers of variables nested in the replaced value are dropped together
+/// with it. Modified variables are walked further, since variables inside of them can still be
+/// inherited.
+///
+/// ```
+/// use velcro_rtti::{
+///     reflect::prelude::*,
+///     variable::{try_inherit, InheritableVariable},
+/// };
+///
+/// #[derive(Reflect, Debug, Clone, PartialEq)]
+/// struct Light {
+///     color: InheritableVariable<[u8; 3]>,
+///     radius: InheritableVariable<f32>,
+/// }
+///
+/// let prefab = Light {
+///     color: [255, 0, 0].into(),
+///     radius: 10.0.into(),
+/// };
+/// let mut instance = Light {
+///     color: InheritableVariable::new_non_modified([255, 255, 255]),
+///     radius: 3.0.into(),
+/// };
+///
+/// try_inherit(&mut instance, &prefab).unwrap();
+/// assert_eq!(*instance.color, [255, 0, 0]);
+/// assert_eq!(*instance.radius, 3.0);
+/// ```
+///
+/// Inheritance stops at the first error, values inherited before it stay in the child.
+pub fn try_inherit(child: &mut dyn Reflect, parent: &dyn Reflect) -> Result<(), InheritError> {
+    inherit_recursive("", child, parent, true)
+}
+
+fn visit_variables(
+    path: &str,
+    object: &dyn Reflect,
+    func: &mut dyn FnMut(&str, &dyn ReflectInheritableVariable),
+) {
+    object.as_inheritable_variable(&mut |variable| {
+        if let Some(variable) = variable {
+            func(path, variable)
+        }
+    });
+
+    let mut done = false;
+    object.as_array(&mut |array| {
+        if let Some(array) = array {
+            done = true;
+            for i in 0..array.reflect_len() {
+                if let Some(item) = array.reflect_index(i) {
+                    visit_variables(&format!("{path}[{i}]"), item, func);
+                }
+            }
+        }
+    });
+    if done {
+        return;
+    }
+
+    object.as_hash_map(&mut |map| {
+        if let Some(map) = map {
+            done = true;
+            for i in 0..map.reflect_len() {
+                if let Some((key, value)) = map.reflect_get_at(i) {
+                    if let Some(key) = key_string(key) {
+                        visit_variables(&format!("{path}[{key}]"), value, func);
+                    }
+                }
+            }
+        }
+    });
+    if done {
+        return;
+    }
+
+    object.fields_info(&mut |fields| {
+        for field in fields {
+            visit_variables(&field_path(path, field.name), field.reflect_value, func);
+        }
+    });
+}
+
+fn visit_variables_mut(
+    path: &str,
+    object: &mut dyn Reflect,
+    func: &mut dyn FnMut(&str, &mut dyn ReflectInheritableVariable),
+) {
+    object.as_inheritable_variable_mut(&mut |variable| {
+        if let Some(variable) = variable {
+            func(path, variable)
+        }
+    });
+
+    let mut done = false;
+    object.as_array_mut(&mut |array| {
+        if let Some(array) = array {
+            done = true;
+            for i in 0..array.reflect_len() {
+                if let Some(item) = array.reflect_index_mut(i) {
+                    visit_variables_mut(&format!("{path}[{i}]"), item, func);
+                }
+            }
+        }
+    });
+    if done {
+        return;
+    }
+
+    object.as_hash_map_mut(&mut |map| {
+        if let Some(map) = map {
+            done = true;
+            for i in 0..map.reflect_len() {
+                let key = map.reflect_get_at(i).and_then(|(key, _)| key_string(key));
+                if let (Some(key), Some(value)) = (key, map.reflect_get_nth_value_mut(i)) {
+                    visit_variables_mut(&format!("{path}[{key}]"), value, func);
+                }
+            }
+        }
+    });
+    if done {
+        return;
+    }
+
+    for name in field_names(object) {
+        object.field_mut(&name, &mut |field| {
+            if let Some(field) = field {
+                visit_variables_mut(&field_path(path, &name), field, func);
+            }
+        });
+    }
+}
+
+fn field_names(object: &dyn Reflect) -> Vec<String> {
+    let mut names = Vec::new();
+    object.fields_info(&mut |fields| {
+        names = fields.iter().map(|field| field.name.to_string()).collect()
+    });
+    names
+}
+
+/// `resize` is `false` inside of modified variables, lists there keep their length.
+fn inherit_recursive(
+    path: &str,
+    child: &mut dyn Reflect,
+    parent: &dyn Reflect,
+    resize: bool,
+) -> Result<(), InheritError> {
+    if concrete_type_id(child) != concrete_type_id(parent) {
+        return Err(InheritError::TypesMismatch {
+            path: path.to_string(),
+            left_type: child.type_name(),
+            right_type: parent.type_name(),
+        });
+    }
+
+    let mut result = None;
+    child.as_inheritable_variable_mut(&mut |variable| {
+        if let Some(variable) = variable {
+            result = Some(if variable.is_modified() {
+                inherit_recursive(path, variable.inner_value_mut(), parent, false)
+            } else {
+                inherit_variable(path, variable, parent)
+            });
+        }
+    });
+    if let Some(result) = result {
+        return result;
+    }
+
+    child.as_array_mut(&mut |child_array| {
+        let Some(child_array) = child_array else {
+            return;
+        };
+        parent.as_array(&mut |parent_array| {
+            let Some(parent_array) = parent_array else {
+                return;
+            };
+            let mut array_result = Ok(());
+            for i in 0..child_array.reflect_len().min(parent_array.reflect_len()) {
+                if let (Some(a), Some(b)) = (
+                    child_array.reflect_index_mut(i),
+                    parent_array.reflect_index(i),
+                ) {
+                    array_result = inherit_recursive(&format!("{path}[{i}]"), a, b, resize);
+                    if array_result.is_err() {
+                        break;
+                    }
+                }
+            }
+            result = Some(array_result);
+        })
+    });
+    if let Some(result) = result {
+        result?;
+        return if resize {
+            resize_list(path, child, parent)
+        } else {
+            Ok(())
+        };
+    }
+
+    child.as_hash_map_mut(&mut |child_map| {
+        let Some(child_map) = child_map else {
+            return;
+        };
+        parent.as_hash_map(&mut |parent_map| {
+            let Some(parent_map) = parent_map else {
+                return;
+            };
+            let mut map_result = Ok(());
+            for i in 0..child_map.reflect_len() {
+                let Some((key, value)) = child_map.reflect_get_at_mut(i) else {
+                    continue;
+                };
+                parent_map.reflect_get(key, &mut |parent_value| {
+                    if let Some(parent_value) = parent_value {
+                        let key = key_string(key).unwrap_or_else(|| format!("{key:?}"));
+                        map_result = inherit_recursive(
+                            &format!("{path}[{key}]"),
+                            value,
+                            parent_value,
+                            resize,
+                        );
+                    }
+                });
+                if map_result.is_err() {
+                    break;
+                }
+            }
+            result = Some(map_result);
+        })
+    });
+    if let Some(result) = result {
+        return result;
+    }
+
+    for name in field_names(child) {
+        let mut field_result = Ok(());
+        child.field_mut(&name, &mut |child_field| {
+            if let Some(child_field) = child_field {
+                parent.field(&name, &mut |parent_field| {
+                    if let Some(parent_field) = parent_field {
+                        field_result = inherit_recursive(
+                            &field_path(path, &name),
+                            child_field,
+                            parent_field,
+                            resize,
+                        );
+                    }
+                })
+            }
+        });
+        field_result?;
+    }
+
+    Ok(())
+}
+
+/// Makes the child list as long as the parent list: extra items are removed, missing items are
+/// copied from the parent and variables nested in them become non-modified.
+fn resize_list(
+    path: &str,
+    child: &mut dyn Reflect,
+    parent: &dyn Reflect,
+) -> Result<(), InheritError> {
+    let mut result = Ok(());
+    child.as_list_mut(&mut |list| {
+        let Some(list) = list else {
+            return;
+        };
+        parent.as_array(&mut |parent_array| {
+            let Some(parent_array) = parent_array else {
+                return;
+            };
+            while list.reflect_len() > parent_array.reflect_len() {
+                list.reflect_pop();
+            }
+            for i in list.reflect_len()..parent_array.reflect_len() {
+                let Some(item) = parent_array.reflect_index(i) else {
+                    break;
+                };
+                result = copy_value(&format!("{path}[{i}]"), item).and_then(|mut copy| {
+                    mark_inheritable_properties_non_modified(&mut *copy);
+                    list.reflect_push(copy)
+                        .map_err(|copy| InheritError::TypesMismatch {
+                            path: format!("{path}[{i}]"),
+                            left_type: list.type_name(),
+                            right_type: copy.type_name(),
+                        })
+                });
+                if result.is_err() {
+                    break;
+                }
+            }
+        })
+    });
+    result
+}
+
+/// Copies a value of the parent, leaf values are copied directly, others with
+/// [`Reflect::clone_box`].
+fn copy_value(path: &str, parent: &dyn Reflect) -> Result<Box<dyn Reflect>, InheritError> {
+    let mut value = None;
+    parent.as_any(&mut |any| value = clone_leaf(any));
+    value
+        .or_else(|| parent.clone_box())
+        .ok_or_else(|| InheritError::NotCloneable {
+            path: path.to_string(),
+            type_name: parent.type_name(),
+        })
+}
+
+fn inherit_variable(
+    path: &str,
+    variable: &mut dyn ReflectInheritableVariable,
+    parent: &dyn Reflect,
+) -> Result<(), InheritError> {
+    let value = copy_value(path, parent)?;
+
+    if let Err(value) = variable.inherit_value(value) {
+        return Err(InheritError::TypesMismatch {
+            path: path.to_string(),
+            left_type: variable.type_name(),
+            right_type: value.type_name(),
+        });
+    }
+    mark_inheritable_properties_non_modified(variable.inner_value_mut());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Reflect, Clone, Debug, Default)]
+    struct Label {
+        text: InheritableVariable<String>,
+        size: InheritableVariable<f32>,
+    }
+
+    #[derive(Reflect, Debug, Default)]
+    struct Panel {
+        visible: InheritableVariable<bool>,
+        labels: Vec<Label>,
+    }
+
+    #[test]
+    fn observers_and_dirty_variables() {
+        let mut panel = Panel::default();
+        panel.labels.push(Label::default());
+        panel.labels.push(Label::default());
+        sync_dirty_variables(&mut panel, &mut |_, _| {});
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let id = panel.labels[1].text.subscribe({
+            let log = log.clone();
+            move |change| {
+                log.lock().unwrap().push(match change {
+                    VariableChange::Set { old, new } => format!("{old:?}->{new:?}"),
+                    VariableChange::Modified(new) | VariableChange::Inherited(new) => {
+                        format!("{new:?}")
+                    }
+                    VariableChange::MutableAccess => "mut".to_string(),
+                })
+            }
+        });
+
+        panel.labels[1]
+            .text
+            .set_value_and_mark_modified("Hello".to_string());
+        panel.labels[1].text.push('!');
+        panel.labels[1]
+            .text
+            .set(Box::new("Bye".to_string()))
+            .unwrap();
+        *panel.visible = false;
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["\"\"->\"Hello\"", "mut", "\"Bye\""]
+        );
+
+        let mut dirty = Vec::new();
+        gather_dirty_variables(&panel, &mut |path, variable| {
+            assert!(variable.is_modified());
+            dirty.push(path.to_string());
+        });
+        assert_eq!(dirty, ["visible", "labels[1].text"]);
+
+        let mut synced = Vec::new();
+        sync_dirty_variables(&mut panel, &mut |path, value| {
+            synced.push(format!("{path}={value:?}"))
+        });
+        assert_eq!(synced, ["visible=false", "labels[1].text=\"Bye\""]);
+        gather_dirty_variables(&panel, &mut |path, _| panic!("{path} is still dirty"));
+
+        assert!(panel.labels[1].text.unsubscribe(id));
+        assert!(!panel.labels[1].text.unsubscribe(id));
+        panel.labels[1].text.set_value_and_mark_modified(String::new());
+        assert_eq!(log.lock().unwrap().len(), 3);
+
+        // Mutable accessors are silent, only writes through reflection mark the variable.
+        let mut label = InheritableVariable::new_non_modified(Label::default());
+        let changes = Arc::new(Mutex::new(0));
+        label.subscribe({
+            let changes = changes.clone();
+            move |_| *changes.lock().unwrap() += 1
+        });
+        label.field_mut("size", &mut |field| assert!(field.is_some()));
+        label.fields_mut(&mut |fields| assert!(!fields.is_empty()));
+        assert!(!label.is_modified() && !label.need_sync());
+        assert_eq!(*changes.lock().unwrap(), 0);
+        label.set_field("size", Box::new(2.0f32), &mut |result| {
+            assert!(result.is_ok())
+        });
+        assert!(label.is_modified() && label.need_sync());
+        assert_eq!(*changes.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn inheritance_copies_non_modified_variables() {
+        let mut prefab = Panel {
+            visible: true.into(),
+            labels: vec![Label::default(), Label::default()],
+        };
+        prefab.labels[0]
+            .text
+            .set_value_and_mark_modified("Title".to_string());
+        prefab.labels[1].size.set_value_and_mark_modified(16.0);
+
+        let mut instance = Panel {
+            visible: InheritableVariable::new_non_modified(false),
+            labels: vec![Label::default(), Label::default()],
+        };
+        mark_inheritable_properties_non_modified(&mut instance);
+        instance.labels[1].size.set_value_and_mark_modified(24.0);
+
+        let inherited = Arc::new(Mutex::new(false));
+        instance.visible.subscribe({
+            let inherited = inherited.clone();
+            move |change| {
+                *inherited.lock().unwrap() = matches!(change, VariableChange::Inherited(true))
+            }
+        });
+
+        try_inherit(&mut instance, &prefab).unwrap();
+        assert!(*instance.visible && *inherited.lock().unwrap());
+        assert!(!instance.visible.is_modified() && instance.visible.need_sync());
+        assert_eq!(*instance.labels[0].text, "Title");
+        assert_eq!(*instance.labels[1].size, 24.0);
+
+        // Lists are resized to the length of the parent list.
+        prefab.labels.push(Label::default());
+        prefab.labels[2].size.set_value_and_mark_modified(8.0);
+        prefab.labels[0]
+            .text
+            .set_value_and_mark_modified("Other".to_string());
+        try_inherit(&mut instance, &prefab).unwrap();
+        assert_eq!(instance.labels.len(), 3);
+        assert_eq!(*instance.labels[0].text, "Other");
+        assert_eq!(*instance.labels[1].size, 24.0);
+        assert!(*instance.labels[2].size == 8.0 && !instance.labels[2].size.is_modified());
+        prefab.labels.truncate(1);
+        try_inherit(&mut instance, &prefab).unwrap();
+        assert_eq!(instance.labels.len(), 1);
+
+        let error = try_inherit(&mut InheritableVariable::new_non_modified(1u32), &1.0f32);
+        assert!(matches!(error, Err(InheritError::TypesMismatch { .. })));
+    }
+}
@@ -51,5 +51,12 @@ mod tests {
         let uid4 = UUID::create_string("{67452301-EFCD-5B89-98BA-DCFE10325471}");
         let uidstr4 = uid4.to_string(true, true);
         println!("4 string to uuid random:{0}", uidstr4);
+
+        assert_eq!(UUID::parse(&uidstr4), Some(uid4));
+        assert_eq!(UUID::parse(&uid1.to_string(false, false)), Some(uid1));
+        assert_eq!(UUID::parse(""), Some(UUID::create_null()));
+        assert_eq!(UUID::parse("{f094d7d4-e168-4a3d-89f3}"), None);
+        assert_eq!(UUID::parse("f094d7d4xe168-4a3d-89f3-ae3b83b1f5db"), None);
+        assert_eq!(UUID::parse("g094d7d4e1684a3d89f3ae3b83b1f5db"), None);
     }
 }
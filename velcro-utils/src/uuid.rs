@@ -51,7 +51,7 @@ fn get_value(c: char) -> u8 {
 
 
 // PartialEq 是否相等
-#[derive(Debug,Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub struct UUID {
     _data: [u8; 16]
 }
@@ -145,6 +145,50 @@ impl UUID {
         return id;
     }
 
+    //=========================================================================
+    // parse 解析 UUID 字符串, 格式错误时返回 None 而不是断言
+    // 接受的格式与 create_string 相同: 32 个十六进制数字, 可以带有分隔符
+    // (xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx) 和大括号, 空字符串解析为空 UUID
+    //=========================================================================
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut bytes = s.as_bytes();
+        if bytes.is_empty() {
+            return Some(UUID::create_null());
+        }
+        if let [b'{', inner @ .., b'}'] = bytes {
+            bytes = inner;
+        }
+
+        let has_dashes = match bytes.len() {
+            32 => false,
+            36 => true,
+            _ => return None,
+        };
+
+        let mut data = [0u8; 16];
+        let mut digit = 0;
+        for (position, &c) in bytes.iter().enumerate() {
+            if has_dashes && matches!(position, 8 | 13 | 18 | 23) {
+                if c != b'-' {
+                    return None;
+                }
+                continue;
+            }
+
+            let value = match c {
+                b'0'..=b'9' => c - b'0',
+                b'a'..=b'f' => c - b'a' + 10,
+                b'A'..=b'F' => c - b'A' + 10,
+                _ => return None,
+            };
+            data[digit / 2] = data[digit / 2] << 4 | value;
+            digit += 1;
+        }
+
+        Some(UUID { _data: data })
+    }
+
     #[allow(dead_code)]
     pub fn create_string_permissive(s: &str, skip_warnings: bool) -> Self {
         const MAX_PERMISSIVE_STRING_SIZE:usize = 60;
@@ -363,21 +407,6 @@ impl ops::Add for UUID {
 }
 
 
-impl PartialEq<Self> for UUID {
-    fn eq(&self, rhs: &Self) -> bool { 
-        let lv64h: u64 = unsafe { read_unaligned(self._data.as_ptr().cast::<u64>())};
-        let lv64l: u64 = unsafe { read_unaligned(self._data[8..].as_ptr().cast::<u64>())};
-
-        let rv64h: u64 = unsafe { read_unaligned(rhs._data.as_ptr().cast::<u64>())};
-        let rv64l: u64 = unsafe { read_unaligned(rhs._data[8..].as_ptr().cast::<u64>())};
-
-         if lv64h != rv64h || lv64l != rv64l {
-            return false;
-         }
-         return true;
-    }
-}
-
 impl PartialOrd for UUID {
     fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
         return Some(self._data.cmp(&rhs._data));